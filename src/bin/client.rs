@@ -1,7 +1,136 @@
 // Analizar el manejo estricto de buffers y el lavado (flush) de la salida estandar
-use std::io::{self, Read, Write};
+// El CLI habla RESP con el servidor: cada argumento viaja con su largo, así que los valores
+// pueden tener espacios, saltos de línea o bytes binarios (escribiéndolos "entre comillas\x00").
+use std::io::{self, BufRead, BufReader, Write};
 use std::net::TcpStream;
 
+// Separa la línea en argumentos: "comillas dobles" con escapes (\n \r \t \\ \" \xHH) o palabras sueltas
+fn split_args(line: &str) -> Result<Vec<Vec<u8>>, String> {
+    let bytes = line.as_bytes();
+    let mut args = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+        let mut arg = Vec::new();
+        if bytes[pos] == b'"' {
+            pos += 1;
+            loop {
+                match bytes.get(pos) {
+                    None => return Err("comillas sin cerrar".to_string()),
+                    Some(b'"') => break,
+                    Some(b'\\') => {
+                        let escaped = *bytes.get(pos + 1).ok_or("escape incompleto")?;
+                        pos += 2;
+                        match escaped {
+                            b'n' => arg.push(b'\n'),
+                            b'r' => arg.push(b'\r'),
+                            b't' => arg.push(b'\t'),
+                            b'x' => {
+                                let hex = bytes
+                                    .get(pos..pos + 2)
+                                    .and_then(|h| std::str::from_utf8(h).ok())
+                                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                                    .ok_or("escape \\x inválido")?;
+                                arg.push(hex);
+                                pos += 2;
+                            }
+                            other => arg.push(other),
+                        }
+                    }
+                    Some(byte) => {
+                        arg.push(*byte);
+                        pos += 1;
+                    }
+                }
+            }
+            pos += 1;
+        } else {
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                arg.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+// *N\r\n $len\r\n arg\r\n ...
+fn encode_resp(args: &[Vec<u8>]) -> Vec<u8> {
+    let mut msg = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        msg.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        msg.extend_from_slice(arg);
+        msg.extend_from_slice(b"\r\n");
+    }
+    msg
+}
+
+// Los bytes que no son texto imprimible se muestran escapados (\xHH) para no romper la terminal
+fn printable(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text)
+            if !text
+                .chars()
+                .any(|c| c.is_control() && c != '\n' && c != '\t') =>
+        {
+            text.to_string()
+        }
+        _ => bytes
+            .iter()
+            .flat_map(|b| std::ascii::escape_default(*b))
+            .map(char::from)
+            .collect(),
+    }
+}
+
+// Lee UNA respuesta RESP completa y la deja lista para mostrar
+fn read_reply(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "socket cerrado",
+        ));
+    }
+    let line = String::from_utf8_lossy(&line).trim_end().to_string();
+    let (kind, rest) = line.split_at(line.len().min(1));
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "respuesta RESP inválida");
+    match kind {
+        "+" | "-" => Ok(rest.to_string()),
+        ":" => Ok(format!("(integer) {}", rest)),
+        "$" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok("NULL".to_string());
+            }
+            let mut data = vec![0u8; len as usize + 2];
+            reader.read_exact(&mut data)?;
+            data.truncate(len as usize);
+            Ok(printable(&data))
+        }
+        "*" => {
+            let len: i64 = rest.parse().map_err(|_| invalid())?;
+            if len < 0 {
+                return Ok("NULL".to_string());
+            }
+            let mut items = Vec::new();
+            for i in 0..len {
+                // Los arreglos anidados (HISTORY) se indentan debajo de su número
+                let label = format!("{}) ", i + 1);
+                let item =
+                    read_reply(reader)?.replace('\n', &format!("\n{}", " ".repeat(label.len())));
+                items.push(format!("{}{}", label, item));
+            }
+            Ok(items.join("\n"))
+        }
+        _ => Err(invalid()),
+    }
+}
+
 fn main() {
     println!("🔌 CHRONOS CLI v0.1.0 [Auditoría de Red Activa]");
     println!("   Estableciendo enlace TCP con el nodo central...");
@@ -21,6 +150,14 @@ fn main() {
         }
     };
 
+    // Las respuestas se leen con buffer: una respuesta grande puede llegar en varios pedazos
+    let mut reader = match stream.try_clone() {
+        Ok(s) => BufReader::new(s),
+        Err(e) => {
+            eprintln!("   ❌ No se pudo preparar la lectura del socket: {}", e);
+            return;
+        }
+    };
     let mut input = String::new();
 
     // 2. Bucle de Eventos (Event Loop)
@@ -52,28 +189,29 @@ fn main() {
                     break;
                 }
 
-                // Inyectamos el salto de línea requerido por nuestro Parser
-                let mut msg_to_send = command.to_string();
-                msg_to_send.push('\n');
+                let args = match split_args(command) {
+                    Ok(args) => args,
+                    Err(e) => {
+                        eprintln!("❌ Comando mal formado: {}", e);
+                        continue;
+                    }
+                };
 
                 // 4. Transmisión de Datos por Red
-                if stream.write_all(msg_to_send.as_bytes()).is_err() {
+                if stream.write_all(&encode_resp(&args)).is_err() {
                     eprintln!("❌ Anomalía detectada: Enlace TCP interrumpido por el host remoto.");
                     break;
                 }
 
                 // 5. Recepción y Decodificación de la Respuesta
-                let mut buffer = [0; 512];
-                match stream.read(&mut buffer) {
-                    Ok(bytes_read) => {
-                        if bytes_read == 0 {
-                            eprintln!("❌ Anomalía detectada: El ervidor cerró el socket silenciosamente.");
-                            break;
-                        }
-                        let response = String::from_utf8_lossy(&buffer[..bytes_read]);
-                        println!("{}", response);
+                match read_reply(&mut reader) {
+                    Ok(response) => println!("{}", response),
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                        eprintln!(
+                            "❌ Anomalía detectada: El ervidor cerró el socket silenciosamente."
+                        );
+                        break;
                     }
-
                     Err(e) => {
                         eprintln!("❌ Error de I/O leyendo el flujo de red: {}", e);
                         break;
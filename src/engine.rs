@@ -1,78 +1,1340 @@
 // Este es el CEREBRO (El motor LSM)
 // Archivo dedicado exclusivamente a manejar el almacenamiento, los archivos y la memoria.
 // Nada de INTERNET. Nada de TCP. ---->>> SOLO DATOS!!
+//
+// Camino de una escritura:  WAL (log) -> MemTable -> (se llena) -> congelada -> SSTable
+// Camino de una lectura:    MemTable -> MemTable congelada -> SSTables (de la más nueva a la más vieja)
 
-use std::collections::HashMap;
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufRead, BufReader, Write};
+mod batch;
+mod block;
+mod bloom;
+mod cache;
+mod compaction;
+mod compaction_filter;
+mod compactor;
+mod conditional;
+mod durability;
+mod entry;
+mod expiry;
+mod history;
+mod key;
+mod keyspace;
+mod manifest;
+mod memtable;
+mod merge;
+mod merge_operator;
+mod mutate;
+mod range_delete;
+mod retention;
+mod scan;
+mod shared_wal;
+mod snapshot;
+mod sstable;
+mod stats;
+#[cfg(test)]
+mod testing;
+mod transaction;
+mod version;
+mod wal;
 
-pub const DB_PATH: &str = "chronos_v3.db";
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cache::BlockCache;
+use compaction::{CompactionJob, CompactionOutput, CompactionProgress};
+use entry::Entry;
+use manifest::Manifest;
+use memtable::MemTable;
+use merge::MergeIter;
+use range_delete::{RangeTombstone, RangeTombstones};
+use retention::Retention;
+use scan::Source;
+use shared_wal::SharedWal;
+use snapshot::SnapshotList;
+use sstable::{Table, TableBuilder, TableOptions};
+use stats::Stats;
+use version::{Version, VersionEdit, NUM_LEVELS};
+
+pub use batch::WriteBatch;
+pub use compaction::{strategy_from_name, CompactionStrategy, Leveled};
+pub use compaction_filter::{CompactionFilter, PrefixFilter};
+pub use compactor::Compactor;
+pub use conditional::{CasOutcome, Expected};
+pub use durability::{Durability, GroupCommit};
+pub use expiry::{ExpirySweeper, Ttl};
+pub use history::{HistoryPage, HistoryQuery};
+pub use keyspace::{Keyspace, KeyspaceSettings, Keyspaces};
+pub use merge_operator::{merge_operator_from_name, MergeOperator};
+pub use retention::RetentionPolicy;
+pub use scan::{DbIter, ScanPage, ScanRange};
+pub use snapshot::Snapshot;
+pub use transaction::{CommitOutcome, Transaction};
+
+// Ahora es una CARPETA: adentro viven los logs (.log), las SSTables (.sst) y el MANIFEST
+// (los de los keyspaces creados con KEYSPACE CREATE viven en subcarpetas, ver keyspace.rs)
+pub const DB_PATH: &str = "chronos_data";
+
+// Cuando la memtable pasa este tamaño, se congela y se vuelca a una SSTable
+pub const DEFAULT_MEMTABLE_SIZE: usize = 4 * 1024 * 1024;
+
+pub struct EngineOptions {
+    pub memtable_size: usize,
+    // Tamaño objetivo de cada SSTable que produce la compactación
+    pub target_file_size: u64,
+    // Tamaño de cada bloque de datos de una SSTable (la unidad que se lee del disco y se cachea)
+    pub block_size: usize,
+    // Presupuesto en bytes del caché LRU de bloques compartido por todas las SSTables
+    pub block_cache_size: usize,
+    // Bits por llave del filtro de Bloom de cada SSTable (0 = sin filtro). 10 bits ~ 1% de falsos positivos
+    pub bloom_bits_per_key: usize,
+    // Qué compactar y cuándo (leveled, size-tiered, time-window...)
+    pub compaction_strategy: Box<dyn CompactionStrategy>,
+    // Cuándo hacemos fsync del WAL (ver durability.rs)
+    pub durability: Durability,
+    // Cuánta historia se garantiza para los viajes en el tiempo (GETAT) en las llaves que no tienen
+    // una política de retención propia: una versión pisada se puede tirar recién cuando la que la
+    // pisó es más vieja que esto, y GETAT/HISTORY de antes de la ventana contestan con un error.
+    // None = toda la historia, para siempre (salvo lo que recorten las reglas de RETENTION).
+    pub history_retention: Option<Duration>,
+    // Cómo se combinan los operandos de MERGE (ver merge_operator.rs). None = MERGE no se acepta.
+    pub merge_operator: Option<Arc<dyn MergeOperator>>,
+    // Se le pregunta por cada valor que reescribe la compactación (ver compaction_filter.rs)
+    pub compaction_filter: Option<Arc<dyn CompactionFilter>>,
+}
+
+impl EngineOptions {
+    pub(crate) fn table_options(&self) -> TableOptions {
+        TableOptions {
+            block_size: self.block_size,
+            bloom_bits_per_key: self.bloom_bits_per_key,
+        }
+    }
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            memtable_size: DEFAULT_MEMTABLE_SIZE,
+            target_file_size: 2 * 1024 * 1024,
+            block_size: 4 * 1024,
+            block_cache_size: 8 * 1024 * 1024,
+            bloom_bits_per_key: 10,
+            compaction_strategy: Box::new(Leveled::default()),
+            durability: Durability::Always,
+            history_retention: None,
+            merge_operator: None,
+            compaction_filter: None,
+        }
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
 
 // Le decimos a Rust que esta estructura es pública
 pub struct Engine {
-    map: HashMap<String, String>,
-    log_file: File,
+    dir: PathBuf,
+    options: EngineOptions,
+    memtable: MemTable,
+    // Memtable congelada: ya no acepta escrituras, está esperando a convertirse en SSTable
+    immutable: Option<MemTable>,
+    // Qué SSTables están vivas y en qué nivel (siempre coincide con lo anotado en el MANIFEST)
+    version: Version,
+    manifest: Manifest,
+    block_cache: Arc<BlockCache>,
+    stats: Arc<Stats>,
+    // Compartido con la compactación de fondo, que reserva ids para sus tablas sin el candado
+    next_file_id: Arc<AtomicU64>,
+    // Hay un trabajo de compactación en vuelo (a lo sumo uno) y cómo viene
+    compacting: bool,
+    progress: Arc<CompactionProgress>,
+    compactor: Option<Arc<Compactor>>,
+    // El WAL es uno solo para todos los keyspaces del servidor (ver shared_wal.rs)
+    wal: Arc<SharedWal>,
+    // El id de este keyspace (el que llevan sus registros en el WAL; 0 = el de por defecto)
+    keyspace: u32,
+    // El log en el que arrancó la memtable activa: lo de este keyspace en logs anteriores ya está
+    // en la congelada o en SSTables (es lo que se anota en el MANIFEST al terminar el volcado)
+    memtable_log: u64,
+    // Se borró el keyspace (KEYSPACE DROP): no acepta más escrituras y sus hilos terminan
+    closed: bool,
+    // Cada escritura lleva un número de secuencia creciente (también queda anotado en el WAL).
+    // Las secuencias son globales: es la de la última escritura de ESTE keyspace.
+    last_sequence: u64,
+    // Momento (ms) de la última escritura: nunca retrocede, aunque el reloj del sistema lo haga
+    last_time: u64,
+    // (vencimiento, llave) de lo escrito con EXPIRE/SETEX: la agenda del barrendero (ver expiry.rs)
+    expiring: BTreeSet<(u64, Vec<u8>)>,
+    // Las lápidas de rango vivas (DELRANGE/DELPREFIX): toda lectura se fija si la tapan
+    ranges: RangeTombstones,
+    // Políticas de retención por prefijo (anotadas en el MANIFEST)
+    retention: Retention,
+    // Snapshots vivos: la compactación no puede tirar las versiones que ellos todavía ven
+    snapshots: Arc<SnapshotList>,
 }
 
 impl Engine {
-    pub fn new(filepath: &str) -> io::Result<Self> {
-        let path = filepath.to_string();
-        let file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .append(true)
-            .open(&path)?;
-
-        let mut map = HashMap::new();
-        println!("   📜 Rehidratando memoria desde '{}'...", path);
-        let reader = BufReader::new(file.try_clone()?);
-
-        for line in reader.lines() {
-            if let Ok(record) = line {
-                if let Some((k, v)) = record.split_once(',') {
-                    map.insert(k.to_string(), v.to_string());
+    // Abre el keyspace `keyspace` que vive en `dir`, escribiendo en el WAL compartido
+    pub(crate) fn open(
+        dir: PathBuf,
+        options: EngineOptions,
+        keyspace: u32,
+        wal: Arc<SharedWal>,
+        block_cache: Arc<BlockCache>,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        // 1. El MANIFEST nos dice qué tablas están vivas y qué logs ya fueron volcados
+        let mut state = Manifest::recover(&dir)?;
+        let mut max_id = 0;
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if let Some(id) = name
+                .strip_suffix(".sst")
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                max_id = max_id.max(id);
+                if !state.levels.iter().any(|level| level.contains_key(&id)) {
+                    // Huérfana: salida de una compactación que nunca llegó al MANIFEST
+                    fs::remove_file(dir.join(name.as_ref()))?;
                 }
+            } else if name.ends_with(".tmp") {
+                // Restos de un volcado o compactación que se cortó a la mitad
+                fs::remove_file(dir.join(name.as_ref()))?;
+            }
+        }
+        state.next_file_id = state.next_file_id.max(max_id + 1);
+        let version = state.open_version(&dir, &block_cache)?;
+
+        // 2. Rehidratamos la memtable con lo que quedó en los logs (lo que nunca llegó a una SSTable)
+        println!("   📜 Rehidratando memoria desde '{}'...", dir.display());
+        let mut memtable = MemTable::new();
+        let mut last_sequence = state.last_sequence;
+        let mut last_time = version.max_time();
+        let records = wal.recover(keyspace, state.log_id)?;
+        let recovered = records.len();
+        for record in records {
+            last_time = last_time.max(record.time);
+            for (seq, (key, entry)) in (record.seq..).zip(record.writes) {
+                last_sequence = last_sequence.max(seq);
+                memtable.insert(&key, seq, record.time, entry);
+            }
+        }
+        wal.advance_sequence(last_sequence);
+        println!(
+            "   ✅ Memoria restaurada: {} registros recuperados del WAL, {} SSTables en disco.",
+            recovered,
+            version.num_files()
+        );
+
+        let manifest = Manifest::create(&dir, &state.snapshot())?;
+        // La agenda del barrendero y las lápidas de rango: lo que quedó en la memtable + las
+        // tablas que tienen alguno
+        let mut expiring = BTreeSet::new();
+        let mut ranges = RangeTombstones::default();
+        let mut index = |internal: &[u8], entry: &Entry| -> io::Result<()> {
+            if let Some(deadline) = entry.deadline() {
+                expiring.insert((deadline, key::user_key(internal)?));
             }
+            if let Entry::RangeDelete(end) = entry {
+                ranges.insert(RangeTombstone {
+                    start: key::user_key(internal)?,
+                    end: end.clone(),
+                    seq: key::seq(internal),
+                    time: key::time(internal),
+                });
+            }
+            Ok(())
+        };
+        for (internal, entry) in memtable.iter() {
+            index(internal, entry)?;
         }
-        println!("   ✅ Memoria restaurada: {} registros.", map.len());
-        Ok(Engine {
-            map,
-            log_file: file,
+        for table in version.levels.iter().flatten() {
+            if table.meta().expiring == 0 && table.meta().range_tombstones == 0 {
+                continue;
+            }
+            for record in table.iter() {
+                let (internal, entry) = record?;
+                index(&internal, &entry)?;
+            }
+        }
+
+        let retention = Retention::new(
+            &state.retention,
+            RetentionPolicy::history(options.history_retention),
+        );
+        let mut engine = Engine {
+            wal,
+            keyspace,
+            memtable_log: state.log_id,
+            closed: false,
+            next_file_id: Arc::new(AtomicU64::new(state.next_file_id)),
+            compacting: false,
+            progress: Arc::new(CompactionProgress::default()),
+            compactor: None,
+            dir,
+            options,
+            memtable,
+            immutable: None,
+            version,
+            manifest,
+            block_cache,
+            stats: Arc::new(Stats::default()),
+            last_sequence,
+            last_time,
+            expiring,
+            ranges,
+            retention,
+            snapshots: Arc::new(SnapshotList::default()),
+        };
+        if engine.memtable.approximate_size() >= engine.options.memtable_size {
+            engine.flush()?;
+        }
+        Ok(engine)
+    }
+
+    // Devuelve la secuencia de la escritura: con `Durability::Always` el que llama tiene que esperar
+    // `Keyspaces::group_commit().wait(seq)` (idealmente soltando antes el candado) para darla por durable
+    pub fn set(&mut self, key: &[u8], value: &[u8]) -> io::Result<u64> {
+        self.write(key, Entry::Value(value.to_vec()))
+    }
+
+    // Borrar = escribir una lápida (mismo contrato de durabilidad que `set`)
+    pub fn delete(&mut self, key: &[u8]) -> io::Result<u64> {
+        self.write(key, Entry::Tombstone)
+    }
+
+    // DELRANGE: borra todas las llaves de [start, end) (end None = hasta el final) con UNA sola
+    // escritura, sin importar cuántas sean (ver range_delete.rs). Mismo contrato que `set`.
+    pub fn delete_range(&mut self, start: &[u8], end: Option<&[u8]>) -> io::Result<u64> {
+        if end.is_some_and(|end| end <= start) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "rango inválido: start tiene que ser menor que end",
+            ));
+        }
+        self.write(start, Entry::RangeDelete(end.map(<[u8]>::to_vec)))
+    }
+
+    // DELPREFIX: todas las llaves que empiezan con `prefix`
+    pub fn delete_prefix(&mut self, prefix: &[u8]) -> io::Result<u64> {
+        let end = range_delete::prefix_end(prefix);
+        self.write(prefix, Entry::RangeDelete(end))
+    }
+
+    // SETEX: un valor que deja de existir en `deadline` (ms desde epoch)
+    pub fn set_with_deadline(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        deadline: u64,
+    ) -> io::Result<u64> {
+        self.write(key, Entry::Expiring(value.to_vec(), deadline))
+    }
+
+    // EXPIRE: le pone vencimiento a una llave que existe reescribiéndola como versión nueva.
+    // Un vencimiento que ya pasó la borra. None = la llave no existe (no se escribe nada).
+    pub fn expire_at(&mut self, key: &[u8], deadline: u64) -> io::Result<Option<u64>> {
+        let now = now_millis();
+        let value = match self.get(key)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        if deadline <= now {
+            return self.write(key, Entry::Tombstone).map(Some);
+        }
+        self.write(key, Entry::Expiring(value, deadline)).map(Some)
+    }
+
+    // PERSIST: le saca el vencimiento. None = no existe o no tenía vencimiento.
+    pub fn persist(&mut self, key: &[u8]) -> io::Result<Option<u64>> {
+        match self.lookup_resolved(key, self.last_sequence)? {
+            Some((_, Entry::Expiring(value, deadline))) if deadline > now_millis() => {
+                self.write(key, Entry::Value(value)).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn ttl(&self, key: &[u8]) -> io::Result<Ttl> {
+        let now = now_millis();
+        Ok(match self.lookup_resolved(key, self.last_sequence)? {
+            Some((_, Entry::Value(_))) => Ttl::Persistent,
+            Some((_, Entry::Expiring(_, deadline))) if deadline > now => {
+                Ttl::ExpiresIn(Duration::from_millis(deadline - now))
+            }
+            _ => Ttl::Missing,
         })
     }
 
-    pub fn set(&mut self, key: &str, value: &str) -> io::Result<()> {
-        self.map.insert(key.to_string(), value.to_string());
-        writeln!(self.log_file, "{},{}", key, value)?;
-        Ok(())
+    // Una tanda del barrendero: revisa hasta `max` llaves de la agenda ya vencidas y les escribe
+    // una lápida si de verdad siguen vencidas. Devuelve (revisadas, borradas).
+    pub fn evict_expired(&mut self, max: usize) -> io::Result<(usize, usize)> {
+        let now = now_millis();
+        let (mut sampled, mut evicted) = (0, 0);
+        while sampled < max {
+            match self.expiring.first() {
+                Some((deadline, _)) if *deadline <= now => {}
+                _ => break,
+            }
+            let (_, key) = self.expiring.pop_first().unwrap();
+            sampled += 1;
+            // La agenda puede estar vieja: la llave se reescribió, se borró o cambió su vencimiento
+            if let Some((_, entry)) = self.lookup_resolved(&key, self.last_sequence)? {
+                if entry.deadline().is_some_and(|deadline| deadline <= now) {
+                    self.write(&key, Entry::Tombstone)?;
+                    Stats::bump(&self.stats.expired_keys);
+                    evicted += 1;
+                }
+            }
+        }
+        Ok((sampled, evicted))
+    }
+
+    // Aplica el lote entero de una: UN registro en el WAL y todas sus versiones en la memtable.
+    // Devuelve la secuencia de la última escritura (la que hay que esperar para el fsync).
+    pub fn write_batch(&mut self, batch: WriteBatch) -> io::Result<u64> {
+        if batch.is_empty() {
+            return Ok(self.last_sequence);
+        }
+        self.apply(batch.into_writes())
+    }
+
+    fn write(&mut self, key: &[u8], entry: Entry) -> io::Result<u64> {
+        self.apply(vec![(key.to_vec(), entry)])
+    }
+
+    fn apply(&mut self, writes: Vec<(Vec<u8>, Entry)>) -> io::Result<u64> {
+        if self.closed {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "el keyspace fue borrado",
+            ));
+        }
+        // Primero el log (durabilidad), después la memoria. El WAL compartido reparte las secuencias.
+        let time = now_millis().max(self.last_time);
+        let record = self.wal.append(self.keyspace, time, writes)?;
+        let first = record.seq;
+        let last = first + record.writes.len() as u64 - 1;
+        self.last_sequence = last;
+        self.last_time = time;
+        for (seq, (key, entry)) in (first..).zip(record.writes) {
+            if let Some(deadline) = entry.deadline() {
+                self.expiring.insert((deadline, key.clone()));
+            }
+            if let Entry::RangeDelete(end) = &entry {
+                self.ranges.insert(RangeTombstone {
+                    start: key.clone(),
+                    end: end.clone(),
+                    seq,
+                    time,
+                });
+            }
+            self.memtable.insert(&key, seq, time, entry);
+        }
+
+        // Se vuelca recién con el lote entero adentro: nunca queda repartido entre dos tablas
+        if self.memtable.approximate_size() >= self.options.memtable_size {
+            self.flush()?;
+        }
+        Ok(last)
+    }
+
+    // La versión de la llave (secuencia de su escritura viva más nueva; 0 = no existe)
+    pub fn version(&self, key: &[u8]) -> io::Result<u64> {
+        Ok(self.current(key)?.map_or(0, |(version, _)| version))
+    }
+
+    // Escribe `value` solo si la llave cumple lo esperado (ver conditional.rs)
+    pub fn compare_and_set(
+        &mut self,
+        key: &[u8],
+        expected: &Expected,
+        value: &[u8],
+    ) -> io::Result<CasOutcome> {
+        let current = self.current(key)?;
+        let version = current.as_ref().map_or(0, |(version, _)| *version);
+        let matches = match expected {
+            Expected::Version(expected) => *expected == version,
+            Expected::Value(expected) => current.as_ref().is_some_and(|(_, v)| v == expected),
+        };
+        if !matches {
+            return Ok(CasOutcome::Mismatch(version));
+        }
+        self.set(key, value).map(CasOutcome::Applied)
+    }
+
+    // (versión, valor) de la llave si existe
+    fn current(&self, key: &[u8]) -> io::Result<Option<(u64, Vec<u8>)>> {
+        Ok(self
+            .lookup_resolved(key, self.last_sequence)?
+            .and_then(|(internal, entry)| {
+                entry
+                    .value_at(now_millis())
+                    .map(|value| (key::seq(&internal), value))
+            }))
+    }
+
+    // INCRBY / DECR: devuelve (secuencia, valor nuevo)
+    pub fn incr_by(&mut self, key: &[u8], delta: i64) -> io::Result<(u64, i64)> {
+        let mut result = 0;
+        let seq = self.update(key, |current| {
+            result = mutate::add_int(mutate::parse_int(current)?, delta)?;
+            Ok(result.to_string().into_bytes())
+        })?;
+        Ok((seq, result))
+    }
+
+    pub fn incr_by_float(&mut self, key: &[u8], delta: f64) -> io::Result<(u64, Vec<u8>)> {
+        let mut result = Vec::new();
+        let seq = self.update(key, |current| {
+            let value = mutate::add_float(mutate::parse_float(current)?, delta)?;
+            result = value.to_string().into_bytes();
+            Ok(result.clone())
+        })?;
+        Ok((seq, result))
+    }
+
+    // APPEND: devuelve (secuencia, largo nuevo)
+    pub fn append(&mut self, key: &[u8], suffix: &[u8]) -> io::Result<(u64, usize)> {
+        let mut len = 0;
+        let seq = self.update(key, |current| {
+            let value = [current.unwrap_or_default(), suffix].concat();
+            len = value.len();
+            Ok(value)
+        })?;
+        Ok((seq, len))
+    }
+
+    // GETSET: escribe y devuelve lo que había (como SET, le saca el vencimiento)
+    pub fn get_set(&mut self, key: &[u8], value: &[u8]) -> io::Result<(u64, Option<Vec<u8>>)> {
+        let old = self.get(key)?;
+        let seq = self.set(key, value)?;
+        Ok((seq, old))
+    }
+
+    // MERGE: anota el operando sin leer nada; se combina recién al leer o al compactar
+    // (mismo contrato de durabilidad que `set`)
+    pub fn merge(&mut self, key: &[u8], operand: &[u8]) -> io::Result<u64> {
+        let operator = self.merge_operator()?;
+        // Un operando que el operador no sabe aplicar se rechaza ahora y no en cada lectura
+        if operator.full_merge(key, None, &[operand]).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "operando inválido para el operador de merge '{}'",
+                    operator.name()
+                ),
+            ));
+        }
+        self.write(key, Entry::Merge(operand.to_vec()))
+    }
+
+    fn merge_operator(&self) -> io::Result<Arc<dyn MergeOperator>> {
+        self.options.merge_operator.clone().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "no hay operador de merge configurado (arrancar el servidor con --merge-operator)",
+            )
+        })
+    }
+
+    // Lee, calcula y escribe sin soltar el candado (ver mutate.rs). Si la llave tenía
+    // vencimiento lo conserva, como INCR y APPEND en Redis.
+    fn update(
+        &mut self,
+        key: &[u8],
+        compute: impl FnOnce(Option<&[u8]>) -> io::Result<Vec<u8>>,
+    ) -> io::Result<u64> {
+        let (current, deadline) = match self.lookup_resolved(key, self.last_sequence)? {
+            Some((_, entry)) => {
+                let deadline = entry.deadline();
+                match entry.value_at(now_millis()) {
+                    Some(value) => (Some(value), deadline),
+                    None => (None, None),
+                }
+            }
+            None => (None, None),
+        };
+        let value = compute(current.as_deref())?;
+        let entry = match deadline {
+            Some(deadline) => Entry::Expiring(value, deadline),
+            None => Entry::Value(value),
+        };
+        self.write(key, entry)
+    }
+
+    // BEGIN: una transacción que lee desde una foto de ahora y escribe en un buffer propio
+    pub fn begin(&self) -> Transaction {
+        Transaction::new(self.snapshot())
+    }
+
+    // COMMIT: si nadie tocó las llaves escritas desde el BEGIN, el buffer entero va como un lote
+    pub fn commit(&mut self, tx: Transaction) -> io::Result<CommitOutcome> {
+        for user_key in tx.written_keys() {
+            if let Some((internal, _)) = self.lookup(user_key, self.last_sequence)? {
+                if key::seq(&internal) > tx.base() {
+                    return Ok(CommitOutcome::Conflict(user_key.clone()));
+                }
+            }
+        }
+        self.write_batch(tx.into_batch())
+            .map(CommitOutcome::Committed)
+    }
+
+    pub fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.get_at_sequence(key, self.last_sequence)
+    }
+
+    // Una foto de la base tal como está ahora: sus lecturas no ven nada escrito después
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshots.acquire(self.last_sequence)
+    }
+
+    pub fn get_snapshot(&self, key: &[u8], snapshot: &Snapshot) -> io::Result<Option<Vec<u8>>> {
+        self.get_at_sequence(key, snapshot.sequence())
+    }
+
+    fn get_at_sequence(&self, key: &[u8], seq: u64) -> io::Result<Option<Vec<u8>>> {
+        Ok(self
+            .lookup_resolved(key, seq)?
+            .and_then(|(_, entry)| entry.value_at(now_millis())))
+    }
+
+    // VIAJE EN EL TIEMPO: el valor que tenía la llave en el instante `time` (ms desde epoch).
+    // Los momentos de escritura crecen con la secuencia, así que vamos de la versión más nueva
+    // hacia atrás hasta la primera escrita en o antes de `time`.
+    pub fn get_at_time(&self, key: &[u8], time: u64) -> io::Result<Option<Vec<u8>>> {
+        self.check_retained(key, time)?;
+        let mut seq = self.last_sequence;
+        while let Some((internal, entry)) = self.lookup(key, seq)? {
+            if key::time(&internal) <= time {
+                let (_, entry) = self.resolve(key, (internal, entry))?;
+                return Ok(entry.value_at(time));
+            }
+            seq = match key::seq(&internal).checked_sub(1) {
+                Some(seq) => seq,
+                None => break,
+            };
+        }
+        Ok(None)
+    }
+
+    // Antes del horizonte de la retención las versiones pisadas pueden ya no estar: un NULL o una
+    // historia con huecos parecerían la verdad, así que se contesta con un error
+    fn check_retained(&self, key: &[u8], time: u64) -> io::Result<()> {
+        let policy = self.retention.policy_for(key);
+        if time >= policy.horizon(now_millis()) {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "el instante {} es anterior a la historia que se guarda de la llave ({})",
+                time,
+                policy.describe()
+            ),
+        ))
+    }
+
+    // Las versiones de la llave escritas dentro de la ventana de la consulta, de a una página.
+    // No se junta la historia entera: se busca en el orden de las llaves internas dónde arranca la
+    // página (el cursor o el borde de la ventana) y se leen a lo sumo limit + 1 versiones.
+    pub fn history(&self, key: &[u8], query: &HistoryQuery) -> io::Result<HistoryPage> {
+        // Las secuencias que entran en la página: (after, until]
+        let mut after = match query.start {
+            0 => 0,
+            start => {
+                self.check_retained(key, start)?;
+                self.last_seq_before(key, start)?
+            }
+        };
+        let mut until = match query.end {
+            u64::MAX => u64::MAX,
+            end => self.last_seq_before(key, end.saturating_add(1))?,
+        };
+        if let Some(cursor) = query.cursor {
+            if query.reverse {
+                until = until.min(cursor.saturating_sub(1));
+            } else {
+                after = after.max(cursor);
+            }
+        }
+        if after >= until {
+            return Ok(history::page(Vec::new(), query.limit));
+        }
+
+        // De la más nueva a la más vieja es el orden de las llaves internas; al revés, hacia atrás
+        let target = key::lookup(key, if query.reverse { until } else { after });
+        let mut sources: Vec<Source> = Vec::new();
+        for memtable in std::iter::once(&self.memtable).chain(&self.immutable) {
+            let entries: Source = if query.reverse {
+                Box::new(
+                    memtable
+                        .iter_from(&target)
+                        .map(|(k, e)| Ok((k.clone(), e.clone()))),
+                )
+            } else {
+                Box::new(
+                    memtable
+                        .iter_rev_before(Some(&target))
+                        .map(|(k, e)| Ok((k.clone(), e.clone()))),
+                )
+            };
+            sources.push(entries);
+        }
+        sources.extend(self.version.versions_from(key, &target, !query.reverse));
+        let sources = if query.reverse {
+            MergeIter::new(sources)
+        } else {
+            MergeIter::reversed(sources)
+        };
+        let prefix = key::prefix(key);
+        let mut versions = Vec::new();
+        for record in sources {
+            let (internal, entry) = record?;
+            let seq = key::seq(&internal);
+            if key::user_part(&internal) != prefix.as_slice() || seq <= after || seq > until {
+                break;
+            }
+            versions.push((internal, entry));
+            if versions.len() > query.limit {
+                break;
+            }
+        }
+        // Los borrados de rango que la taparon cuentan como borrados de la llave (el que empieza
+        // justo en ella ya está entre sus versiones). Son pocos y están en memoria: se suman y se
+        // recorta otra vez, lo que pasa de limit + 1 no puede ser parte de la página.
+        for range in self.ranges.covering(key) {
+            if range.start != key && after < range.seq && range.seq <= until {
+                versions.push((key::encode(key, range.seq, range.time), Entry::Tombstone));
+            }
+        }
+        versions.sort_by_key(|(internal, _)| key::seq(internal));
+        if query.reverse {
+            versions.reverse();
+        }
+        versions.truncate(query.limit + 1);
+
+        // Cada operando de MERGE se muestra como el valor que dejó: se resuelven de la más vieja a
+        // la más nueva, cada uno sobre la versión anterior (la primera, leída con una búsqueda)
+        let shown = versions.len().min(query.limit);
+        let mut page: Vec<&mut (Vec<u8>, Entry)> = versions[..shown].iter_mut().collect();
+        if query.reverse {
+            page.reverse();
+        }
+        let mut previous: Option<Option<Entry>> = None;
+        for (internal, entry) in page {
+            if let Entry::Merge(operand) = entry {
+                let base = match previous.take() {
+                    Some(base) => base,
+                    None => match key::seq(internal).checked_sub(1) {
+                        Some(seq) => self.lookup_resolved(key, seq)?.map(|(_, entry)| entry),
+                        None => None,
+                    },
+                };
+                let operand = mem::take(operand);
+                *entry = merge_operator::resolve(
+                    self.merge_operator()?.as_ref(),
+                    key,
+                    base,
+                    &[operand],
+                    key::time(internal),
+                )?;
+            }
+            previous = Some(Some(entry.clone()));
+        }
+        Ok(history::page(versions, query.limit))
+    }
+
+    // La secuencia de la versión más nueva de la llave escrita antes de `time` (0 = ninguna).
+    // Los momentos de escritura crecen con la secuencia, así que alcanza con una búsqueda binaria
+    // sobre las secuencias: cada paso es una lectura puntual, nunca un recorrido de la historia.
+    fn last_seq_before(&self, key: &[u8], time: u64) -> io::Result<u64> {
+        let (mut lo, mut hi) = (0, self.last_sequence);
+        let mut found = 0;
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            match self.lookup(key, mid)? {
+                Some((internal, _)) if key::time(&internal) >= time => hi = mid - 1,
+                visible => {
+                    found = visible.map_or(0, |(internal, _)| key::seq(&internal));
+                    lo = mid;
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    // RECORRIDO ORDENADO: hasta `limit` llaves vivas del rango, con el cursor para seguir
+    pub fn scan(&self, range: &ScanRange, limit: usize) -> io::Result<ScanPage> {
+        scan::page(self.iter(), range, limit)
+    }
+
+    // Lo mismo de la última llave del rango hacia la primera
+    pub fn rev_scan(&self, range: &ScanRange, limit: usize) -> io::Result<ScanPage> {
+        scan::rev_page(self.iter(), range, limit)
+    }
+
+    // Un iterador en las dos direcciones (seek / seek_for_prev / next / prev) sobre la base tal
+    // como está ahora: no ve lo que se escriba después
+    pub fn iter(&self) -> DbIter<'_> {
+        DbIter::new(self, self.last_sequence, now_millis())
+    }
+
+    // Todo lo que hay (memtables + disco) desde la llave interna `target`, en orden
+    fn sources_from(&self, target: &[u8]) -> Vec<Source<'_>> {
+        let mut sources: Vec<Source> = Vec::new();
+        for memtable in std::iter::once(&self.memtable).chain(&self.immutable) {
+            sources.push(Box::new(
+                memtable
+                    .iter_from(target)
+                    .map(|(internal, entry)| Ok((internal.clone(), entry.clone()))),
+            ));
+        }
+        sources.extend(self.version.iter_from(target));
+        sources
+    }
+
+    // Todo lo que hay antes de la llave interna `target` (None = desde el final), hacia atrás
+    fn sources_before(&self, target: Option<&[u8]>) -> Vec<Source<'_>> {
+        let mut sources: Vec<Source> = Vec::new();
+        for memtable in std::iter::once(&self.memtable).chain(&self.immutable) {
+            sources.push(Box::new(
+                memtable
+                    .iter_rev_before(target)
+                    .map(|(internal, entry)| Ok((internal.clone(), entry.clone()))),
+            ));
+        }
+        sources.extend(self.version.iter_rev_before(target));
+        sources
+    }
+
+    // La versión más nueva con secuencia <= seq, con su llave interna (puede ser una lápida).
+    // Un borrado de rango más nuevo que esa versión aparece como una lápida con su secuencia.
+    fn lookup(&self, key: &[u8], seq: u64) -> io::Result<Option<(Vec<u8>, Entry)>> {
+        let found = self.newest_version(key, seq)?;
+        let below = found.as_ref().map_or(0, |(internal, _)| key::seq(internal));
+        if let Some(range) = self.ranges.newest_covering(key, below, seq) {
+            return Ok(Some((
+                key::encode(key, range.seq, range.time),
+                Entry::Tombstone,
+            )));
+        }
+        Ok(found.map(|(internal, entry)| match entry {
+            // Para la llave donde empieza, la lápida de rango es una lápida común
+            Entry::RangeDelete(_) => (internal, Entry::Tombstone),
+            entry => (internal, entry),
+        }))
+    }
+
+    fn newest_version(&self, key: &[u8], seq: u64) -> io::Result<Option<(Vec<u8>, Entry)>> {
+        // La primera versión que encontramos (de la más nueva a la más vieja) es la que vale;
+        // si es una lápida, la llave no existe aunque haya valores más viejos abajo
+        if let Some((internal, entry)) = self.memtable.get(key, seq) {
+            return Ok(Some((internal.clone(), entry.clone())));
+        }
+        let frozen = self.immutable.as_ref().and_then(|imm| imm.get(key, seq));
+        if let Some((internal, entry)) = frozen {
+            return Ok(Some((internal.clone(), entry.clone())));
+        }
+        self.version.get(key, seq, &self.stats)
+    }
+
+    // Como `lookup`, pero si la versión más nueva es un operando de MERGE la devuelve resuelta
+    fn lookup_resolved(&self, key: &[u8], seq: u64) -> io::Result<Option<(Vec<u8>, Entry)>> {
+        match self.lookup(key, seq)? {
+            Some(found) => self.resolve(key, found).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // Si `found` es un operando, junta los de abajo (del más nuevo al más viejo) hasta la base y
+    // los combina. La llave interna sigue siendo la del operando más nuevo.
+    fn resolve(&self, key: &[u8], found: (Vec<u8>, Entry)) -> io::Result<(Vec<u8>, Entry)> {
+        if !found.1.is_merge() {
+            return Ok(found);
+        }
+        let internal = found.0.clone();
+        let mut operands = Vec::new();
+        let mut since = key::time(&internal);
+        let mut below = Some(found);
+        let base = loop {
+            match below {
+                Some((older, Entry::Merge(operand))) => {
+                    operands.push(operand);
+                    since = key::time(&older);
+                    below = match key::seq(&older).checked_sub(1) {
+                        Some(seq) => self.lookup(key, seq)?,
+                        None => None,
+                    };
+                }
+                other => break other.map(|(_, entry)| entry),
+            }
+        };
+        operands.reverse();
+        let operator = self.merge_operator()?;
+        let entry = merge_operator::resolve(operator.as_ref(), key, base, &operands, since)?;
+        Ok((internal, entry))
     }
 
-    pub fn get(&self, key: &str) -> Option<&String> {
-        self.map.get(key)
+    // Congela la memtable activa y la vuelca a una SSTable nueva en L0.
+    pub fn flush(&mut self) -> io::Result<()> {
+        // Si un volcado anterior falló, la memtable congelada sigue ahí: la reintentamos primero
+        if self.closed {
+            return Ok(());
+        }
+        if self.immutable.is_none() {
+            if self.memtable.is_empty() {
+                return Ok(());
+            }
+            // Lo que escribamos de acá en adelante va a un log nuevo
+            self.memtable_log = self.wal.rotate()?;
+            self.immutable = Some(mem::replace(&mut self.memtable, MemTable::new()));
+        }
+
+        let id = self.next_file_id.fetch_add(1, AtomicOrdering::SeqCst);
+        let mut builder = TableBuilder::create(&self.dir, id, &self.options.table_options())?;
+        for (key, value) in self.immutable.as_ref().unwrap().iter() {
+            builder.add(key, value)?;
+        }
+        let mut meta = builder.finish()?;
+        meta.min_time = self.immutable.as_ref().unwrap().created_at();
+        meta.max_time = now_millis();
+        let table = Table::open(&self.dir, meta.clone(), Arc::clone(&self.block_cache))?;
+        let table = Arc::new(table);
+
+        // Anotamos en el MANIFEST: tabla nueva en L0 + los logs anteriores ya no hacen falta
+        let edit = VersionEdit {
+            new_files: vec![(0, meta)],
+            next_file_id: Some(self.next_file_id.load(AtomicOrdering::SeqCst)),
+            log_id: Some(self.memtable_log),
+            last_sequence: Some(self.last_sequence),
+            ..Default::default()
+        };
+        self.manifest.append(&edit)?;
+        self.version = self.version.apply(&edit, &[table]);
+
+        self.immutable = None;
+        // Los logs viejos ya no le hacen falta a este keyspace (quizás sí a otros: ver shared_wal.rs)
+        let pin = (!self.memtable.is_empty()).then_some(self.memtable_log);
+        self.wal.release(self.keyspace, pin)?;
+        println!("   💾 MemTable volcada a disco: SSTable #{} (L0)", id);
+
+        // Cada volcado puede dejar algún nivel excedido: lo bajamos de a un paso por vez
+        // (en segundo plano si hay un compactador enganchado; si no, acá mismo)
+        match &self.compactor {
+            Some(compactor) => compactor.notify(),
+            None => while self.compact_step(false)? {},
+        }
+        Ok(())
     }
 
+    // COMPACT manual: vuelca la memtable y hace UN paso incremental de compactación
     pub fn compact(&mut self) -> io::Result<()> {
-        let temp_path = "chronos_temp.db";
         println!("   🧹 Iniciando Compactación (Garbage Colecction)...");
+        self.flush()?;
+        if !self.compact_step(true)? {
+            println!("   ✨ Nada que compactar.");
+        }
+        Ok(())
+    }
+
+    // Devuelve true si hizo algún trabajo
+    fn compact_step(&mut self, force: bool) -> io::Result<bool> {
+        match self.pick_compaction(force) {
+            Some(job) => {
+                let output = job.run();
+                self.install_compaction(output)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
 
-        let mut temp_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(temp_path)?;
+    // Elige la próxima compactación y arma un trabajo que se puede ejecutar SIN el candado del motor.
+    // Hay a lo sumo una compactación en vuelo: mientras tanto devuelve None.
+    pub fn pick_compaction(&mut self, force: bool) -> Option<CompactionJob> {
+        if self.compacting || self.closed {
+            return None;
+        }
+        let task = self
+            .options
+            .compaction_strategy
+            .pick(&self.version, force)?;
+        self.compacting = true;
+        self.progress.start(&task);
+        Some(CompactionJob {
+            dir: self.dir.clone(),
+            table_options: self.options.table_options(),
+            target_file_size: self.options.target_file_size,
+            cache: Arc::clone(&self.block_cache),
+            stats: Arc::clone(&self.stats),
+            version: self.version.clone(),
+            task,
+            next_file_id: Arc::clone(&self.next_file_id),
+            progress: Arc::clone(&self.progress),
+            smallest_snapshot: self.snapshots.oldest().unwrap_or(self.last_sequence),
+            retention: self.retention.clone(),
+            merge_operator: self.options.merge_operator.clone(),
+            compaction_filter: self.options.compaction_filter.clone(),
+            ranges: self.ranges.clone(),
+            now: now_millis(),
+        })
+    }
 
-        for (key, value) in &self.map {
-            writeln!(temp_file, "{},{}", key, value)?;
+    // Instala el resultado de un trabajo de compactación: un append al MANIFEST y una versión nueva
+    pub fn install_compaction(&mut self, output: io::Result<CompactionOutput>) -> io::Result<()> {
+        self.compacting = false;
+        // Se borró el keyspace mientras compactábamos: no se anota nada, la carpeta se va entera
+        if self.closed {
+            self.progress.finish(false);
+            return Ok(());
+        }
+        let CompactionOutput {
+            task,
+            mut edit,
+            opened,
+            filtered,
+            dropped_ranges,
+        } = match output {
+            Ok(output) => output,
+            Err(e) => {
+                // Las tablas a medio escribir quedan huérfanas: se limpian al reabrir
+                self.progress.finish(false);
+                return Err(e);
+            }
+        };
+        edit.next_file_id = Some(self.next_file_id.load(AtomicOrdering::SeqCst));
+        if let Err(e) = self.manifest.append(&edit) {
+            self.progress.finish(false);
+            return Err(e);
+        }
+        // Mientras compactábamos pudieron llegar tablas nuevas a L0: el edit es un delta, las respeta
+        self.version = self.version.apply(&edit, &opened);
+        self.progress.finish(true);
+        for (start, seq) in &dropped_ranges {
+            self.ranges.remove(start, *seq);
         }
-        temp_file.sync_all()?;
-        fs::rename(temp_path, DB_PATH)?;
 
-        self.log_file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .append(true)
-            .open(DB_PATH)?;
-        println!("   ✨ Compactación terminada. Basura eliminada.");
+        // Recién ahora (con el MANIFEST a salvo) borramos las tablas de entrada que se reescribieron
+        let mut obsolete = 0;
+        for table in task.inputs.iter().chain(&task.next_inputs) {
+            if !opened.iter().any(|t| t.meta().id == table.meta().id) {
+                fs::remove_file(table.path())?;
+                obsolete += 1;
+            }
+        }
+        println!(
+            "   ✨ Compactación [{}] L{} -> L{}: {} tablas ({} bytes) -> {} tablas nuevas. Basura eliminada: {} archivos.",
+            self.options.compaction_strategy.name(),
+            task.level,
+            task.output_level,
+            task.inputs.len() + task.next_inputs.len(),
+            task.input_bytes(),
+            edit.new_files.len(),
+            obsolete
+        );
+        if let Some(filter) = &self.options.compaction_filter {
+            self.stats
+                .filter_removed
+                .fetch_add(filtered.removed, AtomicOrdering::Relaxed);
+            self.stats
+                .filter_replaced
+                .fetch_add(filtered.replaced, AtomicOrdering::Relaxed);
+            println!(
+                "   🧽 Filtro [{}]: {} versiones quitadas, {} reemplazadas.",
+                filter.name(),
+                filtered.removed,
+                filtered.replaced
+            );
+        }
         Ok(())
     }
+
+    // Cambia (o crea) la política de retención de un prefijo. Queda en el MANIFEST; las versiones
+    // que sobran se van en las próximas compactaciones.
+    pub fn set_retention(&mut self, prefix: &[u8], policy: RetentionPolicy) -> io::Result<()> {
+        let mut retention = self.retention.clone();
+        retention.set(prefix, policy);
+        self.install_retention(retention)
+    }
+
+    // Devuelve false si el prefijo no tenía política propia
+    pub fn remove_retention(&mut self, prefix: &[u8]) -> io::Result<bool> {
+        let mut retention = self.retention.clone();
+        if !retention.remove(prefix) {
+            return Ok(false);
+        }
+        self.install_retention(retention)?;
+        Ok(true)
+    }
+
+    fn install_retention(&mut self, retention: Retention) -> io::Result<()> {
+        self.manifest.append(&VersionEdit {
+            retention: Some(retention.rules()),
+            ..Default::default()
+        })?;
+        self.retention = retention;
+        Ok(())
+    }
+
+    pub fn retention_rules(&self) -> Vec<(Vec<u8>, RetentionPolicy)> {
+        self.retention.rules()
+    }
+
+    // La que vale para las llaves sin política propia (EngineOptions::history_retention)
+    pub fn default_retention(&self) -> RetentionPolicy {
+        self.retention.default_policy()
+    }
+
+    // La política que manda para una llave (la del prefijo más largo, o la general)
+    pub fn retention_for(&self, key: &[u8]) -> RetentionPolicy {
+        self.retention.policy_for(key)
+    }
+
+    // Desde que hay un compactador de fondo enganchado, los volcados solo le avisan
+    pub(crate) fn attach_compactor(&mut self, compactor: Arc<Compactor>) {
+        self.compactor = Some(compactor);
+    }
+
+    pub fn compactor(&self) -> Option<Arc<Compactor>> {
+        self.compactor.clone()
+    }
+
+    // KEYSPACE DROP: el motor deja de aceptar escrituras, suelta sus logs y despierta a sus hilos
+    // para que terminen. Los archivos los borra el que llama.
+    pub(crate) fn close(&mut self) -> io::Result<()> {
+        self.closed = true;
+        if let Some(compactor) = &self.compactor {
+            compactor.notify();
+        }
+        self.wal.release(self.keyspace, None)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // Cómo está configurado (para KEYSPACE LIST)
+    pub fn describe_settings(&self) -> String {
+        format!(
+            "compaction={} memtable={} merge={} history={}",
+            self.options.compaction_strategy.name(),
+            self.options.memtable_size,
+            self.options
+                .merge_operator
+                .as_ref()
+                .map_or("none", |operator| operator.name()),
+            self.retention.default_policy().describe()
+        )
+    }
+
+    // Estado del motor para el comando INFO (pares clave -> valor, estilo Redis)
+    pub fn info(&self) -> Vec<(String, String)> {
+        let commit = self.wal.group_commit();
+        let recovery = self.wal.recovery();
+        let mut info = vec![
+            ("keyspace_id".to_string(), self.keyspace.to_string()),
+            (
+                "compaction_strategy".to_string(),
+                self.options.compaction_strategy.name().to_string(),
+            ),
+            (
+                "compaction_running".to_string(),
+                (self.compacting as u8).to_string(),
+            ),
+            (
+                "compaction_progress".to_string(),
+                match self.progress.percent() {
+                    Some(percent) => format!(
+                        "L{}->L{},{:.1}%,bytes={}/{}",
+                        self.progress.level.load(AtomicOrdering::Relaxed),
+                        self.progress.output_level.load(AtomicOrdering::Relaxed),
+                        percent,
+                        self.progress.read_bytes.load(AtomicOrdering::Relaxed),
+                        self.progress.input_bytes.load(AtomicOrdering::Relaxed)
+                    ),
+                    None => "idle".to_string(),
+                },
+            ),
+            (
+                "compactions_completed".to_string(),
+                self.progress
+                    .completed
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "memtable_bytes".to_string(),
+                self.memtable.approximate_size().to_string(),
+            ),
+            ("memtable_keys".to_string(), self.memtable.len().to_string()),
+            ("sstables".to_string(), self.version.num_files().to_string()),
+            ("last_sequence".to_string(), self.last_sequence.to_string()),
+            ("snapshots".to_string(), self.snapshots.len().to_string()),
+            ("expiring_keys".to_string(), self.expiring.len().to_string()),
+            (
+                "expired_keys".to_string(),
+                self.stats
+                    .expired_keys
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "history_retention".to_string(),
+                self.retention.default_policy().describe(),
+            ),
+            (
+                "retention_rules".to_string(),
+                self.retention.rules().len().to_string(),
+            ),
+            ("durability".to_string(), commit.policy().name()),
+            ("wal_fsyncs".to_string(), commit.fsyncs().to_string()),
+            (
+                "wal_synced_sequence".to_string(),
+                commit.synced_sequence().to_string(),
+            ),
+            ("wal_logs".to_string(), self.wal.num_logs().to_string()),
+            (
+                "wal_recovered_records".to_string(),
+                recovery.recovered.to_string(),
+            ),
+            (
+                "wal_discarded_records".to_string(),
+                recovery.discarded.to_string(),
+            ),
+            (
+                "bloom_bits_per_key".to_string(),
+                self.options.bloom_bits_per_key.to_string(),
+            ),
+            (
+                "bloom_expected_fp_rate".to_string(),
+                format!(
+                    "{:.4}",
+                    bloom::expected_fp_rate(self.options.bloom_bits_per_key)
+                ),
+            ),
+            (
+                "bloom_useful".to_string(),
+                self.stats
+                    .bloom_useful
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "bloom_false_positives".to_string(),
+                self.stats
+                    .bloom_false_positives
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "bloom_fp_rate".to_string(),
+                format!("{:.4}", self.stats.bloom_fp_rate()),
+            ),
+            (
+                "tombstones_dropped".to_string(),
+                self.stats
+                    .tombstones_dropped
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "versions_dropped".to_string(),
+                self.stats
+                    .versions_dropped
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "merge_operator".to_string(),
+                self.options
+                    .merge_operator
+                    .as_ref()
+                    .map_or("none", |operator| operator.name())
+                    .to_string(),
+            ),
+            (
+                "merge_operands_resolved".to_string(),
+                self.stats
+                    .merge_operands_resolved
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "range_tombstones".to_string(),
+                self.ranges.len().to_string(),
+            ),
+            (
+                "range_tombstones_dropped".to_string(),
+                self.stats
+                    .range_tombstones_dropped
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "compaction_filter".to_string(),
+                self.options
+                    .compaction_filter
+                    .as_ref()
+                    .map_or("none", |filter| filter.name())
+                    .to_string(),
+            ),
+            (
+                "filter_removed".to_string(),
+                self.stats
+                    .filter_removed
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "filter_replaced".to_string(),
+                self.stats
+                    .filter_replaced
+                    .load(AtomicOrdering::Relaxed)
+                    .to_string(),
+            ),
+            (
+                "block_cache_capacity".to_string(),
+                self.block_cache.capacity().to_string(),
+            ),
+            (
+                "block_cache_used".to_string(),
+                self.block_cache.used_bytes().to_string(),
+            ),
+            (
+                "block_cache_hits".to_string(),
+                self.block_cache.hits().to_string(),
+            ),
+            (
+                "block_cache_misses".to_string(),
+                self.block_cache.misses().to_string(),
+            ),
+        ];
+        for level in 0..NUM_LEVELS {
+            if !self.version.levels[level].is_empty() {
+                info.push((
+                    format!("level{}", level),
+                    format!(
+                        "files={},bytes={}",
+                        self.version.levels[level].len(),
+                        self.version.level_size(level)
+                    ),
+                ));
+            }
+        }
+        info
+    }
 }
@@ -0,0 +1,104 @@
+// El LOTE de escrituras (WriteBatch, lo que hay detrás de MULTI/EXEC)
+// Varios SET/DEL que tienen que pasar TODOS o NINGUNO. El lote entero viaja en UN solo
+// registro del WAL (con su checksum), así que un corte a la mitad lo descarta completo al
+// recuperar; y se aplica a la memtable con el candado de escritura tomado, así que ninguna
+// lectura ve la mitad del lote.
+
+use super::entry::Entry;
+
+#[derive(Default)]
+pub struct WriteBatch {
+    writes: Vec<(Vec<u8>, Entry)>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        WriteBatch::default()
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.writes
+            .push((key.to_vec(), Entry::Value(value.to_vec())));
+    }
+
+    // Como SETEX: el valor deja de existir en `deadline` (ms desde epoch)
+    pub fn put_with_deadline(&mut self, key: &[u8], value: &[u8], deadline: u64) {
+        self.writes
+            .push((key.to_vec(), Entry::Expiring(value.to_vec(), deadline)));
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.writes.push((key.to_vec(), Entry::Tombstone));
+    }
+
+    pub(super) fn push(&mut self, key: Vec<u8>, entry: Entry) {
+        self.writes.push((key, entry));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    pub(super) fn into_writes(self) -> Vec<(Vec<u8>, Entry)> {
+        self.writes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+
+    use super::super::testing::{self, TempDir};
+    use super::super::wal::log_path;
+    use super::WriteBatch;
+
+    // Un corte en cualquier punto del registro del lote: al recuperar no queda NADA del lote,
+    // y lo de antes sigue entero
+    #[test]
+    fn a_batch_cut_in_half_is_dropped_whole() {
+        for cut in 0..3 {
+            let dir = TempDir::new("batch_torn");
+            let mut engine = testing::open(&dir, testing::small_options());
+            engine.set(b"a", b"1").unwrap();
+            engine.set(b"c", b"1").unwrap();
+            let last = engine.last_sequence;
+            let path = log_path(&dir, 1);
+            let start = fs::metadata(&path).unwrap().len();
+
+            let mut batch = WriteBatch::new();
+            batch.put(b"a", b"2");
+            batch.put(b"b", b"2");
+            batch.delete(b"c");
+            batch.put_with_deadline(b"d", b"2", u64::MAX);
+            engine.write_batch(batch).unwrap();
+            let end = fs::metadata(&path).unwrap().len();
+            drop(engine);
+
+            let at = [start + 3, (start + end) / 2, end - 1][cut];
+            OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_len(at)
+                .unwrap();
+
+            let mut engine = testing::open(&dir, testing::small_options());
+            assert_eq!(engine.get(b"a").unwrap(), Some(b"1".to_vec()));
+            assert_eq!(engine.get(b"b").unwrap(), None);
+            assert_eq!(engine.get(b"c").unwrap(), Some(b"1".to_vec()));
+            assert_eq!(engine.get(b"d").unwrap(), None);
+            assert_eq!(engine.last_sequence, last);
+
+            // Lo que se escribe después del corte se recupera como siempre
+            let mut batch = WriteBatch::new();
+            batch.put(b"b", b"3");
+            batch.delete(b"a");
+            engine.write_batch(batch).unwrap();
+            drop(engine);
+            let engine = testing::open(&dir, testing::small_options());
+            assert_eq!(engine.get(b"a").unwrap(), None);
+            assert_eq!(engine.get(b"b").unwrap(), Some(b"3".to_vec()));
+            assert_eq!(engine.get(b"c").unwrap(), Some(b"1".to_vec()));
+        }
+    }
+}
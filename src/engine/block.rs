@@ -0,0 +1,279 @@
+// Los BLOQUES de datos de una SSTable (~4 KB cada uno, la unidad que leemos del disco y cacheamos).
+// Como las llaves vienen ordenadas, cada llave se parece mucho a la anterior ("user:1001", "user:1002"):
+// guardamos solo la parte que CAMBIA (compresión de prefijos). Cada `RESTART_INTERVAL` entradas
+// hay un "punto de reinicio" con la llave completa, para poder hacer búsqueda binaria adentro del bloque.
+//
+// Formato de un bloque:
+//   [entradas]   shared u32 | unshared u32 | vlen u32 | key[shared..] | value
+//   [reinicios]  offset u32 (uno por punto de reinicio) | num_restarts u32
+
+use std::io;
+
+const RESTART_INTERVAL: usize = 16;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u32(raw: &[u8], pos: usize) -> io::Result<u32> {
+    raw.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| invalid("bloque de SSTable truncado"))
+}
+
+// ---------------------------------------------------------------------------------
+// ESCRITURA
+// ---------------------------------------------------------------------------------
+
+pub struct BlockBuilder {
+    buffer: Vec<u8>,
+    restarts: Vec<u32>,
+    counter: usize,
+    last_key: Vec<u8>,
+}
+
+impl BlockBuilder {
+    pub fn new() -> Self {
+        BlockBuilder {
+            buffer: Vec::new(),
+            restarts: vec![0],
+            counter: 0,
+            last_key: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: &[u8], value: &[u8]) {
+        let shared = if self.counter < RESTART_INTERVAL {
+            key.iter()
+                .zip(&self.last_key)
+                .take_while(|(a, b)| a == b)
+                .count()
+        } else {
+            // Punto de reinicio: la llave va completa
+            self.restarts.push(self.buffer.len() as u32);
+            self.counter = 0;
+            0
+        };
+        self.buffer
+            .extend_from_slice(&(shared as u32).to_le_bytes());
+        self.buffer
+            .extend_from_slice(&((key.len() - shared) as u32).to_le_bytes());
+        self.buffer
+            .extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.buffer.extend_from_slice(&key[shared..]);
+        self.buffer.extend_from_slice(value);
+
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.counter += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    // Tamaño que tendría el bloque si lo cerráramos ahora
+    pub fn estimated_size(&self) -> usize {
+        self.buffer.len() + self.restarts.len() * 4 + 4
+    }
+
+    // Cierra el bloque (agrega los reinicios) y deja el builder listo para el próximo
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut block = std::mem::take(&mut self.buffer);
+        for restart in &self.restarts {
+            block.extend_from_slice(&restart.to_le_bytes());
+        }
+        block.extend_from_slice(&(self.restarts.len() as u32).to_le_bytes());
+        self.restarts = vec![0];
+        self.counter = 0;
+        self.last_key.clear();
+        block
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// LECTURA
+// ---------------------------------------------------------------------------------
+
+// Una entrada tal como está guardada: (llave, valor)
+type KeyValue = (Vec<u8>, Vec<u8>);
+
+pub struct Block {
+    data: Vec<u8>,
+    // Dónde terminan las entradas (empiezan los offsets de reinicio)
+    restarts_offset: usize,
+    num_restarts: usize,
+}
+
+impl Block {
+    pub fn decode(data: Vec<u8>) -> io::Result<Self> {
+        if data.len() < 4 {
+            return Err(invalid("bloque de SSTable demasiado chico"));
+        }
+        let num_restarts = read_u32(&data, data.len() - 4)? as usize;
+        let restarts_offset = (data.len() - 4)
+            .checked_sub(num_restarts * 4)
+            .ok_or_else(|| invalid("reinicios de bloque corruptos"))?;
+        Ok(Block {
+            data,
+            restarts_offset,
+            num_restarts,
+        })
+    }
+
+    // Bytes que ocupa en memoria (para el presupuesto del caché)
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn restart_point(&self, i: usize) -> io::Result<usize> {
+        Ok(read_u32(&self.data, self.restarts_offset + i * 4)? as usize)
+    }
+
+    // Decodifica la entrada en `pos` reconstruyendo la llave a partir de la anterior.
+    // Devuelve (rango del valor, posición de la próxima entrada).
+    fn decode_entry(
+        &self,
+        pos: usize,
+        key: &mut Vec<u8>,
+    ) -> io::Result<(std::ops::Range<usize>, usize)> {
+        let shared = read_u32(&self.data, pos)? as usize;
+        let unshared = read_u32(&self.data, pos + 4)? as usize;
+        let vlen = read_u32(&self.data, pos + 8)? as usize;
+        let key_start = pos + 12;
+        let value_start = key_start + unshared;
+        let end = value_start + vlen;
+        if shared > key.len() || end > self.restarts_offset {
+            return Err(invalid("entrada de bloque corrupta"));
+        }
+        key.truncate(shared);
+        key.extend_from_slice(&self.data[key_start..value_start]);
+        Ok((value_start..end, end))
+    }
+
+    // La primera entrada con llave >= target (None si todas son menores), y la llave justo
+    // anterior si se la cruzó en el recorrido. Si no se cruzó ninguna, o la encontrada es la
+    // primera del bloque o es target misma (cayó justo en un punto de reinicio).
+    pub fn seek(&self, target: &[u8]) -> io::Result<(Option<Vec<u8>>, Option<KeyValue>)> {
+        // 1. Búsqueda binaria sobre los puntos de reinicio (ahí las llaves están completas):
+        //    buscamos el último reinicio cuya llave sea <= target
+        let (mut lo, mut hi) = (0, self.num_restarts);
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            let mut key = Vec::new();
+            self.decode_entry(self.restart_point(mid)?, &mut key)?;
+            if key.as_slice() <= target {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        // 2. Recorrido lineal desde ese reinicio (a lo sumo RESTART_INTERVAL entradas)
+        let mut pos = self.restart_point(lo)?;
+        let mut key = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        while pos < self.restarts_offset {
+            let (value, next) = self.decode_entry(pos, &mut key)?;
+            if key.as_slice() >= target {
+                return Ok((previous, Some((key, self.data[value].to_vec()))));
+            }
+            previous.get_or_insert_with(Vec::new).clone_from(&key);
+            pos = next;
+        }
+        Ok((previous, None))
+    }
+
+    // Todas las entradas del bloque, en orden
+    pub fn entries(&self) -> io::Result<Vec<KeyValue>> {
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        let mut key = Vec::new();
+        while pos < self.restarts_offset {
+            let (value, next) = self.decode_entry(pos, &mut key)?;
+            entries.push((key.clone(), self.data[value].to_vec()));
+            pos = next;
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, BlockBuilder, KeyValue, RESTART_INTERVAL};
+
+    // 100 llaves con mucho prefijo en común ("user:0000", "user:0002", ...): solo las pares
+    fn build() -> (Block, Vec<KeyValue>) {
+        let entries: Vec<KeyValue> = (0..100)
+            .map(|i| {
+                (
+                    format!("user:{:04}", i * 2).into_bytes(),
+                    format!("v{}", i).into_bytes(),
+                )
+            })
+            .collect();
+        let mut builder = BlockBuilder::new();
+        for (key, value) in &entries {
+            builder.add(key, value);
+        }
+        (Block::decode(builder.finish()).unwrap(), entries)
+    }
+
+    #[test]
+    fn restart_points_hold_whole_keys() {
+        let (block, entries) = build();
+        assert_eq!(block.num_restarts, entries.len().div_ceil(RESTART_INTERVAL));
+        for i in 0..block.num_restarts {
+            // Un reinicio no comparte nada con la anterior: se decodifica solo, sin contexto
+            let pos = block.restart_point(i).unwrap();
+            let shared = u32::from_le_bytes(block.data[pos..pos + 4].try_into().unwrap());
+            assert_eq!(shared, 0);
+            let mut key = Vec::new();
+            block.decode_entry(pos, &mut key).unwrap();
+            assert_eq!(key, entries[i * RESTART_INTERVAL].0);
+        }
+        // Entre reinicios solo va lo que cambia: bastante menos que las llaves enteras
+        let whole: usize = entries.iter().map(|(k, v)| 12 + k.len() + v.len()).sum();
+        assert!(block.restarts_offset < whole - entries.len() * 4);
+        assert_eq!(block.entries().unwrap(), entries);
+    }
+
+    #[test]
+    fn seek_inside_and_between_restarts() {
+        let (block, entries) = build();
+        for (i, (key, value)) in entries.iter().enumerate() {
+            // La llave exacta (al principio, al medio o al final de un tramo entre reinicios)
+            let (previous, found) = block.seek(key).unwrap();
+            assert_eq!(found, Some((key.clone(), value.clone())));
+            assert_eq!(previous.is_some(), i % RESTART_INTERVAL != 0);
+            if let Some(previous) = previous {
+                assert_eq!(previous, entries[i - 1].0);
+            }
+            // Una que no está (impar): cae en la siguiente
+            let missing = format!("user:{:04}", i * 2 + 1).into_bytes();
+            let (_, found) = block.seek(&missing).unwrap();
+            assert_eq!(
+                found.map(|(k, _)| k),
+                entries.get(i + 1).map(|(k, _)| k.clone())
+            );
+        }
+        let (previous, found) = block.seek(b"a").unwrap();
+        assert_eq!(
+            (previous, found.map(|(k, _)| k)),
+            (None, Some(entries[0].0.clone()))
+        );
+        let (previous, found) = block.seek(b"z").unwrap();
+        assert_eq!(previous, Some(entries[99].0.clone()));
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn corrupt_restarts_are_an_error() {
+        let (block, _) = build();
+        let mut raw = block.data.clone();
+        let n = raw.len();
+        raw[n - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Block::decode(raw).is_err());
+        assert!(Block::decode(vec![0, 0]).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+// El FILTRO DE BLOOM (el portero de cada SSTable)
+// Un arreglo de bits que responde "esta llave SEGURO no está" o "QUIZÁS está".
+// Si dice que no, nos ahorramos ir al disco. Si dice que quizás, puede equivocarse
+// (falso positivo), pero nunca al revés: una llave que está siempre pasa el filtro.
+//
+// Con `bits_per_key` = 10 la probabilidad de falso positivo ronda el 1%.
+// Formato serializado:  bits... | num_probes u8
+
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    num_probes: u32,
+}
+
+// FNV-1a de 64 bits + el "mezclador" final de MurmurHash3 (llaves muy parecidas como
+// "user:1" y "user:2" tienen que terminar en bits bien distintos)
+pub fn hash(key: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key {
+        h ^= *byte as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+// Cantidad de sondas óptima: k = bits_por_llave * ln(2)
+fn probes_for(bits_per_key: usize) -> u32 {
+    ((bits_per_key as f64 * 0.69) as u32).clamp(1, 30)
+}
+
+// Probabilidad teórica de falso positivo para una configuración dada
+pub fn expected_fp_rate(bits_per_key: usize) -> f64 {
+    if bits_per_key == 0 {
+        return 1.0;
+    }
+    let k = probes_for(bits_per_key) as f64;
+    (1.0 - (-k / bits_per_key as f64).exp()).powf(k)
+}
+
+impl BloomFilter {
+    pub fn build(hashes: &[u64], bits_per_key: usize) -> Self {
+        let num_probes = probes_for(bits_per_key);
+        // Con muy pocas llaves el filtro sería inútil de tan chico: mínimo 64 bits
+        let num_bits = (hashes.len() * bits_per_key).max(64);
+        let mut filter = BloomFilter {
+            bits: vec![0u8; num_bits.div_ceil(8)],
+            num_probes,
+        };
+        for h in hashes {
+            for bit in filter.probes(*h) {
+                filter.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+        filter
+    }
+
+    // Doble hashing (como LevelDB): de un solo hash sacamos k posiciones distintas
+    fn probes(&self, h: u64) -> impl Iterator<Item = usize> {
+        let num_bits = (self.bits.len() * 8) as u64;
+        let delta = h.rotate_right(33);
+        (0..self.num_probes as u64)
+            .map(move |i| (h.wrapping_add(i.wrapping_mul(delta)) % num_bits) as usize)
+    }
+
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        self.probes(hash(key))
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut raw = self.bits.clone();
+        raw.push(self.num_probes as u8);
+        raw
+    }
+
+    pub fn decode(mut raw: Vec<u8>) -> Option<Self> {
+        let num_probes = raw.pop()? as u32;
+        if raw.is_empty() || num_probes == 0 {
+            return None;
+        }
+        Some(BloomFilter {
+            bits: raw,
+            num_probes,
+        })
+    }
+}
@@ -0,0 +1,174 @@
+// El CACHÉ DE BLOQUES (LRU compartido por TODAS las SSTables abiertas)
+// Leer un bloque del disco es caro; si alguien lo pidió hace poco, es muy probable que lo vuelvan
+// a pedir. Guardamos los bloques decodificados con un presupuesto de BYTES (no de cantidad),
+// y cuando nos pasamos tiramos el que hace más tiempo que nadie usa (Least Recently Used).
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::block::Block;
+
+// (id de la SSTable, offset del bloque dentro del archivo)
+pub type BlockKey = (u64, u64);
+
+struct LruState {
+    // Bloque + "tick" del último uso
+    entries: HashMap<BlockKey, (Arc<Block>, u64)>,
+    // tick -> bloque: el primero es el menos usado recientemente
+    order: BTreeMap<u64, BlockKey>,
+    tick: u64,
+    used_bytes: usize,
+}
+
+pub struct BlockCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Self {
+        BlockCache {
+            capacity,
+            state: Mutex::new(LruState {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+                used_bytes: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: BlockKey) -> Option<Arc<Block>> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        let found = match state.entries.get_mut(&key) {
+            Some((block, last_used)) => {
+                let old = std::mem::replace(last_used, tick);
+                Some((Arc::clone(block), old))
+            }
+            None => None,
+        };
+        match found {
+            Some((block, old)) => {
+                // Lo movemos al final de la fila: recién usado
+                state.order.remove(&old);
+                state.order.insert(tick, key);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(block)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: BlockKey, block: Arc<Block>) {
+        // Un bloque más grande que todo el presupuesto no tiene sentido guardarlo
+        if block.size() > self.capacity {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        state.used_bytes += block.size();
+        if let Some((old, old_tick)) = state.entries.insert(key, (block, tick)) {
+            state.used_bytes -= old.size();
+            state.order.remove(&old_tick);
+        }
+        state.order.insert(tick, key);
+
+        // Desalojamos a los menos usados hasta volver al presupuesto
+        while state.used_bytes > self.capacity {
+            let (_, victim) = match state.order.pop_first() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if let Some((block, _)) = state.entries.remove(&victim) {
+                state.used_bytes -= block.size();
+            }
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.state.lock().unwrap().used_bytes
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::block::{Block, BlockBuilder};
+    use super::BlockCache;
+
+    // Un bloque con un valor de `payload` bytes (más lo que ocupa el formato)
+    fn block(payload: usize) -> Arc<Block> {
+        let mut builder = BlockBuilder::new();
+        builder.add(b"k", &vec![b'x'; payload]);
+        Arc::new(Block::decode(builder.finish()).unwrap())
+    }
+
+    #[test]
+    fn the_least_recently_used_block_goes_first() {
+        let size = block(100).size();
+        let cache = BlockCache::new(3 * size);
+        for offset in 0..3 {
+            cache.insert((1, offset), block(100));
+        }
+        assert_eq!(cache.used_bytes(), 3 * size);
+
+        // Usar el 0 lo salva: el que sale es el 1
+        assert!(cache.get((1, 0)).is_some());
+        cache.insert((1, 3), block(100));
+        assert_eq!(cache.used_bytes(), 3 * size);
+        assert!(cache.get((1, 1)).is_none());
+        for offset in [0, 2, 3] {
+            assert!(cache.get((1, offset)).is_some(), "bloque {}", offset);
+        }
+        assert_eq!((cache.hits(), cache.misses()), (4, 1));
+
+        // Uno que ocupa por dos saca a los dos menos usados (0 y 2)
+        let overhead = size - 100;
+        let double = block(200 + overhead);
+        assert_eq!(double.size(), 2 * size);
+        cache.insert((2, 0), double);
+        assert!(cache.used_bytes() <= cache.capacity());
+        assert!(cache.get((1, 0)).is_none());
+        assert!(cache.get((1, 2)).is_none());
+        assert!(cache.get((1, 3)).is_some());
+        assert!(cache.get((2, 0)).is_some());
+    }
+
+    #[test]
+    fn reinserting_and_oversized_blocks() {
+        let cache = BlockCache::new(1000);
+        // El mismo bloque otra vez no cuenta doble
+        cache.insert((1, 0), block(300));
+        cache.insert((1, 0), block(400));
+        assert_eq!(cache.used_bytes(), block(400).size());
+        // Más grande que todo el presupuesto: ni se guarda ni saca a nadie
+        cache.insert((1, 1), block(2000));
+        assert!(cache.get((1, 1)).is_none());
+        assert!(cache.get((1, 0)).is_some());
+        assert_eq!(cache.used_bytes(), block(400).size());
+    }
+}
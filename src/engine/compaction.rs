@@ -0,0 +1,924 @@
+// La COMPACTACIÓN (con estrategias intercambiables)
+// Cada carga de trabajo necesita algo distinto, así que QUÉ compactar lo decide una estrategia
+// (el trait CompactionStrategy) y el CÓMO (mezclar y escribir tablas nuevas) es común a todas:
+//
+//   - Leveled     (LevelDB)    -> niveles L1..Ln acotados en tamaño; poca amplificación de lectura.
+//   - SizeTiered  (Cassandra)  -> junta corridas de tamaño parecido; poca amplificación de escritura.
+//   - TimeWindow  (series)     -> agrupa por ventana de tiempo y NUNCA mezcla ventanas viejas entre sí.
+//
+// Sea cual sea la estrategia, cada paso es incremental y queda anotado en el MANIFEST como un único VersionEdit.
+//
+// Las LÁPIDAS se tiran en la compactación solo cuando ninguna tabla MÁS VIEJA que quede afuera
+// puede tener esa llave: si no, al desaparecer la lápida la versión vieja "resucitaría".
+//
+// Las VERSIONES viejas de una llave se tiran cuando nadie puede verlas: ni un snapshot (ya hay una
+// versión más nueva con secuencia <= al snapshot más viejo) ni un viaje en el tiempo (la política
+// de retención de su prefijo ya no la quiere, ver retention.rs).
+//
+// Los OPERANDOS de MERGE nunca dejan caer lo que tapan: una versión que se podría tirar se combina
+// con los operandos de arriba (y si es la base, quedan resueltos en un valor común). Ver merge_operator.rs.
+//
+// Cada valor que sobrevive pasa por el FILTRO de compactación, si hay uno (ver compaction_filter.rs).
+//
+// Una versión tapada por un BORRADO DE RANGO más nuevo se tira con las mismas reglas que una pisada
+// (el rango cuenta como la versión que la pisó). La lápida de rango se tira cuando ya nada de lo
+// que tapa puede quedar: la ven todos los snapshots, la retención no quiere nada de su rango y
+// ninguna tabla de afuera con datos anteriores a ella se pisa con el rango (ver range_delete.rs).
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::cache::BlockCache;
+use super::compaction_filter::{CompactionFilter, FilterCounts, FilterDecision};
+use super::entry::Entry;
+use super::key;
+use super::merge::MergeIter;
+use super::merge_operator::{self, MergeOperator};
+use super::range_delete::{RangeTombstone, RangeTombstones};
+use super::retention::Retention;
+use super::sstable::{Table, TableBuilder, TableOptions};
+use super::stats::Stats;
+use super::version::{Version, VersionEdit, NUM_LEVELS};
+
+type Source = Box<dyn Iterator<Item = io::Result<(Vec<u8>, Entry)>>>;
+
+pub struct Compaction {
+    pub level: usize,
+    // Tablas del nivel `level`
+    pub inputs: Vec<Arc<Table>>,
+    // Tablas del nivel `output_level` que se pisan con las de arriba (vacío si output_level == level)
+    pub next_inputs: Vec<Arc<Table>>,
+    pub output_level: usize,
+}
+
+impl Compaction {
+    pub fn input_bytes(&self) -> u64 {
+        self.inputs
+            .iter()
+            .chain(&self.next_inputs)
+            .map(|t| t.meta().size)
+            .sum()
+    }
+
+    fn is_input(&self, table: &Table) -> bool {
+        self.inputs
+            .iter()
+            .chain(&self.next_inputs)
+            .any(|t| t.meta().id == table.meta().id)
+    }
+
+    // Las tablas que quedan afuera y tienen datos MÁS VIEJOS que la salida: todo lo que está en
+    // niveles más profundos y, si la salida queda en L0, las corridas de L0 anteriores a las entradas
+    fn older_tables(&self, version: &Version) -> Vec<Arc<Table>> {
+        let oldest_run = self.inputs.iter().map(|t| t.meta().run).min().unwrap_or(0);
+        let mut older = Vec::new();
+        for (level, tables) in version.levels.iter().enumerate() {
+            for table in tables {
+                let deeper = level > self.output_level;
+                let older_run = level == self.output_level
+                    && self.output_level == 0
+                    && table.meta().run < oldest_run;
+                if (deeper || older_run) && !self.is_input(table) {
+                    older.push(Arc::clone(table));
+                }
+            }
+        }
+        older
+    }
+}
+
+pub trait CompactionStrategy: Send + Sync {
+    // El nombre que reporta INFO
+    fn name(&self) -> &'static str;
+
+    // `force` = compactación pedida a mano (COMPACT): hacer algo útil aunque nada esté excedido
+    fn pick(&mut self, version: &Version, force: bool) -> Option<Compaction>;
+}
+
+// Para elegir la estrategia por nombre (línea de comandos, configuración)
+pub fn strategy_from_name(name: &str) -> Option<Box<dyn CompactionStrategy>> {
+    match name.to_lowercase().as_str() {
+        "leveled" => Some(Box::new(Leveled::default())),
+        "size-tiered" | "size_tiered" | "stcs" => Some(Box::new(SizeTiered::default())),
+        "time-window" | "time_window" | "twcs" => Some(Box::new(TimeWindow::default())),
+        _ => None,
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// LEVELED
+// ---------------------------------------------------------------------------------
+//   - L0 tiene demasiadas tablas  -> mezclamos las de L0 con las de L1 que se pisan con ellas.
+//   - Ln se pasó de tamaño        -> elegimos UNA tabla de Ln (por turnos) + las de Ln+1 que pisa.
+
+pub struct Leveled {
+    // Con tantas tablas en L0 arranca una compactación hacia L1
+    pub level0_file_limit: usize,
+    // Tamaño máximo de L1 (cada nivel siguiente aguanta 10 veces más)
+    pub level1_max_bytes: u64,
+    // Por cada nivel, la última llave compactada (para ir rotando qué tabla bajamos)
+    pointers: Vec<Vec<u8>>,
+}
+
+impl Default for Leveled {
+    fn default() -> Self {
+        Leveled::new(4, 10 * 1024 * 1024)
+    }
+}
+
+impl Leveled {
+    pub fn new(level0_file_limit: usize, level1_max_bytes: u64) -> Self {
+        Leveled {
+            level0_file_limit,
+            level1_max_bytes,
+            pointers: vec![Vec::new(); NUM_LEVELS],
+        }
+    }
+
+    // Cuánto puede pesar cada nivel: L1 = level1_max_bytes, L2 = 10x, L3 = 100x...
+    pub fn max_bytes_for_level(&self, level: usize) -> u64 {
+        let mut bytes = self.level1_max_bytes;
+        for _ in 1..level {
+            bytes = bytes.saturating_mul(10);
+        }
+        bytes
+    }
+
+    // Puntaje >= 1.0 significa "este nivel necesita compactarse"
+    fn score(&self, version: &Version, level: usize) -> f64 {
+        if level == 0 {
+            version.levels[0].len() as f64 / self.level0_file_limit as f64
+        } else {
+            version.level_size(level) as f64 / self.max_bytes_for_level(level) as f64
+        }
+    }
+}
+
+impl CompactionStrategy for Leveled {
+    fn name(&self) -> &'static str {
+        "leveled"
+    }
+
+    fn pick(&mut self, version: &Version, force: bool) -> Option<Compaction> {
+        // El último nivel no tiene a dónde bajar
+        let (level, best) = (0..NUM_LEVELS - 1)
+            .map(|level| (level, self.score(version, level)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        if best <= 0.0 || (best < 1.0 && !force) {
+            return None;
+        }
+
+        let inputs = if level == 0 {
+            // En L0 las tablas se pisan entre sí: si bajamos una sin las demás, una versión vieja
+            // podría quedar "arriba" de una nueva. Las bajamos todas juntas (L0 siempre es chico).
+            version.levels[0].clone()
+        } else {
+            // Por turnos: la primera tabla que empiece después de la última que compactamos en este nivel
+            let files = &version.levels[level];
+            let table = files
+                .iter()
+                .find(|t| t.meta().smallest > self.pointers[level])
+                .unwrap_or(&files[0]);
+            self.pointers[level] = table.meta().largest.clone();
+            vec![Arc::clone(table)]
+        };
+
+        let smallest = inputs.iter().map(|t| t.meta().smallest.as_slice()).min()?;
+        let largest = inputs.iter().map(|t| t.meta().largest.as_slice()).max()?;
+        let next_inputs = version.overlapping(level + 1, smallest, largest);
+        Some(Compaction {
+            level,
+            inputs,
+            next_inputs,
+            output_level: level + 1,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// SIZE-TIERED
+// ---------------------------------------------------------------------------------
+// Todas las tablas viven en L0 como "corridas" ordenadas por antigüedad. Cuando hay
+// `min_threshold` corridas SEGUIDAS de tamaño parecido, se funden en una sola más grande.
+// (Tienen que ser seguidas: si salteamos una corrida, el resultado quedaría "arriba" de ella
+// con datos más viejos).
+
+pub struct SizeTiered {
+    pub min_threshold: usize,
+    pub max_threshold: usize,
+    // Una corrida entra en el balde si su tamaño está entre bucket_low y bucket_high veces el promedio
+    pub bucket_low: f64,
+    pub bucket_high: f64,
+}
+
+impl Default for SizeTiered {
+    fn default() -> Self {
+        SizeTiered {
+            min_threshold: 4,
+            max_threshold: 32,
+            bucket_low: 0.5,
+            bucket_high: 1.5,
+        }
+    }
+}
+
+impl CompactionStrategy for SizeTiered {
+    fn name(&self) -> &'static str {
+        "size-tiered"
+    }
+
+    fn pick(&mut self, version: &Version, force: bool) -> Option<Compaction> {
+        let runs = &version.levels[0];
+        let mut best: Option<(usize, usize)> = None;
+
+        // Para cada punto de arranque, estiramos el balde mientras el tamaño siga siendo parecido
+        for start in 0..runs.len() {
+            let mut total = 0u64;
+            let mut end = start;
+            while end < runs.len() && end - start < self.max_threshold {
+                let size = runs[end].meta().size as f64;
+                if end > start {
+                    let avg = total as f64 / (end - start) as f64;
+                    if size < avg * self.bucket_low || size > avg * self.bucket_high {
+                        break;
+                    }
+                }
+                total += runs[end].meta().size;
+                end += 1;
+            }
+            // Preferimos el balde más largo; a igualdad, el de corridas más chicas (más barato)
+            let better = match best {
+                None => true,
+                Some((s, e)) => end - start > e - s,
+            };
+            if end - start >= self.min_threshold && better {
+                best = Some((start, end));
+            }
+        }
+
+        let (start, end) = match best {
+            Some(bucket) => bucket,
+            // A mano y sin baldes llenos: fundimos las dos corridas más nuevas
+            None if force && runs.len() >= 2 => (0, 2),
+            None => return None,
+        };
+        Some(Compaction {
+            level: 0,
+            inputs: runs[start..end].to_vec(),
+            next_inputs: Vec::new(),
+            output_level: 0,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// TIME-WINDOW
+// ---------------------------------------------------------------------------------
+// Pensada para series de tiempo (como los prototipos s7_* / s9_*): cada tabla cae en la ventana
+// de su escritura más nueva. Solo se mezclan tablas de la MISMA ventana; cuando una ventana vieja
+// quedó en una sola tabla ya nadie la vuelve a tocar, así que los datos viejos no se reescriben.
+
+pub struct TimeWindow {
+    pub window_ms: u64,
+    // Cuántas tablas tiene que acumular la ventana actual antes de fundirlas
+    pub min_threshold: usize,
+}
+
+impl Default for TimeWindow {
+    fn default() -> Self {
+        TimeWindow {
+            window_ms: 60 * 60 * 1000,
+            min_threshold: 4,
+        }
+    }
+}
+
+impl CompactionStrategy for TimeWindow {
+    fn name(&self) -> &'static str {
+        "time-window"
+    }
+
+    fn pick(&mut self, version: &Version, force: bool) -> Option<Compaction> {
+        let runs = &version.levels[0];
+        let window_of = |t: &Arc<Table>| t.meta().max_time / self.window_ms.max(1);
+        let current = runs.first().map(window_of)?;
+
+        // Las tablas de L0 van de la más nueva a la más vieja, así que cada ventana es un tramo seguido
+        let mut start = 0;
+        while start < runs.len() {
+            let window = window_of(&runs[start]);
+            let end = start
+                + runs[start..]
+                    .iter()
+                    .take_while(|t| window_of(t) == window)
+                    .count();
+            let count = end - start;
+            // La ventana actual espera a juntar min_threshold tablas; una ventana ya cerrada
+            // se funde una última vez en una sola tabla y queda congelada para siempre.
+            let threshold = if window == current && !force {
+                self.min_threshold
+            } else {
+                2
+            };
+            if count >= threshold {
+                return Some(Compaction {
+                    level: 0,
+                    inputs: runs[start..end].to_vec(),
+                    next_inputs: Vec::new(),
+                    output_level: 0,
+                });
+            }
+            start = end;
+        }
+        None
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// EJECUCIÓN (común a todas las estrategias)
+// ---------------------------------------------------------------------------------
+
+// Todo lo que necesita una compactación para correr SIN el candado del motor: una copia de la
+// versión (las tablas son inmutables, así que la copia no cambia bajo nuestros pies), las opciones
+// de escritura y los contadores compartidos. Se arma con el candado (rápido), se ejecuta sin él
+// (lento) y el resultado se instala otra vez con el candado (rápido).
+pub struct CompactionJob {
+    pub dir: PathBuf,
+    pub table_options: TableOptions,
+    pub target_file_size: u64,
+    pub cache: Arc<BlockCache>,
+    pub stats: Arc<Stats>,
+    pub version: Version,
+    pub task: Compaction,
+    pub next_file_id: Arc<AtomicU64>,
+    pub progress: Arc<CompactionProgress>,
+    // La secuencia del snapshot vivo más viejo (o la última escrita si no hay ninguno)
+    pub smallest_snapshot: u64,
+    // Qué historia guardar de cada llave, evaluada en el instante `now` (ms) en que se armó el trabajo
+    pub retention: Retention,
+    // Para combinar los operandos de MERGE (None = quedan tal cual)
+    pub merge_operator: Option<Arc<dyn MergeOperator>>,
+    // Reglas de negocio para quitar o reescribir valores (None = todo queda como está)
+    pub compaction_filter: Option<Arc<dyn CompactionFilter>>,
+    // Las lápidas de rango vivas cuando se armó el trabajo
+    pub ranges: RangeTombstones,
+    pub now: u64,
+}
+
+pub struct CompactionOutput {
+    pub task: Compaction,
+    pub edit: VersionEdit,
+    pub opened: Vec<Arc<Table>>,
+    pub filtered: FilterCounts,
+    // Lápidas de rango que se tiraron (llave de inicio, secuencia): el motor las saca de su lista
+    pub dropped_ranges: Vec<(Vec<u8>, u64)>,
+}
+
+// El avance de la compactación en curso (lo lee INFO mientras el hilo de fondo trabaja)
+#[derive(Default)]
+pub struct CompactionProgress {
+    pub running: AtomicBool,
+    pub level: AtomicU64,
+    pub output_level: AtomicU64,
+    pub input_bytes: AtomicU64,
+    pub read_bytes: Arc<AtomicU64>,
+    pub completed: AtomicU64,
+}
+
+impl CompactionProgress {
+    pub fn start(&self, task: &Compaction) {
+        self.level.store(task.level as u64, Ordering::Relaxed);
+        self.output_level
+            .store(task.output_level as u64, Ordering::Relaxed);
+        self.input_bytes
+            .store(task.input_bytes(), Ordering::Relaxed);
+        self.read_bytes.store(0, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self, ok: bool) {
+        self.running.store(false, Ordering::Relaxed);
+        if ok {
+            self.completed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Porcentaje leído de las tablas de entrada (None si no hay nada corriendo)
+    pub fn percent(&self) -> Option<f64> {
+        if !self.running.load(Ordering::Relaxed) {
+            return None;
+        }
+        let total = self.input_bytes.load(Ordering::Relaxed).max(1);
+        let read = self.read_bytes.load(Ordering::Relaxed);
+        Some((read as f64 * 100.0 / total as f64).min(100.0))
+    }
+}
+
+impl CompactionJob {
+    // Ejecuta la compactación y devuelve el edit a anotar + las tablas nuevas ya abiertas
+    pub fn run(self) -> io::Result<CompactionOutput> {
+        let compaction = &self.task;
+        let level = compaction.level;
+        let output_level = compaction.output_level;
+        let mut edit = VersionEdit::default();
+        for table in &compaction.inputs {
+            edit.deleted_files.push((level, table.meta().id));
+        }
+        for table in &compaction.next_inputs {
+            edit.deleted_files.push((output_level, table.meta().id));
+        }
+
+        // Movida trivial: nadie abajo se pisa con esta tabla -> solo cambia de nivel, cero bytes reescritos
+        if compaction.inputs.len() == 1
+            && compaction.next_inputs.is_empty()
+            && output_level != level
+        {
+            let table = Arc::clone(&compaction.inputs[0]);
+            edit.new_files.push((output_level, table.meta().clone()));
+            return Ok(CompactionOutput {
+                task: self.task,
+                edit,
+                opened: vec![table],
+                filtered: FilterCounts::default(),
+                dropped_ranges: Vec::new(),
+            });
+        }
+
+        // Fuentes de la más nueva a la más vieja: cada tabla de L0 por separado (se pisan),
+        // pero las de un nivel L1+ se pueden encadenar porque ya están ordenadas y sin pisarse.
+        let read_bytes = &self.progress.read_bytes;
+        let mut sources: Vec<Source> = Vec::new();
+        if level == 0 {
+            for table in &compaction.inputs {
+                sources.push(Box::new(table.iter().tracking(Arc::clone(read_bytes))));
+            }
+        } else {
+            sources.push(chain(&compaction.inputs, read_bytes));
+        }
+        sources.push(chain(&compaction.next_inputs, read_bytes));
+
+        // La salida hereda la antigüedad y el rango de tiempo de sus entradas
+        let all = compaction.inputs.iter().chain(&compaction.next_inputs);
+        let run_order = all.clone().map(|t| t.meta().run).max().unwrap_or(0);
+        let min_time = all.clone().map(|t| t.meta().min_time).min().unwrap_or(0);
+        let max_time = all.map(|t| t.meta().max_time).max().unwrap_or(0);
+
+        let older = compaction.older_tables(&self.version);
+        let mut outputs = Vec::new();
+        let mut builder: Option<TableBuilder> = None;
+        // La llave de usuario que venimos recorriendo, su política, cuántas versiones suyas ya
+        // pasaron y la secuencia/momento de la anterior (más nueva)
+        let mut current_user_key: Option<Vec<u8>> = None;
+        let mut policy = self.retention.default_policy();
+        let mut rank = 0;
+        let mut last_seq_for_key = u64::MAX;
+        let mut last_time_for_key = u64::MAX;
+        // Operandos de MERGE de la llave que todavía no escribimos (llave interna, operando), del
+        // más nuevo al más viejo: esperan a ver si lo de abajo se puede combinar con ellos
+        let mut operands: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        let mut filtered = FilterCounts::default();
+        let mut dropped_ranges = Vec::new();
+        for record in MergeIter::new(sources) {
+            let (internal, entry) = record?;
+            let user_part = key::user_part(&internal);
+            let seq = key::seq(&internal);
+            let time = key::time(&internal);
+            let new_user_key = current_user_key.as_deref() != Some(user_part);
+            if new_user_key {
+                self.flush_operands(&mut builder, &mut operands, &older)?;
+                current_user_key = Some(user_part.to_vec());
+                policy = self.retention.policy_for_user_part(user_part);
+                rank = 0;
+                last_seq_for_key = u64::MAX;
+                last_time_for_key = u64::MAX;
+                // En L1+ cortamos en tablas de tamaño acotado, pero nunca en medio de las versiones
+                // de una misma llave (si no, una llave quedaría repartida en dos tablas del nivel)
+                if output_level > 0
+                    && builder
+                        .as_ref()
+                        .is_some_and(|b| b.size() >= self.target_file_size)
+                {
+                    outputs.push(builder.take().unwrap().finish()?);
+                }
+            }
+
+            // La lápida de rango no es una versión más de su llave: se queda o se va por sus reglas
+            if let Entry::RangeDelete(end) = &entry {
+                self.write_operands(&mut builder, &mut operands)?;
+                let range = RangeTombstone {
+                    start: key::user_key(&internal)?,
+                    end: end.clone(),
+                    seq,
+                    time,
+                };
+                if self.range_reclaimable(&range) {
+                    Stats::bump(&self.stats.range_tombstones_dropped);
+                    dropped_ranges.push((range.start, seq));
+                } else {
+                    self.add(&mut builder, &internal, &entry)?;
+                }
+                continue;
+            }
+
+            // Tapada: una versión más nueva ya es visible para todos los snapshots y la retención
+            // ya no quiere esta versión vieja
+            let hidden = last_seq_for_key <= self.smallest_snapshot
+                && policy.expired(rank, last_time_for_key, self.now);
+            last_seq_for_key = seq;
+            last_time_for_key = time;
+            rank += 1;
+            // Borrada por un rango más nuevo que ya ven todos (y que la retención deja ir)
+            let user_key = match self.ranges.len() {
+                0 => None,
+                _ => Some(key::user_key(&internal)?),
+            };
+            let covered = user_key
+                .as_deref()
+                .and_then(|k| self.ranges.newest_covering(k, seq, u64::MAX));
+            if covered.is_some_and(|range| {
+                range.seq <= self.smallest_snapshot && policy.expired(rank, range.time, self.now)
+            }) {
+                Stats::bump(&self.stats.versions_dropped);
+                continue;
+            }
+            if hidden && !operands.is_empty() {
+                // Tapada por operandos: en vez de tirarla se combina con ellos (sin operador no se
+                // puede, así que se queda). Si un rango la borró antes de ellos, no es su base.
+                if self.merge_operator.is_some() {
+                    let oldest = key::seq(&operands[operands.len() - 1].0);
+                    let deleted_below = user_key.as_deref().is_some_and(|k| {
+                        self.ranges
+                            .covering(k)
+                            .any(|range| seq < range.seq && range.seq < oldest)
+                    });
+                    let base = if deleted_below {
+                        Entry::Tombstone
+                    } else {
+                        entry
+                    };
+                    match base {
+                        Entry::Merge(operand) => operands.push((internal, operand)),
+                        base => {
+                            self.resolve_operands(&mut builder, &mut operands, (internal, base))?
+                        }
+                    }
+                    continue;
+                }
+            } else if hidden {
+                Stats::bump(&self.stats.versions_dropped);
+                continue;
+            }
+            // Alguien necesita esta versión tal cual: los operandos de arriba no pueden absorberla
+            self.write_operands(&mut builder, &mut operands)?;
+            if let Entry::Merge(operand) = &entry {
+                operands.push((internal, operand.clone()));
+                continue;
+            }
+            let entry = self.filter(&internal, entry, &mut filtered)?;
+            // La lápida ya no tapa nada: todos la ven, la retención tira todo lo que tapaba (las
+            // versiones que siguen se van con ella) y ninguna tabla más vieja puede tener esta llave
+            if entry.is_tombstone()
+                && seq <= self.smallest_snapshot
+                && policy.expired(rank, time, self.now)
+                && !older
+                    .iter()
+                    .any(|t| t.meta().overlaps(&internal, &internal) && t.may_contain(user_part))
+            {
+                Stats::bump(&self.stats.tombstones_dropped);
+                continue;
+            }
+            self.add(&mut builder, &internal, &entry)?;
+        }
+        self.flush_operands(&mut builder, &mut operands, &older)?;
+        if let Some(current) = builder {
+            outputs.push(current.finish()?);
+        }
+
+        let mut opened = Vec::with_capacity(outputs.len());
+        for mut meta in outputs {
+            meta.run = run_order;
+            meta.min_time = min_time;
+            meta.max_time = max_time;
+            let table = Table::open(&self.dir, meta.clone(), Arc::clone(&self.cache))?;
+            opened.push(Arc::new(table));
+            edit.new_files.push((output_level, meta));
+        }
+        Ok(CompactionOutput {
+            task: self.task,
+            edit,
+            opened,
+            filtered,
+            dropped_ranges,
+        })
+    }
+
+    fn range_reclaimable(&self, range: &RangeTombstone) -> bool {
+        if range.seq > self.smallest_snapshot {
+            return false;
+        }
+        // Cualquier versión que tapa pudo ser la más nueva de su llave (la pisó el rango: puesto 1)
+        let policies = self
+            .retention
+            .policies_in_range(&range.start, range.end.as_deref());
+        if !policies
+            .iter()
+            .all(|policy| policy.expired(1, range.time, self.now))
+        {
+            return false;
+        }
+        // Una tabla que arranca después del borrado solo tiene versiones más nuevas que él
+        !self.version.levels.iter().flatten().any(|table| {
+            !self.task.is_input(table)
+                && table.meta().min_time <= range.time
+                && range.overlaps(&table.meta().smallest, &table.meta().largest)
+        })
+    }
+
+    // Lo que el filtro de compactación decide para esta versión (una quitada queda como lápida)
+    fn filter(
+        &self,
+        internal: &[u8],
+        entry: Entry,
+        counts: &mut FilterCounts,
+    ) -> io::Result<Entry> {
+        let filter = match &self.compaction_filter {
+            Some(filter) => filter,
+            None => return Ok(entry),
+        };
+        let deadline = entry.deadline();
+        let value = match &entry {
+            Entry::Value(value) | Entry::Expiring(value, _) => value,
+            _ => return Ok(entry),
+        };
+        let user_key = key::user_key(internal)?;
+        let decision = filter.filter(
+            self.task.output_level,
+            &user_key,
+            value,
+            key::seq(internal),
+            key::time(internal),
+        );
+        Ok(match decision {
+            FilterDecision::Keep => entry,
+            FilterDecision::Remove => {
+                counts.removed += 1;
+                Entry::Tombstone
+            }
+            FilterDecision::Replace(value) => {
+                counts.replaced += 1;
+                match deadline {
+                    Some(deadline) => Entry::Expiring(value, deadline),
+                    None => Entry::Value(value),
+                }
+            }
+        })
+    }
+
+    fn add(
+        &self,
+        builder: &mut Option<TableBuilder>,
+        internal: &[u8],
+        entry: &Entry,
+    ) -> io::Result<()> {
+        if builder.is_none() {
+            let id = self.next_file_id.fetch_add(1, Ordering::SeqCst);
+            *builder = Some(TableBuilder::create(&self.dir, id, &self.table_options)?);
+        }
+        builder.as_mut().unwrap().add(internal, entry)
+    }
+
+    // Se terminaron las versiones de la llave (en esta compactación). Si ninguna tabla más vieja
+    // puede tenerla, abajo de los operandos no hay nada: se resuelven sin base.
+    fn flush_operands(
+        &self,
+        builder: &mut Option<TableBuilder>,
+        operands: &mut Vec<(Vec<u8>, Vec<u8>)>,
+        older: &[Arc<Table>],
+    ) -> io::Result<()> {
+        let newest = match operands.first() {
+            Some((internal, _)) => internal.clone(),
+            None => return Ok(()),
+        };
+        let user_part = key::user_part(&newest);
+        let base_elsewhere = older
+            .iter()
+            .any(|t| t.meta().overlaps(&newest, &newest) && t.may_contain(user_part));
+        if self.merge_operator.is_some() && !base_elsewhere {
+            let user_key = key::user_key(&newest)?;
+            if let Ok(entry) = self.combine(&user_key, None, operands) {
+                return self.write_resolved(builder, operands, &newest, entry);
+            }
+        }
+        self.write_operands(builder, operands)
+    }
+
+    // La versión de abajo de los operandos (ya tapada) es su base: todo queda en un valor común.
+    // Si el operador no puede combinarlos, la base se queda tal cual debajo de ellos.
+    fn resolve_operands(
+        &self,
+        builder: &mut Option<TableBuilder>,
+        operands: &mut Vec<(Vec<u8>, Vec<u8>)>,
+        (internal, base): (Vec<u8>, Entry),
+    ) -> io::Result<()> {
+        let newest = operands[0].0.clone();
+        let user_key = key::user_key(&newest)?;
+        match self.combine(&user_key, Some(base.clone()), operands) {
+            Ok(entry) => self.write_resolved(builder, operands, &newest, entry),
+            Err(_) => {
+                self.write_operands(builder, operands)?;
+                self.add(builder, &internal, &base)
+            }
+        }
+    }
+
+    fn combine(
+        &self,
+        user_key: &[u8],
+        base: Option<Entry>,
+        operands: &[(Vec<u8>, Vec<u8>)],
+    ) -> io::Result<Entry> {
+        let operator = self.merge_operator.as_deref().unwrap();
+        let since = key::time(&operands[operands.len() - 1].0);
+        let values: Vec<Vec<u8>> = operands.iter().rev().map(|(_, op)| op.clone()).collect();
+        merge_operator::resolve(operator, user_key, base, &values, since)
+    }
+
+    // El resultado ocupa el lugar del operando más nuevo (su secuencia y su momento)
+    fn write_resolved(
+        &self,
+        builder: &mut Option<TableBuilder>,
+        operands: &mut Vec<(Vec<u8>, Vec<u8>)>,
+        newest: &[u8],
+        entry: Entry,
+    ) -> io::Result<()> {
+        self.stats
+            .merge_operands_resolved
+            .fetch_add(operands.len() as u64, Ordering::Relaxed);
+        operands.clear();
+        self.add(builder, newest, &entry)
+    }
+
+    // Los operandos siguen sin base: juntos en uno solo si el operador sabe, si no uno por uno
+    fn write_operands(
+        &self,
+        builder: &mut Option<TableBuilder>,
+        operands: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> io::Result<()> {
+        if operands.len() > 1 {
+            if let Some(operator) = &self.merge_operator {
+                let user_key = key::user_key(&operands[0].0)?;
+                let values: Vec<&[u8]> =
+                    operands.iter().rev().map(|(_, op)| op.as_slice()).collect();
+                if let Some(combined) = operator.partial_merge(&user_key, &values) {
+                    let newest = operands[0].0.clone();
+                    return self.write_resolved(builder, operands, &newest, Entry::Merge(combined));
+                }
+            }
+        }
+        for (internal, operand) in operands.drain(..) {
+            self.add(builder, &internal, &Entry::Merge(operand))?;
+        }
+        Ok(())
+    }
+}
+
+fn chain(tables: &[Arc<Table>], read_bytes: &Arc<AtomicU64>) -> Source {
+    let iters: Vec<_> = tables
+        .iter()
+        .map(|t| t.iter().tracking(Arc::clone(read_bytes)))
+        .collect();
+    Box::new(iters.into_iter().flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, TempDir};
+
+    #[test]
+    fn a_deleted_key_is_reclaimed_with_its_tombstone() {
+        let dir = TempDir::new("gc_tombstone");
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        for round in 0..3 {
+            engine
+                .set(b"gone", format!("v{}", round).as_bytes())
+                .unwrap();
+            engine
+                .set(b"kept", format!("v{}", round).as_bytes())
+                .unwrap();
+            engine.flush().unwrap();
+        }
+        engine.delete(b"gone").unwrap();
+        testing::compact_all(&mut engine);
+
+        assert_eq!(engine.get(b"gone").unwrap(), None);
+        assert_eq!(testing::stored_versions(&engine, b"gone"), 0);
+        assert_eq!(engine.get(b"kept").unwrap(), Some(b"v2".to_vec()));
+        assert_eq!(testing::stored_versions(&engine, b"kept"), 1);
+    }
+
+    // Las lápidas bajan de nivel de a una compactación: mientras alguna tabla de abajo pueda tener
+    // la llave, la lápida se queda (si no, el valor viejo volvería a aparecer)
+    #[test]
+    fn a_deleted_key_never_resurfaces_between_compactions() {
+        let dir = TempDir::new("gc_resurface");
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        for i in 0..400 {
+            let key = format!("key{:04}", i);
+            engine.set(key.as_bytes(), &[b'x'; 32]).unwrap();
+        }
+        testing::compact_all(&mut engine);
+        for i in (0..400).step_by(2) {
+            engine.delete(format!("key{:04}", i).as_bytes()).unwrap();
+        }
+        engine.flush().unwrap();
+        loop {
+            for i in 0..400 {
+                let found = engine.get(format!("key{:04}", i).as_bytes()).unwrap();
+                assert_eq!(found.is_none(), i % 2 == 0, "key{:04}", i);
+            }
+            if !engine.compact_step(true).unwrap() {
+                break;
+            }
+        }
+        for i in (0..400).step_by(2) {
+            let key = format!("key{:04}", i);
+            assert_eq!(testing::stored_versions(&engine, key.as_bytes()), 0);
+        }
+    }
+
+    #[test]
+    fn a_live_snapshot_keeps_the_versions_it_sees() {
+        let dir = TempDir::new("gc_snapshot");
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        // Cada escritura en su tabla: así la compactación las mezcla (una tabla sola solo baja
+        // de nivel, sin reescribirse)
+        engine.set(b"k", b"old").unwrap();
+        engine.set(b"gone", b"old").unwrap();
+        engine.flush().unwrap();
+        let snapshot = engine.snapshot();
+        engine.set(b"k", b"new").unwrap();
+        engine.delete(b"gone").unwrap();
+        engine.flush().unwrap();
+        testing::compact_all(&mut engine);
+
+        // La compactación no puede tirar lo que el snapshot todavía ve (ni la lápida que lo tapa)
+        assert_eq!(
+            engine.get_snapshot(b"k", &snapshot).unwrap(),
+            Some(b"old".to_vec())
+        );
+        assert_eq!(
+            engine.get_snapshot(b"gone", &snapshot).unwrap(),
+            Some(b"old".to_vec())
+        );
+        assert_eq!(engine.get(b"k").unwrap(), Some(b"new".to_vec()));
+        assert_eq!(engine.get(b"gone").unwrap(), None);
+        assert_eq!(testing::stored_versions(&engine, b"k"), 2);
+        assert_eq!(testing::stored_versions(&engine, b"gone"), 2);
+
+        // Sin el snapshot ya nadie las ve: la próxima compactación las tira
+        drop(snapshot);
+        engine.set(b"h", b"x").unwrap();
+        testing::compact_all(&mut engine);
+        assert_eq!(testing::stored_versions(&engine, b"k"), 1);
+        assert_eq!(testing::stored_versions(&engine, b"gone"), 0);
+        assert_eq!(engine.get(b"k").unwrap(), Some(b"new".to_vec()));
+    }
+
+    // Solo cuenta el snapshot más viejo: lo tapado por una versión que él ya ve se tira, lo de
+    // después queda entero hasta que se suelte
+    #[test]
+    fn versions_older_than_the_oldest_snapshot_are_trimmed() {
+        let dir = TempDir::new("gc_oldest_snapshot");
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        let mut snapshot = None;
+        for value in [b"v1", b"v2", b"v3", b"v4"] {
+            engine.set(b"k", value).unwrap();
+            engine.flush().unwrap();
+            if value == b"v2" {
+                snapshot = Some(engine.snapshot());
+            }
+        }
+        let snapshot = snapshot.unwrap();
+        testing::compact_all(&mut engine);
+
+        // v1 la tapa v2, que ven todos; v2 la necesita el snapshot; v3 y v4 son más nuevas que él
+        assert_eq!(testing::stored_versions(&engine, b"k"), 3);
+        assert_eq!(
+            engine.get_snapshot(b"k", &snapshot).unwrap(),
+            Some(b"v2".to_vec())
+        );
+        assert_eq!(engine.get(b"k").unwrap(), Some(b"v4".to_vec()));
+
+        drop(snapshot);
+        engine.set(b"k", b"v5").unwrap();
+        testing::compact_all(&mut engine);
+        assert_eq!(testing::stored_versions(&engine, b"k"), 1);
+        assert_eq!(engine.get(b"k").unwrap(), Some(b"v5".to_vec()));
+    }
+}
@@ -0,0 +1,203 @@
+// El FILTRO DE COMPACTACIÓN
+// Para purgar datos con reglas de negocio ("todo lo del tenant 42", "anonimizar los emails") no
+// hace falta mandar millones de DEL: la compactación igual relee y reescribe cada versión, así que
+// de paso le pregunta a un filtro qué hacer con cada una. Se queda, se va o cambia de valor.
+//
+// El filtro ve los valores (con o sin vencimiento) que la compactación va a escribir; las lápidas
+// y los operandos de MERGE pasan sin preguntar (un MERGE ya resuelto se filtra en la compactación
+// siguiente, cuando es un valor común). Solo mira lo que entra a una compactación: el dato se
+// limpia a medida que los niveles se van compactando (COMPACT lo apura).
+//
+// Quitar una versión no la borra sin más: se reescribe como una LÁPIDA en su lugar, así una
+// versión más vieja de la llave que vive en otra tabla no "resucita". La lápida se va después con
+// las reglas de siempre. Ojo: los snapshots y los viajes en el tiempo ven lo que dejó el filtro.
+
+use std::sync::Arc;
+
+pub enum FilterDecision {
+    Keep,
+    Remove,
+    // Misma llave, misma versión, otro valor (si tenía vencimiento, lo conserva)
+    Replace(Vec<u8>),
+}
+
+pub trait CompactionFilter: Send + Sync {
+    // El nombre que reporta INFO
+    fn name(&self) -> &'static str;
+
+    // `level` es el nivel al que va la salida de la compactación; `seq` y `time` identifican la versión
+    fn filter(&self, level: usize, key: &[u8], value: &[u8], seq: u64, time: u64)
+        -> FilterDecision;
+}
+
+// Cuánto cambió el filtro en una compactación
+#[derive(Default, Clone, Copy)]
+pub struct FilterCounts {
+    pub removed: u64,
+    pub replaced: u64,
+}
+
+// ---------------------------------------------------------------------------------
+// PREFIX: purga (quita) o anonimiza (reemplaza el valor) las llaves de ciertos prefijos
+// ---------------------------------------------------------------------------------
+#[derive(Default)]
+pub struct PrefixFilter {
+    pub purge: Vec<Vec<u8>>,
+    pub redact: Vec<Vec<u8>>,
+}
+
+// Lo que queda en el lugar de un valor anonimizado
+pub const REDACTED: &[u8] = b"<redacted>";
+
+impl PrefixFilter {
+    // None si no hay ninguna regla (no vale la pena preguntarle nada a un filtro que no hace nada)
+    pub fn into_filter(self) -> Option<Arc<dyn CompactionFilter>> {
+        if self.purge.is_empty() && self.redact.is_empty() {
+            return None;
+        }
+        Some(Arc::new(self))
+    }
+}
+
+impl CompactionFilter for PrefixFilter {
+    fn name(&self) -> &'static str {
+        "prefix"
+    }
+
+    fn filter(
+        &self,
+        _level: usize,
+        key: &[u8],
+        value: &[u8],
+        _seq: u64,
+        _time: u64,
+    ) -> FilterDecision {
+        if self.purge.iter().any(|prefix| key.starts_with(prefix)) {
+            FilterDecision::Remove
+        } else if value != REDACTED && self.redact.iter().any(|prefix| key.starts_with(prefix)) {
+            FilterDecision::Replace(REDACTED.to_vec())
+        } else {
+            FilterDecision::Keep
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::super::testing::{self, TempDir};
+    use super::super::{now_millis, Engine, EngineOptions, Ttl};
+    use super::{CompactionFilter, FilterDecision, PrefixFilter, REDACTED};
+
+    fn counts(engine: &Engine) -> (u64, u64) {
+        (
+            engine.stats.filter_removed.load(Ordering::Relaxed),
+            engine.stats.filter_replaced.load(Ordering::Relaxed),
+        )
+    }
+
+    fn with_filter(filter: Option<Arc<dyn CompactionFilter>>) -> EngineOptions {
+        let mut options = testing::small_options();
+        options.compaction_filter = filter;
+        options
+    }
+
+    // Cada versión que entra a la compactación cuenta una vez: con toda la historia guardada,
+    // las dos versiones de una llave se quitan (o se anonimizan) las dos
+    #[test]
+    fn purge_and_redact_counts() {
+        let dir = TempDir::new("filter_counts");
+        let filter = PrefixFilter {
+            purge: vec![b"tenant:42:".to_vec()],
+            redact: vec![b"pii:".to_vec()],
+        };
+        let mut engine = testing::open(&dir, with_filter(filter.into_filter()));
+        for key in ["tenant:42:a", "tenant:42:b", "tenant:42:c", "tenant:7:a"] {
+            engine.set(key.as_bytes(), b"v1").unwrap();
+        }
+        engine.set(b"pii:mail", b"ana@example.com").unwrap();
+        engine
+            .set_with_deadline(b"pii:phone", b"555", now_millis() + 3_600_000)
+            .unwrap();
+        engine.flush().unwrap();
+        let before = testing::instant_between();
+        engine.set(b"tenant:42:a", b"v2").unwrap();
+        engine.set(b"pii:mail", b"ana@example.org").unwrap();
+        engine.delete(b"tenant:42:c").unwrap();
+        testing::compact_all(&mut engine);
+
+        // tenant:42: a (2 versiones) + b (la lápida de c no se pregunta, su valor sí); pii: 3
+        assert_eq!(counts(&engine), (4, 3));
+        for key in ["tenant:42:a", "tenant:42:b", "tenant:42:c"] {
+            assert_eq!(engine.get(key.as_bytes()).unwrap(), None, "{}", key);
+        }
+        assert_eq!(engine.get(b"tenant:7:a").unwrap(), Some(b"v1".to_vec()));
+        assert_eq!(engine.get(b"pii:mail").unwrap(), Some(REDACTED.to_vec()));
+        assert_eq!(
+            engine.get_at_time(b"pii:mail", before).unwrap(),
+            Some(REDACTED.to_vec())
+        );
+        // Anonimizar no le saca el vencimiento
+        assert_eq!(engine.get(b"pii:phone").unwrap(), Some(REDACTED.to_vec()));
+        assert!(matches!(
+            engine.ttl(b"pii:phone").unwrap(),
+            Ttl::ExpiresIn(_)
+        ));
+
+        // Reescribir todo otra vez no cuenta nada de nuevo: lo quitado ya es lápida y lo
+        // anonimizado ya dice <redacted>
+        engine.set(b"a", b"x").unwrap();
+        engine.set(b"z", b"x").unwrap();
+        testing::compact_all(&mut engine);
+        assert_eq!(counts(&engine), (4, 3));
+    }
+
+    // Quita los valores "secret" en cualquier llave
+    struct SecretFilter;
+
+    impl CompactionFilter for SecretFilter {
+        fn name(&self) -> &'static str {
+            "secret"
+        }
+
+        fn filter(&self, _: usize, _: &[u8], value: &[u8], _: u64, _: u64) -> FilterDecision {
+            if value == b"secret" {
+                FilterDecision::Remove
+            } else {
+                FilterDecision::Keep
+            }
+        }
+    }
+
+    // La versión quitada queda como lápida: la más vieja que vive en una tabla que no entró a la
+    // compactación no vuelve a aparecer
+    #[test]
+    fn a_removed_version_does_not_resurrect_an_older_one() {
+        let dir = TempDir::new("filter_resurrect");
+        let mut engine = testing::open(&dir, with_filter(Some(Arc::new(SecretFilter))));
+        engine.set(b"k", b"public").unwrap();
+        testing::compact_all(&mut engine);
+        let bottom = engine.version.levels.len() - 1;
+        assert_eq!(engine.version.levels[bottom].len(), 1);
+
+        // Dos tablas en L0 para que se reescriban (una sola solo bajaría de nivel)
+        engine.set(b"k", b"secret").unwrap();
+        engine.flush().unwrap();
+        engine.set(b"other", b"x").unwrap();
+        engine.flush().unwrap();
+        assert!(engine.compact_step(true).unwrap());
+        assert!(engine.version.levels[0].is_empty());
+        assert_eq!(engine.version.levels[bottom].len(), 1);
+
+        assert_eq!(counts(&engine), (1, 0));
+        assert_eq!(engine.get(b"k").unwrap(), None);
+        assert_eq!(testing::stored_versions(&engine, b"k"), 2);
+    }
+
+    #[test]
+    fn a_filter_without_rules_is_not_installed() {
+        assert!(PrefixFilter::default().into_filter().is_none());
+    }
+}
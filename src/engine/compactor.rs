@@ -0,0 +1,142 @@
+// El COMPACTADOR EN SEGUNDO PLANO
+// Antes, COMPACT (y el volcado de cada memtable) reescribían tablas con el candado de escritura
+// tomado: todas las conexiones quedaban congeladas mientras tanto. Ahora un hilo dedicado:
+//   1. toma el candado un instante para ELEGIR qué compactar (y llevarse una copia de la versión),
+//   2. lo suelta y MEZCLA/ESCRIBE las tablas nuevas mientras los demás siguen leyendo y escribiendo,
+//   3. lo vuelve a tomar un instante para INSTALAR el resultado (MANIFEST + versión nueva).
+// Las tablas son inmutables, así que la copia de la versión sigue siendo válida todo el tiempo.
+
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::Engine;
+
+#[derive(Default)]
+struct Requests {
+    // Alguien volcó una memtable: quizás algún nivel quedó excedido
+    wake: bool,
+    // Pedidos de COMPACT manual (numerados) y hasta cuál ya se atendió
+    forced_requested: u64,
+    forced_done: u64,
+    last_error: Option<String>,
+    // El hilo terminó (se borró el keyspace): ya no atiende pedidos
+    stopped: bool,
+}
+
+pub struct Compactor {
+    requests: Mutex<Requests>,
+    changed: Condvar,
+    // El hilo, para esperar a que termine antes de borrar la carpeta del keyspace
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Compactor {
+    // Arranca el hilo y se engancha al motor (desde ahí los volcados ya no compactan en línea)
+    pub fn start(db: Arc<RwLock<Engine>>) -> Arc<Compactor> {
+        let compactor = Arc::new(Compactor {
+            requests: Mutex::new(Requests::default()),
+            changed: Condvar::new(),
+            worker: Mutex::new(None),
+        });
+        db.write().unwrap().attach_compactor(Arc::clone(&compactor));
+
+        let worker = Arc::clone(&compactor);
+        *compactor.worker.lock().unwrap() = Some(thread::spawn(move || worker.run(db)));
+        // Puede haber quedado trabajo pendiente de antes de reiniciar
+        compactor.notify();
+        compactor
+    }
+
+    pub fn notify(&self) {
+        self.requests.lock().unwrap().wake = true;
+        self.changed.notify_all();
+    }
+
+    // Para un motor ya cerrado: despierta al hilo y espera a que salga. Si estaba en medio de una
+    // compactación la termina (sin instalarla) antes de salir, así nadie escribe más en la carpeta.
+    // Sin el candado del motor tomado: el hilo lo necesita para salir.
+    pub fn join(&self) {
+        self.notify();
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            let _ = worker.join();
+        }
+    }
+
+    // COMPACT manual: encola UN paso forzado y espera a que termine (sin tener el candado del motor)
+    pub fn compact_and_wait(&self) -> io::Result<()> {
+        let mut requests = self.requests.lock().unwrap();
+        if requests.stopped {
+            return Err(io::Error::other("el keyspace fue borrado"));
+        }
+        requests.forced_requested += 1;
+        let ticket = requests.forced_requested;
+        self.changed.notify_all();
+        while requests.forced_done < ticket {
+            requests = self.changed.wait(requests).unwrap();
+        }
+        match &requests.last_error {
+            Some(e) => Err(io::Error::other(e.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn run(&self, db: Arc<RwLock<Engine>>) {
+        loop {
+            // Dormimos hasta que haya algo que hacer
+            let forced = {
+                let mut requests = self.requests.lock().unwrap();
+                while !requests.wake && requests.forced_done == requests.forced_requested {
+                    requests = self.changed.wait(requests).unwrap();
+                }
+                requests.wake = false;
+                (requests.forced_done < requests.forced_requested)
+                    .then_some(requests.forced_requested)
+            };
+
+            // Se borró el keyspace (KEYSPACE DROP): nadie más espera nada de este hilo
+            if db.read().unwrap().is_closed() {
+                let mut requests = self.requests.lock().unwrap();
+                requests.forced_done = requests.forced_requested;
+                requests.last_error = Some("el keyspace fue borrado".to_string());
+                requests.stopped = true;
+                self.changed.notify_all();
+                return;
+            }
+
+            if let Some(ticket) = forced {
+                let result = Compactor::step(&db, true);
+                let mut requests = self.requests.lock().unwrap();
+                requests.forced_done = ticket;
+                requests.last_error = result.err().map(|e| e.to_string());
+                self.changed.notify_all();
+            }
+
+            // Bajamos de a un paso mientras algún nivel siga excedido
+            loop {
+                match Compactor::step(&db, false) {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) => {
+                        println!("   ❌ Error en la compactación de fondo: {}", e);
+                        // No martillamos el disco si el error se repite
+                        thread::sleep(Duration::from_secs(1));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Un paso completo: elegir (con candado), ejecutar (sin candado), instalar (con candado)
+    fn step(db: &RwLock<Engine>, force: bool) -> io::Result<bool> {
+        let job = match db.write().unwrap().pick_compaction(force) {
+            Some(job) => job,
+            None => return Ok(false),
+        };
+        let output = job.run();
+        db.write().unwrap().install_compaction(output)?;
+        Ok(true)
+    }
+}
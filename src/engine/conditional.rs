@@ -0,0 +1,114 @@
+// Las ESCRITURAS CONDICIONALES (SETNX, SET ... IF-VERSION, CAS)
+// "Escribí esto solo si la llave sigue como yo la vi". La comparación y la escritura pasan con
+// el candado de escritura tomado, así que entre medio no se puede colar nadie: sirve para
+// elegir un líder, tomar un lock o mantener un contador sin carreras.
+//
+// La VERSIÓN de una llave es la secuencia de su escritura viva más nueva (cada SET/DEL tiene una
+// distinta y siempre creciente). Una llave que no existe (nunca escrita, borrada o vencida)
+// tiene versión 0: "espero la versión 0" es "solo si no existe" (SETNX).
+
+// Qué tiene que cumplir la llave para que la escritura se haga
+pub enum Expected {
+    Version(u64),
+    Value(Vec<u8>),
+}
+
+pub enum CasOutcome {
+    // Se escribió: la versión nueva de la llave
+    Applied(u64),
+    // No se escribió nada: la versión que tiene ahora (0 = no existe)
+    Mismatch(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::super::now_millis;
+    use super::super::testing::{self, TempDir};
+    use super::{CasOutcome, Expected};
+
+    fn applied(outcome: CasOutcome) -> Option<u64> {
+        match outcome {
+            CasOutcome::Applied(version) => Some(version),
+            CasOutcome::Mismatch(_) => None,
+        }
+    }
+
+    #[test]
+    fn version_zero_means_the_key_does_not_exist() {
+        let dir = TempDir::new("cas_zero");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let created = engine
+            .compare_and_set(b"lock", &Expected::Version(0), b"a")
+            .unwrap();
+        let version = applied(created).expect("no existía: se escribe");
+        assert_eq!(engine.version(b"lock").unwrap(), version);
+
+        // Ya existe: "solo si no existe" falla y avisa la versión que tiene
+        match engine
+            .compare_and_set(b"lock", &Expected::Version(0), b"b")
+            .unwrap()
+        {
+            CasOutcome::Mismatch(current) => assert_eq!(current, version),
+            CasOutcome::Applied(_) => panic!("la llave ya existía"),
+        }
+        assert_eq!(engine.get(b"lock").unwrap(), Some(b"a".to_vec()));
+
+        // Borrada vuelve a la versión 0
+        engine.delete(b"lock").unwrap();
+        assert_eq!(engine.version(b"lock").unwrap(), 0);
+        assert!(applied(
+            engine
+                .compare_and_set(b"lock", &Expected::Version(0), b"c")
+                .unwrap()
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn an_expired_key_is_at_version_zero() {
+        let dir = TempDir::new("cas_expired");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine
+            .set_with_deadline(b"session", b"old", now_millis() + 50)
+            .unwrap();
+        let live = engine.version(b"session").unwrap();
+        assert!(live > 0);
+        thread::sleep(Duration::from_millis(80));
+
+        // Vencida: para la versión y para el valor es como si no existiera
+        assert_eq!(engine.version(b"session").unwrap(), 0);
+        let stale = engine
+            .compare_and_set(b"session", &Expected::Version(live), b"x")
+            .unwrap();
+        assert!(matches!(stale, CasOutcome::Mismatch(0)));
+        let by_value = engine
+            .compare_and_set(b"session", &Expected::Value(b"old".to_vec()), b"x")
+            .unwrap();
+        assert!(matches!(by_value, CasOutcome::Mismatch(0)));
+        let fresh = engine
+            .compare_and_set(b"session", &Expected::Version(0), b"new")
+            .unwrap();
+        assert!(applied(fresh).is_some());
+        assert_eq!(engine.get(b"session").unwrap(), Some(b"new".to_vec()));
+    }
+
+    #[test]
+    fn a_stale_version_does_not_write() {
+        let dir = TempDir::new("cas_stale");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let first = engine.set(b"counter", b"1").unwrap();
+        let second = engine.set(b"counter", b"2").unwrap();
+        let outcome = engine
+            .compare_and_set(b"counter", &Expected::Version(first), b"x")
+            .unwrap();
+        assert!(matches!(outcome, CasOutcome::Mismatch(v) if v == second));
+        let outcome = engine
+            .compare_and_set(b"counter", &Expected::Version(second), b"3")
+            .unwrap();
+        assert!(applied(outcome).is_some_and(|v| v > second));
+        assert_eq!(engine.get(b"counter").unwrap(), Some(b"3".to_vec()));
+    }
+}
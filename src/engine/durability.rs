@@ -0,0 +1,300 @@
+// La DURABILIDAD (cuándo una escritura está REALMENTE a salvo)
+// Que `write` haya vuelto no significa que los bytes estén en el disco: están en el caché del SO,
+// y un corte de luz se los lleva. Solo `fsync` lo garantiza... pero cuesta milisegundos.
+//
+// Políticas:
+//   - Always:      cada SET espera su fsync antes del OK (0% de pérdida ante un corte de luz).
+//   - Every(N ms): un hilo hace fsync cada N ms; el OK es inmediato y se puede perder hasta N ms.
+//   - OsManaged:   nunca hacemos fsync; el SO escribe cuando quiere (lo más rápido, lo más frágil).
+//
+// GROUP COMMIT: con `Always`, varios hilos esperando a la vez comparten UN solo fsync.
+// El primero en llegar se vuelve "líder": hace el fsync para todo lo escrito hasta ese momento,
+// mientras los demás duermen. Al terminar despierta a todos, y los que quedaron cubiertos se van.
+
+use std::fs::File;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Durability {
+    Always,
+    Every(Duration),
+    OsManaged,
+}
+
+impl Durability {
+    // "always", "os-managed" o "every-100ms"
+    pub fn from_name(name: &str) -> Option<Durability> {
+        match name.to_lowercase().as_str() {
+            "always" => Some(Durability::Always),
+            "os-managed" | "os_managed" | "os" => Some(Durability::OsManaged),
+            other => other
+                .strip_prefix("every-")
+                .and_then(|ms| ms.strip_suffix("ms"))
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .filter(|ms| *ms > 0)
+                .map(|ms| Durability::Every(Duration::from_millis(ms))),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Durability::Always => "always".to_string(),
+            Durability::Every(interval) => format!("every-{}ms", interval.as_millis()),
+            Durability::OsManaged => "os-managed".to_string(),
+        }
+    }
+}
+
+struct SyncState {
+    // Copia del descriptor del log activo (el fsync no necesita el candado del motor)
+    file: File,
+    // Hasta qué secuencia ya se escribió en el log / ya pasó por un fsync
+    written: u64,
+    synced: u64,
+    // Hay un líder haciendo fsync en este momento
+    syncing: bool,
+    fsyncs: u64,
+    // Si un fsync falla no sabemos qué quedó en disco: de ahí en adelante todo es error
+    failed: Option<String>,
+}
+
+pub struct GroupCommit {
+    policy: Durability,
+    state: Mutex<SyncState>,
+    done: Condvar,
+}
+
+impl GroupCommit {
+    pub fn new(policy: Durability, file: File, last_sequence: u64) -> Arc<Self> {
+        let commit = Arc::new(GroupCommit {
+            policy,
+            state: Mutex::new(SyncState {
+                file,
+                written: last_sequence,
+                synced: last_sequence,
+                syncing: false,
+                fsyncs: 0,
+                failed: None,
+            }),
+            done: Condvar::new(),
+        });
+        if let Durability::Every(interval) = policy {
+            // El hilo solo tiene una referencia débil: cuando el motor se cae, el hilo termina solo
+            let weak = Arc::downgrade(&commit);
+            thread::spawn(move || periodic_sync(weak, interval));
+        }
+        commit
+    }
+
+    pub fn policy(&self) -> Durability {
+        self.policy
+    }
+
+    // El motor avisa que ya escribió (sin fsync) hasta esta secuencia
+    pub fn written(&self, seq: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.written = state.written.max(seq);
+    }
+
+    // Rotación de log: el log viejo ya pasó por fsync (o va a quedar en una SSTable),
+    // así que todo lo escrito hasta acá cuenta como durable y seguimos con el archivo nuevo
+    pub fn switch_file(&self, file: File) {
+        let mut state = self.state.lock().unwrap();
+        state.file = file;
+        state.synced = state.written;
+        self.done.notify_all();
+    }
+
+    // Bloquea hasta que `seq` sea durable según la política. Se llama SIN el candado del motor.
+    pub fn wait(&self, seq: u64) -> io::Result<()> {
+        if self.policy != Durability::Always {
+            return Ok(());
+        }
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(e) = &state.failed {
+                return Err(io::Error::other(e.clone()));
+            }
+            if state.synced >= seq {
+                return Ok(());
+            }
+            if state.syncing {
+                // Ya hay un líder: esperamos a que termine (quizás nos cubre)
+                state = self.done.wait(state).unwrap();
+            } else {
+                state = self.sync_locked(state);
+            }
+        }
+    }
+
+    // Somos el líder: fsync de todo lo escrito hasta ahora, soltando el candado mientras dura
+    fn sync_locked<'a>(
+        &'a self,
+        mut state: std::sync::MutexGuard<'a, SyncState>,
+    ) -> std::sync::MutexGuard<'a, SyncState> {
+        let target = state.written;
+        let file = match state.file.try_clone() {
+            Ok(file) => file,
+            Err(e) => {
+                state.failed = Some(e.to_string());
+                self.done.notify_all();
+                return state;
+            }
+        };
+        state.syncing = true;
+        drop(state);
+
+        let result = file.sync_data();
+
+        let mut state = self.state.lock().unwrap();
+        state.syncing = false;
+        state.fsyncs += 1;
+        match result {
+            Ok(()) => state.synced = state.synced.max(target),
+            Err(e) => state.failed = Some(e.to_string()),
+        }
+        self.done.notify_all();
+        state
+    }
+
+    // fsync de lo pendiente (si hay algo y nadie lo está haciendo ya)
+    fn sync_now(&self) {
+        let state = self.state.lock().unwrap();
+        if state.synced < state.written && !state.syncing && state.failed.is_none() {
+            drop(self.sync_locked(state));
+        }
+    }
+
+    pub fn synced_sequence(&self) -> u64 {
+        self.state.lock().unwrap().synced
+    }
+
+    pub fn fsyncs(&self) -> u64 {
+        self.state.lock().unwrap().fsyncs
+    }
+}
+
+fn periodic_sync(commit: Weak<GroupCommit>, interval: Duration) {
+    loop {
+        thread::sleep(interval);
+        match commit.upgrade() {
+            Some(commit) => commit.sync_now(),
+            None => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::super::testing::TempDir;
+    use super::{Durability, GroupCommit};
+
+    fn commit(dir: &TempDir, policy: Durability) -> Arc<GroupCommit> {
+        let file = File::create(dir.join("000001.log")).unwrap();
+        GroupCommit::new(policy, file, 0)
+    }
+
+    #[test]
+    fn policy_names() {
+        for policy in [
+            Durability::Always,
+            Durability::OsManaged,
+            Durability::Every(Duration::from_millis(100)),
+        ] {
+            assert_eq!(Durability::from_name(&policy.name()), Some(policy));
+        }
+        assert_eq!(
+            Durability::from_name("EVERY-5ms"),
+            Some(Durability::Every(Duration::from_millis(5)))
+        );
+        for bad in ["every-0ms", "every-ms", "every-5s", "sometimes"] {
+            assert_eq!(Durability::from_name(bad), None, "{}", bad);
+        }
+    }
+
+    // Con Every el OK no espera: el fsync llega después, del hilo de fondo, y sin escrituras
+    // nuevas no se repite
+    #[test]
+    fn every_syncs_in_the_background() {
+        let dir = TempDir::new("durability_every");
+        let commit = commit(&dir, Durability::Every(Duration::from_millis(20)));
+        commit.written(5);
+        let started = Instant::now();
+        commit.wait(5).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(20));
+
+        while commit.synced_sequence() < 5 {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "nunca hubo fsync"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+        let fsyncs = commit.fsyncs();
+        assert!(fsyncs >= 1);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(commit.fsyncs(), fsyncs);
+
+        // Lo que se escribe en la misma ventana entra en un solo fsync (dos si el hilo justo se
+        // despertó en el medio)
+        for seq in 6..=20 {
+            commit.written(seq);
+        }
+        while commit.synced_sequence() < 20 {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "nunca hubo fsync"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!((1..=2).contains(&(commit.fsyncs() - fsyncs)));
+    }
+
+    // Con Always el que espera hace el fsync de TODO lo escrito: los que vienen detrás ya están
+    // cubiertos y no hacen otro
+    #[test]
+    fn always_shares_one_fsync() {
+        let dir = TempDir::new("durability_always");
+        let commit = commit(&dir, Durability::Always);
+        commit.written(10);
+        commit.wait(3).unwrap();
+        assert_eq!((commit.fsyncs(), commit.synced_sequence()), (1, 10));
+        commit.wait(10).unwrap();
+        assert_eq!(commit.fsyncs(), 1);
+
+        // Muchos hilos a la vez: nunca más fsyncs que escritores, y todos terminan durables
+        commit.written(30);
+        let waiters: Vec<_> = (11..=30)
+            .map(|seq| {
+                let commit = Arc::clone(&commit);
+                thread::spawn(move || commit.wait(seq).unwrap())
+            })
+            .collect();
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(commit.synced_sequence(), 30);
+        assert!(commit.fsyncs() <= 1 + 20);
+    }
+
+    #[test]
+    fn os_managed_never_syncs() {
+        let dir = TempDir::new("durability_os");
+        let commit = commit(&dir, Durability::OsManaged);
+        commit.written(5);
+        commit.wait(5).unwrap();
+        assert_eq!((commit.fsyncs(), commit.synced_sequence()), (0, 0));
+
+        // Rotar el log cuenta lo escrito como durable (el log viejo ya no se toca)
+        commit.switch_file(File::create(dir.join("000002.log")).unwrap());
+        assert_eq!(commit.synced_sequence(), 5);
+    }
+}
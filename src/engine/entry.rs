@@ -0,0 +1,143 @@
+// Qué guarda el motor para cada llave: un valor (quizás con vencimiento)... o una LÁPIDA (tombstone).
+// Borrar en un LSM no puede tocar las SSTables viejas (son inmutables), así que escribimos una
+// marca "esta llave fue borrada" que tapa a todas las versiones más viejas. La compactación
+// la tira recién cuando ya no queda ninguna versión vieja que pueda volver a aparecer.
+//
+// Un valor con VENCIMIENTO (EXPIRE/SETEX) lleva el instante absoluto (ms desde epoch) en que deja
+// de existir: pasado ese momento las lecturas lo tratan como una lápida, aunque nadie lo haya borrado.
+//
+// Un OPERANDO de MERGE no es un valor todavía: es "aplicale esto a lo que haya abajo". Las lecturas
+// juntan la pila de operandos hasta el último valor (o lápida) y recién ahí la resuelven con el
+// operador de merge (merge_operator.rs).
+//
+// Una LÁPIDA DE RANGO (DELRANGE/DELPREFIX) se guarda como versión de la llave donde empieza el
+// rango y lleva dónde termina: para esa llave es una lápida más; para el resto ver range_delete.rs.
+//
+// En los bloques de una SSTable (y en el WAL) el valor lleva un byte de tipo adelante:
+//   0 = lápida (sin datos) | 1 = valor + bytes | 2 = vence_en u64 + bytes | 3 = operando + bytes
+//   4 = lápida de rango + (0 = sin final | 1 + llave final)
+
+use std::io;
+
+const TAG_TOMBSTONE: u8 = 0;
+const TAG_VALUE: u8 = 1;
+const TAG_EXPIRING: u8 = 2;
+const TAG_MERGE: u8 = 3;
+const TAG_RANGE_DELETE: u8 = 4;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Entry {
+    Value(Vec<u8>),
+    Expiring(Vec<u8>, u64),
+    Tombstone,
+    Merge(Vec<u8>),
+    // Hasta qué llave (sin incluir) borra; None = hasta el final
+    RangeDelete(Option<Vec<u8>>),
+}
+
+impl Entry {
+    // Bytes "útiles" (para estimar el tamaño de la memtable)
+    pub fn len(&self) -> usize {
+        match self {
+            Entry::Value(value) | Entry::Merge(value) => value.len(),
+            Entry::Expiring(value, _) => value.len() + 8,
+            Entry::Tombstone => 0,
+            Entry::RangeDelete(end) => end.as_ref().map_or(0, Vec::len),
+        }
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        matches!(self, Entry::Tombstone)
+    }
+
+    pub fn is_merge(&self) -> bool {
+        matches!(self, Entry::Merge(_))
+    }
+
+    // Cuándo vence (None = nunca)
+    pub fn deadline(&self) -> Option<u64> {
+        match self {
+            Entry::Expiring(_, deadline) => Some(*deadline),
+            _ => None,
+        }
+    }
+
+    // Lo que se escribió, sin mirar el vencimiento (una lápida no tiene valor; un operando se
+    // devuelve tal cual, hay que resolverlo antes si se quiere el valor de verdad)
+    pub fn into_value(self) -> Option<Vec<u8>> {
+        match self {
+            Entry::Value(value) | Entry::Expiring(value, _) | Entry::Merge(value) => Some(value),
+            Entry::Tombstone | Entry::RangeDelete(_) => None,
+        }
+    }
+
+    // La vista del usuario en el instante `now`: una lápida o un valor vencido es "no existe"
+    pub fn value_at(self, now: u64) -> Option<Vec<u8>> {
+        match self {
+            Entry::Expiring(_, deadline) if deadline <= now => None,
+            entry => entry.into_value(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Entry::Value(value) => {
+                let mut raw = Vec::with_capacity(1 + value.len());
+                raw.push(TAG_VALUE);
+                raw.extend_from_slice(value);
+                raw
+            }
+            Entry::Expiring(value, deadline) => {
+                let mut raw = Vec::with_capacity(9 + value.len());
+                raw.push(TAG_EXPIRING);
+                raw.extend_from_slice(&deadline.to_le_bytes());
+                raw.extend_from_slice(value);
+                raw
+            }
+            Entry::Tombstone => vec![TAG_TOMBSTONE],
+            Entry::Merge(operand) => {
+                let mut raw = Vec::with_capacity(1 + operand.len());
+                raw.push(TAG_MERGE);
+                raw.extend_from_slice(operand);
+                raw
+            }
+            Entry::RangeDelete(None) => vec![TAG_RANGE_DELETE, 0],
+            Entry::RangeDelete(Some(end)) => {
+                let mut raw = Vec::with_capacity(2 + end.len());
+                raw.extend_from_slice(&[TAG_RANGE_DELETE, 1]);
+                raw.extend_from_slice(end);
+                raw
+            }
+        }
+    }
+
+    pub fn decode(mut raw: Vec<u8>) -> io::Result<Entry> {
+        match raw.first() {
+            Some(&TAG_VALUE) => {
+                raw.remove(0);
+                Ok(Entry::Value(raw))
+            }
+            Some(&TAG_EXPIRING) if raw.len() >= 9 => {
+                let deadline = u64::from_le_bytes(raw[1..9].try_into().unwrap());
+                raw.drain(..9);
+                Ok(Entry::Expiring(raw, deadline))
+            }
+            Some(&TAG_TOMBSTONE) if raw.len() == 1 => Ok(Entry::Tombstone),
+            Some(&TAG_MERGE) => {
+                raw.remove(0);
+                Ok(Entry::Merge(raw))
+            }
+            Some(&TAG_RANGE_DELETE) if raw.get(1) == Some(&0) && raw.len() == 2 => {
+                Ok(Entry::RangeDelete(None))
+            }
+            Some(&TAG_RANGE_DELETE) if raw.get(1) == Some(&1) => {
+                raw.drain(..2);
+                Ok(Entry::RangeDelete(Some(raw)))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "tipo de entrada desconocido en la SSTable",
+            )),
+        }
+    }
+}
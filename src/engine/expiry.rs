@@ -0,0 +1,211 @@
+// El VENCIMIENTO de llaves (TTL: EXPIRE, SETEX, TTL, PERSIST)
+// Cada valor con vencimiento guarda el instante ABSOLUTO (ms desde epoch) en que deja de existir,
+// en el WAL y en las SSTables, así que sobrevive a los reinicios. Hay dos formas de que se vaya:
+//
+//   - PEREZOSA: cada lectura compara el vencimiento con la hora y, si ya pasó, contesta "no existe".
+//   - ACTIVA:   un hilo barrendero despierta cada tanto, mira las llaves con vencimiento más
+//               próximo (de a tandas, como el ciclo de Redis) y les escribe una lápida a las vencidas,
+//               así el espacio se recupera aunque nadie las vuelva a leer.
+//
+// El barrendero se guía por un índice en memoria (vencimiento, llave) que se arma al escribir y,
+// al arrancar, releyendo la memtable y solo las SSTables que tienen alguna entrada con vencimiento.
+// El índice puede quedar viejo (la llave se reescribió o se borró): antes de borrar se confirma.
+
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use super::Engine;
+
+// Cada cuánto despierta el barrendero y cuántas llaves mira por tanda
+const SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+pub const SWEEP_BATCH: usize = 20;
+
+// Lo que contesta TTL (con la semántica de Redis: -2 no existe, -1 sin vencimiento)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ttl {
+    Missing,
+    Persistent,
+    ExpiresIn(Duration),
+}
+
+pub struct ExpirySweeper;
+
+impl ExpirySweeper {
+    pub fn start(db: Arc<RwLock<Engine>>) {
+        thread::spawn(move || loop {
+            thread::sleep(SWEEP_INTERVAL);
+            // Se borró el keyspace (KEYSPACE DROP): el barrendero se jubila con él
+            if db.read().unwrap().is_closed() {
+                return;
+            }
+            // Mientras las tandas vengan llenas hay más vencidas esperando: seguimos (soltando el
+            // candado entre tanda y tanda para no frenar a las conexiones)
+            loop {
+                match ExpirySweeper::sweep(&db) {
+                    Ok(sampled) if sampled == SWEEP_BATCH => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        println!("   ❌ Error al barrer llaves vencidas: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    fn sweep(db: &RwLock<Engine>) -> io::Result<usize> {
+        let (sampled, evicted) = db.write().unwrap().evict_expired(SWEEP_BATCH)?;
+        if evicted > 0 {
+            println!("   ⌛ {} llaves vencidas eliminadas.", evicted);
+        }
+        Ok(sampled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::super::entry::Entry;
+    use super::super::testing::{self, TempDir};
+    use super::super::{now_millis, Engine};
+    use super::{ExpirySweeper, Ttl, SWEEP_BATCH};
+
+    // La versión más nueva de la llave tal como está guardada (sin mirar la hora)
+    fn newest(engine: &Engine, key: &[u8]) -> Option<Entry> {
+        engine
+            .lookup_resolved(key, engine.last_sequence)
+            .unwrap()
+            .map(|(_, entry)| entry)
+    }
+
+    fn expired(engine: &Engine) -> u64 {
+        engine.stats.expired_keys.load(Ordering::Relaxed)
+    }
+
+    // Vencidas de entrada (el vencimiento ya pasó): la lectura perezosa ya no las ve, pero siguen
+    // guardadas hasta que pasa el barrendero
+    #[test]
+    fn the_sweeper_deletes_in_batches() {
+        let dir = TempDir::new("expiry_batches");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let past = now_millis() - 1;
+        for i in 0..50 {
+            let key = format!("gone{:02}", i);
+            engine
+                .set_with_deadline(key.as_bytes(), b"x", past - i)
+                .unwrap();
+        }
+        engine
+            .set_with_deadline(b"later", b"x", now_millis() + 3_600_000)
+            .unwrap();
+        engine.set(b"plain", b"x").unwrap();
+        assert_eq!(engine.get(b"gone00").unwrap(), None);
+        assert!(matches!(
+            newest(&engine, b"gone00"),
+            Some(Entry::Expiring(..))
+        ));
+
+        let mut batches = Vec::new();
+        loop {
+            let (sampled, evicted) = engine.evict_expired(SWEEP_BATCH).unwrap();
+            assert_eq!(sampled, evicted);
+            batches.push(sampled);
+            if sampled < SWEEP_BATCH {
+                break;
+            }
+        }
+        assert_eq!(batches, vec![20, 20, 10]);
+        assert_eq!(expired(&engine), 50);
+        assert_eq!(newest(&engine, b"gone00"), Some(Entry::Tombstone));
+        assert_eq!(newest(&engine, b"gone49"), Some(Entry::Tombstone));
+        assert!(matches!(engine.ttl(b"later").unwrap(), Ttl::ExpiresIn(_)));
+        assert_eq!(engine.ttl(b"plain").unwrap(), Ttl::Persistent);
+        assert_eq!(engine.expiring.len(), 1);
+    }
+
+    // La agenda quedó vieja: la llave se reescribió sin vencimiento, se borró, o se le corrió el
+    // vencimiento. Se revisa y no se borra nada.
+    #[test]
+    fn a_stale_agenda_entry_is_not_deleted() {
+        let dir = TempDir::new("expiry_stale");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let soon = now_millis() + 30;
+        for key in [&b"rewritten"[..], b"persisted", b"moved", b"deleted"] {
+            engine.set_with_deadline(key, b"x", soon).unwrap();
+        }
+        engine.set(b"rewritten", b"y").unwrap();
+        engine.persist(b"persisted").unwrap().unwrap();
+        engine
+            .expire_at(b"moved", now_millis() + 3_600_000)
+            .unwrap()
+            .unwrap();
+        engine.delete(b"deleted").unwrap();
+        thread::sleep(Duration::from_millis(40));
+
+        let last = engine.last_sequence;
+        let (sampled, evicted) = engine.evict_expired(SWEEP_BATCH).unwrap();
+        assert_eq!((sampled, evicted), (4, 0));
+        assert_eq!(engine.last_sequence, last);
+        assert_eq!(expired(&engine), 0);
+        assert_eq!(engine.get(b"rewritten").unwrap(), Some(b"y".to_vec()));
+        assert_eq!(engine.get(b"persisted").unwrap(), Some(b"x".to_vec()));
+        assert!(matches!(engine.ttl(b"moved").unwrap(), Ttl::ExpiresIn(_)));
+    }
+
+    // La agenda se rearma al abrir: de la memtable (vía WAL) y de las SSTables con vencimientos
+    #[test]
+    fn the_agenda_is_rebuilt_on_reopen() {
+        let dir = TempDir::new("expiry_reopen");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let past = now_millis() - 1;
+        engine.set_with_deadline(b"on_disk", b"x", past).unwrap();
+        engine.flush().unwrap();
+        engine.set_with_deadline(b"in_log", b"x", past).unwrap();
+        engine.set(b"plain", b"x").unwrap();
+        drop(engine);
+
+        let mut engine = testing::open(&dir, testing::small_options());
+        assert_eq!(engine.expiring.len(), 2);
+        assert_eq!(engine.evict_expired(SWEEP_BATCH).unwrap(), (2, 2));
+        assert_eq!(newest(&engine, b"on_disk"), Some(Entry::Tombstone));
+        assert_eq!(newest(&engine, b"in_log"), Some(Entry::Tombstone));
+    }
+
+    // El hilo de verdad: barre solo, y termina cuando se cierra el keyspace
+    #[test]
+    fn the_background_sweeper() {
+        let dir = TempDir::new("expiry_thread");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let past = now_millis() - 1;
+        for i in 0..45 {
+            let key = format!("gone{:02}", i);
+            engine
+                .set_with_deadline(key.as_bytes(), b"x", past)
+                .unwrap();
+        }
+        let db = Arc::new(RwLock::new(engine));
+        ExpirySweeper::start(Arc::clone(&db));
+
+        let started = Instant::now();
+        while expired(&db.read().unwrap()) < 45 {
+            assert!(started.elapsed() < Duration::from_secs(5), "no barrió");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(db.read().unwrap().expiring.is_empty());
+        db.write().unwrap().close().unwrap();
+        // Al despertar ve el keyspace cerrado y suelta su referencia
+        while Arc::strong_count(&db) > 1 {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "el hilo no terminó"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// La HISTORIA de una llave (el comando HISTORY)
+// Las versiones de una llave ya están juntas y ordenadas por secuencia en las llaves internas, y
+// como los momentos de escritura crecen con la secuencia, también por tiempo: los bordes de la
+// ventana [start, end] se encuentran con una búsqueda binaria sobre las secuencias y de ahí se
+// recorre (memtables + SSTables mezcladas) solo lo que entra en la página.
+//
+// Las historias pueden ser enormes, así que se devuelven de a PÁGINAS. El cursor es la secuencia
+// de la última versión entregada: las escrituras nuevas siempre tienen secuencias más altas,
+// así que seguir "después de la secuencia X" no se desordena aunque entren escrituras entre páginas.
+
+use super::entry::Entry;
+use super::key;
+
+#[derive(Clone, Copy)]
+pub struct HistoryQuery {
+    // Ventana de tiempo, en ms desde epoch (ambas puntas incluidas)
+    pub start: u64,
+    pub end: u64,
+    pub limit: usize,
+    // De la más nueva a la más vieja
+    pub reverse: bool,
+    // Lo que devolvió la página anterior (None = primera página)
+    pub cursor: Option<u64>,
+}
+
+pub struct HistoryEntry {
+    pub seq: u64,
+    pub time: u64,
+    // None = en ese momento la llave se borró (lápida)
+    pub value: Option<Vec<u8>>,
+}
+
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    // Para pedir la página siguiente (None = no hay más)
+    pub cursor: Option<u64>,
+}
+
+// `versions` ya viene en el orden de la página y con los operandos resueltos: hasta limit + 1
+// versiones (si sobra una, hay otra página)
+pub fn page(versions: Vec<(Vec<u8>, Entry)>, limit: usize) -> HistoryPage {
+    let more = versions.len() > limit;
+    let entries: Vec<HistoryEntry> = versions
+        .into_iter()
+        .take(limit)
+        .map(|(internal, entry)| HistoryEntry {
+            seq: key::seq(&internal),
+            time: key::time(&internal),
+            value: entry.into_value(),
+        })
+        .collect();
+    let cursor = if more {
+        entries.last().map(|e| e.seq)
+    } else {
+        None
+    };
+    HistoryPage { entries, cursor }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::merge_operator::AddOperator;
+    use super::super::testing::{self, TempDir};
+    use super::super::Engine;
+    use super::HistoryQuery;
+
+    fn query(start: u64, end: u64, limit: usize, reverse: bool) -> HistoryQuery {
+        HistoryQuery {
+            start,
+            end,
+            limit,
+            reverse,
+            cursor: None,
+        }
+    }
+
+    // Sigue el cursor hasta el final; cada versión como texto ("-" = borrada)
+    fn all_pages(engine: &Engine, key: &[u8], mut query: HistoryQuery) -> Vec<String> {
+        let mut seen = Vec::new();
+        loop {
+            let page = engine.history(key, &query).unwrap();
+            assert!(page.entries.len() <= query.limit);
+            seen.extend(page.entries.iter().map(|entry| match &entry.value {
+                Some(value) => String::from_utf8(value.clone()).unwrap(),
+                None => "-".to_string(),
+            }));
+            match page.cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return seen,
+            }
+        }
+    }
+
+    fn versions(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("v{}", i)).collect()
+    }
+
+    // 25 versiones repartidas entre SSTables de varios niveles y la memtable, con llaves vecinas
+    // a los dos lados que no se tienen que colar
+    fn write_versions(engine: &mut Engine, from: usize, to: usize) {
+        for i in from..to {
+            engine.set(b"j", b"vecina").unwrap();
+            engine.set(b"k", format!("v{}", i).as_bytes()).unwrap();
+            engine.set(b"l", b"vecina").unwrap();
+            if i % 7 == 6 {
+                engine.flush().unwrap();
+            }
+            if i == 13 {
+                testing::compact_all(engine);
+            }
+        }
+    }
+
+    #[test]
+    fn pages_in_both_directions() {
+        let dir = TempDir::new("history_pages");
+        let mut engine = testing::open(&dir, testing::small_options());
+        write_versions(&mut engine, 0, 25);
+
+        for limit in [1, 4, 25, 100] {
+            let forward = all_pages(&engine, b"k", query(0, u64::MAX, limit, false));
+            assert_eq!(forward, versions(0..25), "limit {}", limit);
+            let mut backward = all_pages(&engine, b"k", query(0, u64::MAX, limit, true));
+            backward.reverse();
+            assert_eq!(backward, versions(0..25), "limit {}", limit);
+        }
+        // Una página justa no promete otra
+        let page = engine
+            .history(b"k", &query(0, u64::MAX, 25, false))
+            .unwrap();
+        assert_eq!(page.cursor, None);
+        let page = engine.history(b"k", &query(0, u64::MAX, 24, true)).unwrap();
+        assert!(page.cursor.is_some());
+        assert!(all_pages(&engine, b"nada", query(0, u64::MAX, 4, false)).is_empty());
+    }
+
+    #[test]
+    fn only_the_time_window() {
+        let dir = TempDir::new("history_window");
+        let mut engine = testing::open(&dir, testing::small_options());
+        write_versions(&mut engine, 0, 5);
+        let start = testing::instant_between();
+        write_versions(&mut engine, 5, 15);
+        let end = testing::instant_between();
+        write_versions(&mut engine, 15, 25);
+
+        for limit in [3, 100] {
+            assert_eq!(
+                all_pages(&engine, b"k", query(start, end, limit, false)),
+                versions(5..15)
+            );
+            let mut backward = all_pages(&engine, b"k", query(start, end, limit, true));
+            backward.reverse();
+            assert_eq!(backward, versions(5..15));
+            assert_eq!(
+                all_pages(&engine, b"k", query(0, start, limit, false)),
+                versions(0..5)
+            );
+            assert_eq!(
+                all_pages(&engine, b"k", query(end, u64::MAX, limit, false)),
+                versions(15..25)
+            );
+        }
+        assert!(all_pages(&engine, b"k", query(1, start - 100, 10, false)).is_empty());
+    }
+
+    // El cursor es una secuencia: lo escrito entre páginas no desordena ni repite nada
+    #[test]
+    fn a_cursor_survives_writes_between_pages() {
+        let dir = TempDir::new("history_cursor");
+        let mut engine = testing::open(&dir, testing::small_options());
+        write_versions(&mut engine, 0, 10);
+
+        let mut forward = query(0, u64::MAX, 4, false);
+        let mut backward = query(0, u64::MAX, 4, true);
+        let first = engine.history(b"k", &forward).unwrap();
+        let last = engine.history(b"k", &backward).unwrap();
+        forward.cursor = first.cursor;
+        backward.cursor = last.cursor;
+
+        write_versions(&mut engine, 10, 20);
+        testing::compact_all(&mut engine);
+
+        // Para adelante, las nuevas aparecen al final; para atrás, no aparecen
+        let mut seen: Vec<String> = first
+            .entries
+            .iter()
+            .map(|e| String::from_utf8(e.value.clone().unwrap()).unwrap())
+            .collect();
+        seen.extend(all_pages(&engine, b"k", forward));
+        assert_eq!(seen, versions(0..20));
+        let mut seen: Vec<String> = last
+            .entries
+            .iter()
+            .map(|e| String::from_utf8(e.value.clone().unwrap()).unwrap())
+            .collect();
+        seen.extend(all_pages(&engine, b"k", backward));
+        seen.reverse();
+        assert_eq!(seen, versions(0..10));
+    }
+
+    // Un borrado por rango que tapó la llave es una versión borrada más, en su lugar
+    #[test]
+    fn a_range_delete_is_part_of_the_history() {
+        let dir = TempDir::new("history_range");
+        let mut engine = testing::open(&dir, testing::small_options());
+        write_versions(&mut engine, 0, 3);
+        engine.delete_range(b"a", Some(b"z")).unwrap();
+        engine.set(b"k", b"v3").unwrap();
+        engine.flush().unwrap();
+        engine.delete_prefix(b"k").unwrap();
+        engine.delete(b"k").unwrap();
+        engine.set(b"k", b"v4").unwrap();
+
+        let expected = ["v0", "v1", "v2", "-", "v3", "-", "-", "v4"];
+        for limit in [1, 2, 3, 100] {
+            assert_eq!(
+                all_pages(&engine, b"k", query(0, u64::MAX, limit, false)),
+                expected
+            );
+            let mut backward = all_pages(&engine, b"k", query(0, u64::MAX, limit, true));
+            backward.reverse();
+            assert_eq!(backward, expected);
+        }
+        // El rango que empieza justo en la llave no se cuenta dos veces
+        assert_eq!(
+            all_pages(&engine, b"a", query(0, u64::MAX, 10, false)),
+            ["-"]
+        );
+    }
+
+    // Cada versión muestra el valor que tenía la llave, con los operandos ya aplicados, aunque la
+    // base quede en una página anterior
+    #[test]
+    fn merge_operands_are_resolved_per_version() {
+        let dir = TempDir::new("history_merge");
+        let mut options = testing::small_options();
+        options.merge_operator = Some(Arc::new(AddOperator));
+        let mut engine = testing::open(&dir, options);
+        engine.set(b"n", b"10").unwrap();
+        engine.merge(b"n", b"1").unwrap();
+        engine.flush().unwrap();
+        engine.merge(b"n", b"2").unwrap();
+        engine.delete(b"n").unwrap();
+        engine.merge(b"n", b"5").unwrap();
+
+        let expected = ["10", "11", "13", "-", "5"];
+        for limit in [1, 2, 100] {
+            assert_eq!(
+                all_pages(&engine, b"n", query(0, u64::MAX, limit, false)),
+                expected
+            );
+            let mut backward = all_pages(&engine, b"n", query(0, u64::MAX, limit, true));
+            backward.reverse();
+            assert_eq!(backward, expected);
+        }
+    }
+}
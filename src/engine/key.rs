@@ -0,0 +1,88 @@
+// La LLAVE INTERNA: (llave del usuario, número de secuencia, momento de escritura)
+// Cada escritura lleva su número de secuencia, y guardamos TODAS las versiones de una llave
+// (hasta que la compactación decide que ya nadie puede verlas). Para que las tablas, el índice
+// y el mezclador sigan comparando bytes crudos sin saber nada de versiones, la llave interna
+// se codifica de forma que el orden de bytes sea justo el que queremos:
+//
+//   user_key escapada | 0x00 0x01 | (u64::MAX - seq) big-endian | time (ms) big-endian
+//
+//   - escapada: cada 0x00 de la llave se escribe 0x00 0xFF, así el terminador 0x00 0x01 siempre
+//     queda antes que cualquier continuación ("a" < "a\0" < "ab" se respeta).
+//   - la secuencia va invertida: para una misma llave, la versión MÁS NUEVA queda PRIMERO.
+//   - el momento de escritura va al final: no cambia el orden (la secuencia ya es única) pero
+//     viaja con cada versión, así los viajes en el tiempo (GETAT) no necesitan otro índice.
+//
+// Buscar "la llave k vista desde la secuencia S" es buscar la primera llave interna >= lookup(k, S).
+
+use std::io;
+
+const SEQ_SIZE: usize = 8;
+const TRAILER_SIZE: usize = SEQ_SIZE + 8;
+
+// La llave escapada, sin terminador: sirve para comparar PREFIJOS de llaves
+pub fn escape(user_key: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(user_key.len() + 2 + TRAILER_SIZE);
+    for byte in user_key {
+        out.push(*byte);
+        if *byte == 0 {
+            out.push(0xFF);
+        }
+    }
+    out
+}
+
+// Lo que va antes de la secuencia: la llave escapada + terminador
+pub fn prefix(user_key: &[u8]) -> Vec<u8> {
+    let mut out = escape(user_key);
+    out.extend_from_slice(&[0x00, 0x01]);
+    out
+}
+
+pub fn encode(user_key: &[u8], seq: u64, time: u64) -> Vec<u8> {
+    let mut out = prefix(user_key);
+    out.extend_from_slice(&(u64::MAX - seq).to_be_bytes());
+    out.extend_from_slice(&time.to_be_bytes());
+    out
+}
+
+// La llave interna más chica posible para (k, seq): de ahí en adelante están las versiones <= seq
+pub fn lookup(user_key: &[u8], seq: u64) -> Vec<u8> {
+    encode(user_key, seq, 0)
+}
+
+// La parte de la llave interna que identifica a la llave del usuario (comparable con `prefix`)
+pub fn user_part(internal: &[u8]) -> &[u8] {
+    &internal[..internal.len().saturating_sub(TRAILER_SIZE)]
+}
+
+fn trailer(internal: &[u8]) -> Option<(u64, u64)> {
+    let pos = internal.len().checked_sub(TRAILER_SIZE)?;
+    let seq = u64::from_be_bytes(internal[pos..pos + SEQ_SIZE].try_into().unwrap());
+    let time = u64::from_be_bytes(internal[pos + SEQ_SIZE..].try_into().unwrap());
+    Some((u64::MAX - seq, time))
+}
+
+pub fn seq(internal: &[u8]) -> u64 {
+    trailer(internal).map_or(0, |(seq, _)| seq)
+}
+
+pub fn time(internal: &[u8]) -> u64 {
+    trailer(internal).map_or(0, |(_, time)| time)
+}
+
+// De vuelta a la llave del usuario (sin escapes)
+pub fn user_key(internal: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "llave interna corrupta");
+    let escaped = user_part(internal)
+        .strip_suffix(&[0x00, 0x01])
+        .ok_or_else(invalid)?;
+    let mut out = Vec::with_capacity(escaped.len());
+    let mut bytes = escaped.iter();
+    while let Some(byte) = bytes.next() {
+        out.push(*byte);
+        if *byte == 0 && bytes.next() != Some(&0xFF) {
+            return Err(invalid());
+        }
+    }
+    Ok(out)
+}
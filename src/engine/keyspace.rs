@@ -0,0 +1,355 @@
+// Los KEYSPACES (varias bases lógicas dentro de un mismo servidor: SELECT/USE y KEYSPACE ...)
+// Cada keyspace es un motor completo con su memtable, sus SSTables, su MANIFEST y su configuración
+// (estrategia de compactación, tamaño de memtable, operador de MERGE, retención): las llaves de uno
+// no se ven desde otro y cada uno compacta por su lado. Lo que comparten es el WAL (un solo fsync
+// para todos, ver shared_wal.rs) y el caché de bloques (un solo presupuesto de memoria).
+//
+// En disco:
+//   chronos_data/               el keyspace por defecto (id 0, como siempre) y los logs compartidos
+//   chronos_data/KEYSPACES      el registro: los keyspaces creados, su id y su configuración
+//   chronos_data/keyspaces/x/   las SSTables y el MANIFEST del keyspace "x"
+//
+// Los ids no se reusan nunca: así las escrituras de un keyspace borrado que todavía estén en algún
+// log no terminan en uno nuevo que se llame igual.
+
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::cache::BlockCache;
+use super::compaction::strategy_from_name;
+use super::merge_operator::merge_operator_from_name;
+use super::shared_wal::SharedWal;
+use super::{Compactor, Engine, EngineOptions, ExpirySweeper, GroupCommit};
+
+pub const DEFAULT_KEYSPACE: &str = "default";
+
+const REGISTRY_FILE: &str = "KEYSPACES";
+const REGISTRY_TMP: &str = "KEYSPACES.tmp";
+const KEYSPACES_DIR: &str = "keyspaces";
+const MAX_NAME_LEN: usize = 64;
+
+// Lo que se elige al crear un keyspace (None = lo de siempre). El keyspace por defecto usa, en
+// cambio, las opciones de la línea de comandos.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct KeyspaceSettings {
+    pub compaction: Option<String>,
+    pub memtable_size: Option<usize>,
+    pub merge_operator: Option<String>,
+    pub history_retention: Option<Duration>,
+}
+
+impl KeyspaceSettings {
+    fn engine_options(&self) -> io::Result<EngineOptions> {
+        let mut options = EngineOptions::default();
+        if let Some(name) = &self.compaction {
+            options.compaction_strategy = strategy_from_name(name).ok_or_else(|| {
+                invalid(format!(
+                    "estrategia de compactación desconocida: '{}' (usar leveled, size-tiered o time-window)",
+                    name
+                ))
+            })?;
+        }
+        if let Some(size) = self.memtable_size {
+            options.memtable_size = size;
+        }
+        if let Some(name) = &self.merge_operator {
+            options.merge_operator = Some(merge_operator_from_name(name).ok_or_else(|| {
+                invalid(format!(
+                    "operador de merge desconocido: '{}' (usar add, max o append)",
+                    name
+                ))
+            })?);
+        }
+        options.history_retention = self.history_retention;
+        Ok(options)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RegistryEntry {
+    id: u32,
+    name: String,
+    settings: KeyspaceSettings,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Registry {
+    next_id: u32,
+    keyspaces: Vec<RegistryEntry>,
+}
+
+impl Registry {
+    fn load(dir: &Path) -> io::Result<Registry> {
+        let path = dir.join(REGISTRY_FILE);
+        if !path.exists() {
+            return Ok(Registry {
+                next_id: 1,
+                keyspaces: Vec::new(),
+            });
+        }
+        bincode::deserialize(&fs::read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    // Se escribe entero en un temporal y se renombra: o queda el registro viejo o el nuevo
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let raw = bincode::serialize(self).map_err(|e| io::Error::other(e.to_string()))?;
+        let tmp = dir.join(REGISTRY_TMP);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp)?;
+        file.write_all(&raw)?;
+        file.sync_all()?;
+        fs::rename(&tmp, dir.join(REGISTRY_FILE))
+    }
+}
+
+// Un keyspace abierto: su motor con su compactador y su barrendero
+pub struct Keyspace {
+    pub id: u32,
+    pub name: String,
+    pub db: Arc<RwLock<Engine>>,
+}
+
+impl Keyspace {
+    fn start(id: u32, name: String, engine: Engine) -> Arc<Keyspace> {
+        let db = Arc::new(RwLock::new(engine));
+        Compactor::start(Arc::clone(&db));
+        ExpirySweeper::start(Arc::clone(&db));
+        Arc::new(Keyspace { id, name, db })
+    }
+}
+
+struct KeyspaceState {
+    registry: Registry,
+    open: BTreeMap<u32, Arc<Keyspace>>,
+}
+
+pub struct Keyspaces {
+    dir: PathBuf,
+    wal: Arc<SharedWal>,
+    block_cache: Arc<BlockCache>,
+    state: RwLock<KeyspaceState>,
+}
+
+impl Keyspaces {
+    // Abre el keyspace por defecto (con `options`) y todos los del registro
+    pub fn open(filepath: &str, options: EngineOptions) -> io::Result<Keyspaces> {
+        let dir = PathBuf::from(filepath);
+        fs::create_dir_all(dir.join(KEYSPACES_DIR))?;
+        let wal = SharedWal::open(&dir, options.durability)?;
+        let block_cache = Arc::new(BlockCache::new(options.block_cache_size));
+        let registry = Registry::load(&dir)?;
+
+        let mut open = BTreeMap::new();
+        let engine = Engine::open(
+            dir.clone(),
+            options,
+            0,
+            Arc::clone(&wal),
+            Arc::clone(&block_cache),
+        )?;
+        open.insert(0, Keyspace::start(0, DEFAULT_KEYSPACE.to_string(), engine));
+        for entry in &registry.keyspaces {
+            println!("   🗂️  Abriendo keyspace '{}'...", entry.name);
+            let engine = Engine::open(
+                dir.join(KEYSPACES_DIR).join(&entry.name),
+                entry.settings.engine_options()?,
+                entry.id,
+                Arc::clone(&wal),
+                Arc::clone(&block_cache),
+            )?;
+            open.insert(
+                entry.id,
+                Keyspace::start(entry.id, entry.name.clone(), engine),
+            );
+        }
+        wal.finish_recovery()?;
+
+        // Carpetas que no figuran en el registro: un KEYSPACE DROP que se cortó a la mitad
+        for entry in fs::read_dir(dir.join(KEYSPACES_DIR))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !registry.keyspaces.iter().any(|k| k.name == name) {
+                fs::remove_dir_all(entry.path())?;
+                println!("   🧹 Keyspace huérfano '{}' eliminado.", name);
+            }
+        }
+
+        Ok(Keyspaces {
+            dir,
+            wal,
+            block_cache,
+            state: RwLock::new(KeyspaceState { registry, open }),
+        })
+    }
+
+    pub fn get(&self, id: u32) -> Option<Arc<Keyspace>> {
+        self.state.read().unwrap().open.get(&id).cloned()
+    }
+
+    // Por nombre, o por número como el SELECT de Redis (SELECT 0 = el de por defecto)
+    pub fn find(&self, name: &str) -> Option<Arc<Keyspace>> {
+        let state = self.state.read().unwrap();
+        let by_name = state.open.values().find(|k| k.name == name);
+        let by_id = || name.parse().ok().and_then(|id| state.open.get(&id));
+        by_name.or_else(by_id).cloned()
+    }
+
+    // Todos, en orden de creación (el de por defecto primero)
+    pub fn list(&self) -> Vec<Arc<Keyspace>> {
+        self.state.read().unwrap().open.values().cloned().collect()
+    }
+
+    pub fn create(&self, name: &str, settings: KeyspaceSettings) -> io::Result<Arc<Keyspace>> {
+        validate_name(name)?;
+        let options = settings.engine_options()?;
+        let mut state = self.state.write().unwrap();
+        if state.open.values().any(|k| k.name == name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("el keyspace '{}' ya existe", name),
+            ));
+        }
+
+        let id = state.registry.next_id;
+        let path = self.dir.join(KEYSPACES_DIR).join(name);
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        let engine = Engine::open(
+            path,
+            options,
+            id,
+            Arc::clone(&self.wal),
+            Arc::clone(&self.block_cache),
+        )?;
+        state.registry.next_id += 1;
+        state.registry.keyspaces.push(RegistryEntry {
+            id,
+            name: name.to_string(),
+            settings,
+        });
+        state.registry.save(&self.dir)?;
+
+        let keyspace = Keyspace::start(id, name.to_string(), engine);
+        state.open.insert(id, Arc::clone(&keyspace));
+        println!("   🗂️  Keyspace '{}' creado (id {}).", name, id);
+        Ok(keyspace)
+    }
+
+    // Borra el keyspace con todos sus datos. Las conexiones que lo tenían elegido reciben error.
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        if name == DEFAULT_KEYSPACE {
+            return Err(invalid(
+                "el keyspace por defecto no se puede borrar".to_string(),
+            ));
+        }
+        let mut state = self.state.write().unwrap();
+        let id = match state.registry.keyspaces.iter().find(|k| k.name == name) {
+            Some(entry) => entry.id,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("el keyspace '{}' no existe", name),
+                ))
+            }
+        };
+        // Primero el registro: si nos cortan acá, la carpeta se limpia al reabrir
+        state.registry.keyspaces.retain(|k| k.id != id);
+        state.registry.save(&self.dir)?;
+        let keyspace = state.open.remove(&id).unwrap();
+        drop(state);
+
+        // Cerrado, ya nadie escribe en la carpeta: ni las conexiones ni el barrendero (pasan por el
+        // candado) ni el compactador, que trabaja sin candado y por eso hay que esperarlo
+        keyspace.db.write().unwrap().close()?;
+        let compactor = keyspace.db.read().unwrap().compactor();
+        if let Some(compactor) = compactor {
+            compactor.join();
+        }
+        fs::remove_dir_all(self.dir.join(KEYSPACES_DIR).join(name))?;
+        println!("   🗑️  Keyspace '{}' eliminado.", name);
+        Ok(())
+    }
+
+    pub fn group_commit(&self) -> Arc<GroupCommit> {
+        self.wal.group_commit()
+    }
+
+    // Apagado: cada keyspace vuelca su memtable
+    pub fn flush_all(&self) -> io::Result<()> {
+        for keyspace in self.list() {
+            keyspace.db.write().unwrap().flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        // Un nombre solo de dígitos se confundiría con un número en SELECT
+        && !name.chars().all(|c| c.is_ascii_digit());
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "nombre de keyspace inválido: '{}' (hasta {} letras, dígitos, '-' o '_', no solo dígitos)",
+            name, MAX_NAME_LEN
+        )))
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, TempDir};
+    use super::{KeyspaceSettings, Keyspaces, KEYSPACES_DIR};
+
+    // KEYSPACE DROP de uno que clava el log más viejo: se espera a su compactador, se borra la
+    // carpeta y los logs que solo él necesitaba se van
+    #[test]
+    fn drop_releases_the_logs_it_pinned() {
+        let dir = TempDir::new("keyspace_drop");
+        let keyspaces = Keyspaces::open(dir.to_str().unwrap(), testing::small_options()).unwrap();
+        let sessions = keyspaces
+            .create("sessions", KeyspaceSettings::default())
+            .unwrap();
+        sessions.db.write().unwrap().set(b"s:1", b"x").unwrap();
+        let default = keyspaces.find("default").unwrap();
+        for round in 0..3 {
+            let mut engine = default.db.write().unwrap();
+            engine.set(b"k", &[round]).unwrap();
+            engine.flush().unwrap();
+        }
+        assert_eq!(keyspaces.wal.num_logs(), 4);
+
+        keyspaces.remove("sessions").unwrap();
+        assert_eq!(keyspaces.wal.num_logs(), 1);
+        assert!(!dir.join(KEYSPACES_DIR).join("sessions").exists());
+        assert!(keyspaces.find("sessions").is_none());
+        assert!(sessions.db.read().unwrap().is_closed());
+
+        // El nombre se puede volver a usar y arranca vacío
+        let again = keyspaces
+            .create("sessions", KeyspaceSettings::default())
+            .unwrap();
+        assert_eq!(again.db.read().unwrap().get(b"s:1").unwrap(), None);
+        assert_ne!(again.id, sessions.id);
+    }
+}
@@ -0,0 +1,81 @@
+// La MEMTABLE (La memoria de corto plazo del motor)
+// Todas las escrituras caen primero acá. Está ORDENADA (BTreeMap) para que al congelarla
+// podamos volcarla directo a una SSTable sin tener que ordenar nada.
+// Las llaves son LLAVES INTERNAS (ver key.rs): cada escritura agrega una versión nueva en vez de
+// pisar la anterior, así un snapshot viejo todavía puede leer lo que había.
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use super::entry::Entry;
+use super::key;
+use super::now_millis;
+
+// Bytes extra que estimamos por cada entrada (nodos del árbol, punteros, etc.)
+const ENTRY_OVERHEAD: usize = 32;
+
+pub struct MemTable {
+    map: BTreeMap<Vec<u8>, Entry>,
+    approximate_size: usize,
+    // Cuándo empezó a recibir escrituras (ms): es la punta vieja del rango de tiempo de su SSTable
+    created_at: u64,
+}
+
+impl MemTable {
+    pub fn new() -> Self {
+        MemTable {
+            map: BTreeMap::new(),
+            approximate_size: 0,
+            created_at: now_millis(),
+        }
+    }
+
+    // Un valor nuevo o una lápida: queda como la versión `seq` de la llave (las viejas siguen ahí)
+    pub fn insert(&mut self, user_key: &[u8], seq: u64, time: u64, entry: Entry) {
+        let internal = key::encode(user_key, seq, time);
+        self.approximate_size += internal.len() + entry.len() + ENTRY_OVERHEAD;
+        self.map.insert(internal, entry);
+    }
+
+    // La versión más nueva de la llave con secuencia <= seq (con su llave interna)
+    pub fn get(&self, user_key: &[u8], seq: u64) -> Option<(&Vec<u8>, &Entry)> {
+        let found = self.map.range(key::lookup(user_key, seq)..).next()?;
+        (key::user_part(found.0) == key::prefix(user_key).as_slice()).then_some(found)
+    }
+
+    // Las entradas desde la primera llave interna >= target, en orden
+    pub fn iter_from(&self, target: &[u8]) -> impl Iterator<Item = (&Vec<u8>, &Entry)> {
+        self.map.range(target.to_vec()..)
+    }
+
+    // Hacia atrás, las entradas con llave interna < target (None = desde la última)
+    pub fn iter_rev_before(
+        &self,
+        target: Option<&[u8]>,
+    ) -> impl Iterator<Item = (&Vec<u8>, &Entry)> {
+        let upper = target.map_or(Bound::Unbounded, |target| Bound::Excluded(target.to_vec()));
+        self.map.range((Bound::Unbounded, upper)).rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // Tamaño aproximado en bytes: lo usamos para decidir cuándo congelar y volcar a disco
+    pub fn approximate_size(&self) -> usize {
+        self.approximate_size
+    }
+
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    // Recorre las entradas en orden de llave interna (listo para escribir una SSTable)
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Entry)> {
+        self.map.iter()
+    }
+}
@@ -0,0 +1,95 @@
+// El MEZCLADOR (k-way merge)
+// Recibe varias fuentes ordenadas (SSTables) y devuelve UNA sola secuencia ordenada.
+// Si la misma llave aparece en varias fuentes, gana la fuente más NUEVA (la de índice 0).
+// Todo es streaming: nunca cargamos una tabla entera en memoria.
+// También anda HACIA ATRÁS (fuentes que vienen de mayor a menor): ahí sale primero la más grande.
+
+use std::io;
+
+use super::entry::Entry;
+
+type Record = (Vec<u8>, Entry);
+
+pub struct MergeIter<I: Iterator<Item = io::Result<Record>>> {
+    // Ordenadas de la más nueva (0) a la más vieja
+    sources: Vec<I>,
+    heads: Vec<Option<Record>>,
+    started: bool,
+    reverse: bool,
+}
+
+impl<I: Iterator<Item = io::Result<Record>>> MergeIter<I> {
+    pub fn new(sources: Vec<I>) -> Self {
+        let heads = sources.iter().map(|_| None).collect();
+        MergeIter {
+            sources,
+            heads,
+            started: false,
+            reverse: false,
+        }
+    }
+
+    // Para fuentes que recorren de la llave más grande a la más chica
+    pub fn reversed(sources: Vec<I>) -> Self {
+        MergeIter {
+            reverse: true,
+            ..MergeIter::new(sources)
+        }
+    }
+
+    // ¿La llave `a` sale antes (o junto) que `b`?
+    fn ahead(&self, a: &[u8], b: &[u8]) -> bool {
+        if self.reverse {
+            a >= b
+        } else {
+            a <= b
+        }
+    }
+
+    fn advance(&mut self, i: usize) -> io::Result<()> {
+        self.heads[i] = self.sources[i].next().transpose()?;
+        Ok(())
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        if !self.started {
+            self.started = true;
+            for i in 0..self.sources.len() {
+                self.advance(i)?;
+            }
+        }
+
+        // 1. Buscamos la llave más chica (más grande si vamos hacia atrás) entre las cabezas
+        //    (la primera fuente que la tenga es la más nueva)
+        let mut winner: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            if let Some((key, _)) = head {
+                match winner {
+                    Some(w) if self.ahead(&self.heads[w].as_ref().unwrap().0, key) => {}
+                    _ => winner = Some(i),
+                }
+            }
+        }
+        let winner = match winner {
+            Some(w) => w,
+            None => return Ok(None),
+        };
+
+        // 2. Las versiones viejas de esa misma llave en otras fuentes se descartan
+        let record = self.heads[winner].take().unwrap();
+        for i in 0..self.heads.len() {
+            if i == winner || matches!(&self.heads[i], Some((k, _)) if *k == record.0) {
+                self.advance(i)?;
+            }
+        }
+        Ok(Some(record))
+    }
+}
+
+impl<I: Iterator<Item = io::Result<Record>>> Iterator for MergeIter<I> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
@@ -0,0 +1,370 @@
+// Los OPERADORES DE MERGE (el comando MERGE)
+// Un contador con INCR tiene que leer antes de escribir: busca la versión viva en la memtable y
+// quizás en varias SSTables. MERGE no lee nada: anota el OPERANDO ("+5", "otro elemento") como
+// una versión más y listo, tan barato como un SET.
+//
+// El trabajo se hace tarde (y solo si alguien lo pide):
+//   - Al LEER se juntan los operandos de la llave, del más nuevo hacia atrás, hasta dar con un valor
+//     o una lápida (la BASE), y el operador los combina: base + op1 + op2 + ... = valor.
+//   - Al COMPACTAR, los operandos que ningún snapshot necesita por separado se resuelven contra su
+//     base si está en la compactación (o si seguro no existe) y quedan como un valor común. Si la
+//     base vive en una tabla más vieja, el operador puede juntar los operandos en uno solo.
+//
+// Qué significa combinar lo decide el operador, que se elige al abrir el motor
+// (EngineOptions::merge_operator). Sin operador, MERGE no se acepta.
+
+use std::io;
+use std::sync::Arc;
+
+use super::entry::Entry;
+
+pub trait MergeOperator: Send + Sync {
+    // El nombre que reporta INFO
+    fn name(&self) -> &'static str;
+
+    // Aplica los operandos (del más viejo al más nuevo) sobre la base (None = la llave no existía).
+    // None = no se pueden combinar (un operando o la base no tienen el formato esperado).
+    fn full_merge(&self, key: &[u8], base: Option<&[u8]>, operands: &[&[u8]]) -> Option<Vec<u8>>;
+
+    // Junta operandos seguidos en uno solo sin conocer la base (lo usa la compactación cuando la
+    // base está en una tabla más vieja). None = no se puede: quedan como estaban.
+    fn partial_merge(&self, _key: &[u8], _operands: &[&[u8]]) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+// Para elegir el operador por nombre (línea de comandos, configuración)
+pub fn merge_operator_from_name(name: &str) -> Option<Arc<dyn MergeOperator>> {
+    match name.to_lowercase().as_str() {
+        "add" | "sum" => Some(Arc::new(AddOperator)),
+        "max" => Some(Arc::new(MaxOperator)),
+        "append" | "list" => Some(Arc::new(AppendOperator)),
+        _ => None,
+    }
+}
+
+// El valor de una pila de operandos. `base` es la versión de abajo de todo (None = no hay) y
+// `since` el momento del operando más viejo: una base que ya había vencido para entonces no
+// cuenta (el primer operando arrancó de cero), y una que todavía no le pasa su vencimiento al
+// resultado, como INCR en Redis.
+pub fn resolve(
+    operator: &dyn MergeOperator,
+    key: &[u8],
+    base: Option<Entry>,
+    operands: &[Vec<u8>],
+    since: u64,
+) -> io::Result<Entry> {
+    let (base, deadline) = match base {
+        Some(Entry::Value(value)) => (Some(value), None),
+        Some(Entry::Expiring(value, deadline)) if deadline > since => (Some(value), Some(deadline)),
+        _ => (None, None),
+    };
+    let operands: Vec<&[u8]> = operands.iter().map(Vec::as_slice).collect();
+    let value = operator
+        .full_merge(key, base.as_deref(), &operands)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "el operador de merge '{}' no puede combinar el valor con sus operandos",
+                    operator.name()
+                ),
+            )
+        })?;
+    Ok(match deadline {
+        Some(deadline) => Entry::Expiring(value, deadline),
+        None => Entry::Value(value),
+    })
+}
+
+fn parse_int(raw: &[u8]) -> Option<i64> {
+    std::str::from_utf8(raw).ok()?.parse().ok()
+}
+
+// ---------------------------------------------------------------------------------
+// ADD: contadores. Base y operandos son enteros en texto ("42", "-3"); sin base arranca de 0.
+// ---------------------------------------------------------------------------------
+pub struct AddOperator;
+
+impl AddOperator {
+    fn sum(start: i64, operands: &[&[u8]]) -> Option<Vec<u8>> {
+        let mut total = start;
+        for operand in operands {
+            total = total.checked_add(parse_int(operand)?)?;
+        }
+        Some(total.to_string().into_bytes())
+    }
+}
+
+impl MergeOperator for AddOperator {
+    fn name(&self) -> &'static str {
+        "add"
+    }
+
+    fn full_merge(&self, _key: &[u8], base: Option<&[u8]>, operands: &[&[u8]]) -> Option<Vec<u8>> {
+        let start = match base {
+            Some(raw) => parse_int(raw)?,
+            None => 0,
+        };
+        AddOperator::sum(start, operands)
+    }
+
+    // La suma es asociativa: +2 +3 = +5
+    fn partial_merge(&self, _key: &[u8], operands: &[&[u8]]) -> Option<Vec<u8>> {
+        AddOperator::sum(0, operands)
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// MAX: el máximo visto (récords, marcas de agua). Enteros en texto, como ADD.
+// ---------------------------------------------------------------------------------
+pub struct MaxOperator;
+
+impl MaxOperator {
+    fn max(start: Option<i64>, operands: &[&[u8]]) -> Option<Vec<u8>> {
+        let mut best = start;
+        for operand in operands {
+            let value = parse_int(operand)?;
+            best = Some(best.map_or(value, |best| best.max(value)));
+        }
+        best.map(|best| best.to_string().into_bytes())
+    }
+}
+
+impl MergeOperator for MaxOperator {
+    fn name(&self) -> &'static str {
+        "max"
+    }
+
+    fn full_merge(&self, _key: &[u8], base: Option<&[u8]>, operands: &[&[u8]]) -> Option<Vec<u8>> {
+        let start = match base {
+            Some(raw) => Some(parse_int(raw)?),
+            None => None,
+        };
+        MaxOperator::max(start, operands)
+    }
+
+    fn partial_merge(&self, _key: &[u8], operands: &[&[u8]]) -> Option<Vec<u8>> {
+        MaxOperator::max(None, operands)
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// APPEND: listas. Cada operando es un elemento que se agrega al final, separado por comas
+// ("a,b" + "c" = "a,b,c"). Cualquier valor sirve de base.
+// ---------------------------------------------------------------------------------
+pub struct AppendOperator;
+
+const LIST_SEPARATOR: &[u8] = b",";
+
+impl MergeOperator for AppendOperator {
+    fn name(&self) -> &'static str {
+        "append"
+    }
+
+    fn full_merge(&self, _key: &[u8], base: Option<&[u8]>, operands: &[&[u8]]) -> Option<Vec<u8>> {
+        let items = base.into_iter().chain(operands.iter().copied());
+        Some(items.collect::<Vec<_>>().join(LIST_SEPARATOR))
+    }
+
+    fn partial_merge(&self, _key: &[u8], operands: &[&[u8]]) -> Option<Vec<u8>> {
+        Some(operands.join(LIST_SEPARATOR))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::super::compaction_filter::PrefixFilter;
+    use super::super::entry::Entry;
+    use super::super::key;
+    use super::super::testing::{self, TempDir};
+    use super::super::{Engine, EngineOptions};
+    use super::AddOperator;
+
+    fn options() -> EngineOptions {
+        let mut options = testing::small_options();
+        options.merge_operator = Some(Arc::new(AddOperator));
+        options
+    }
+
+    // Sin historia (la compactación solo combina lo que nadie necesita por separado)
+    fn open(dir: &TempDir, options: EngineOptions) -> Engine {
+        let mut engine = testing::open(dir, options);
+        testing::without_history(&mut engine);
+        engine
+    }
+
+    // Las versiones guardadas de la llave, de la más nueva a la más vieja
+    fn stored(engine: &Engine, user_key: &[u8]) -> Vec<Entry> {
+        let prefix = key::prefix(user_key);
+        let mut found: Vec<(Vec<u8>, Entry)> = Vec::new();
+        for memtable in std::iter::once(&engine.memtable).chain(&engine.immutable) {
+            found.extend(
+                memtable
+                    .iter()
+                    .filter(|(internal, _)| key::user_part(internal) == prefix.as_slice())
+                    .map(|(internal, entry)| (internal.clone(), entry.clone())),
+            );
+        }
+        for table in engine.version.levels.iter().flatten() {
+            for record in table.iter() {
+                let (internal, entry) = record.unwrap();
+                if key::user_part(&internal) == prefix.as_slice() {
+                    found.push((internal, entry));
+                }
+            }
+        }
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        found.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    fn merge(engine: &mut Engine, key: &[u8], operands: &[&str]) {
+        for operand in operands {
+            engine.merge(key, operand.as_bytes()).unwrap();
+        }
+    }
+
+    // La base y sus operandos entran juntos a la compactación: queda un valor común
+    #[test]
+    fn operands_fold_into_their_base() {
+        let dir = TempDir::new("merge_fold");
+        let mut engine = open(&dir, options());
+        engine.set(b"n", b"10").unwrap();
+        engine.flush().unwrap();
+        merge(&mut engine, b"n", &["1", "2"]);
+        engine.flush().unwrap();
+        testing::compact_all(&mut engine);
+
+        assert_eq!(stored(&engine, b"n"), vec![Entry::Value(b"13".to_vec())]);
+        assert_eq!(engine.get(b"n").unwrap(), Some(b"13".to_vec()));
+        assert_eq!(
+            engine.stats.merge_operands_resolved.load(Ordering::Relaxed),
+            2
+        );
+    }
+
+    // La base vive en una tabla más vieja que no entra a la compactación: los operandos se juntan
+    // en uno solo (partial_merge) y la base queda donde estaba
+    #[test]
+    fn operands_over_a_base_in_an_older_table_are_combined() {
+        let dir = TempDir::new("merge_partial");
+        let mut engine = open(&dir, options());
+        engine.set(b"n", b"10").unwrap();
+        testing::compact_all(&mut engine);
+        let bottom = engine.version.levels.len() - 1;
+        assert_eq!(engine.version.levels[bottom].len(), 1);
+
+        // Dos tablas en L0 para que la compactación las reescriba (una sola solo bajaría de nivel)
+        merge(&mut engine, b"n", &["1"]);
+        engine.flush().unwrap();
+        merge(&mut engine, b"n", &["2", "3"]);
+        engine.flush().unwrap();
+        assert!(engine.compact_step(true).unwrap());
+        assert!(engine.version.levels[0].is_empty());
+        assert_eq!(engine.version.levels[bottom].len(), 1);
+
+        assert_eq!(
+            stored(&engine, b"n"),
+            vec![Entry::Merge(b"6".to_vec()), Entry::Value(b"10".to_vec())]
+        );
+        assert_eq!(engine.get(b"n").unwrap(), Some(b"16".to_vec()));
+    }
+
+    // Una lápida entre operandos corta la pila: lo de abajo no cuenta
+    #[test]
+    fn a_tombstone_between_operands_is_the_base() {
+        let dir = TempDir::new("merge_tombstone");
+        let mut engine = open(&dir, options());
+        engine.set(b"n", b"10").unwrap();
+        merge(&mut engine, b"n", &["1"]);
+        engine.flush().unwrap();
+        engine.delete(b"n").unwrap();
+        merge(&mut engine, b"n", &["5"]);
+        engine.flush().unwrap();
+        merge(&mut engine, b"n", &["2"]);
+        assert_eq!(engine.get(b"n").unwrap(), Some(b"7".to_vec()));
+        testing::compact_all(&mut engine);
+
+        assert_eq!(stored(&engine, b"n"), vec![Entry::Value(b"7".to_vec())]);
+        assert_eq!(engine.get(b"n").unwrap(), Some(b"7".to_vec()));
+    }
+
+    // Un snapshot que ve un operando del medio: lo de abajo se combina hasta ahí, lo de arriba
+    // queda suelto hasta que se suelte el snapshot
+    #[test]
+    fn a_snapshot_pins_an_intermediate_operand() {
+        let dir = TempDir::new("merge_snapshot");
+        let mut engine = open(&dir, options());
+        engine.set(b"n", b"10").unwrap();
+        merge(&mut engine, b"n", &["1"]);
+        engine.flush().unwrap();
+        let snapshot = engine.snapshot();
+        merge(&mut engine, b"n", &["2", "3"]);
+        engine.flush().unwrap();
+        testing::compact_all(&mut engine);
+
+        assert_eq!(
+            stored(&engine, b"n"),
+            vec![
+                Entry::Merge(b"3".to_vec()),
+                Entry::Merge(b"2".to_vec()),
+                Entry::Value(b"11".to_vec()),
+            ]
+        );
+        assert_eq!(
+            engine.get_snapshot(b"n", &snapshot).unwrap(),
+            Some(b"11".to_vec())
+        );
+        assert_eq!(engine.get(b"n").unwrap(), Some(b"16".to_vec()));
+
+        // Sin el snapshot, una tabla que pisa a la llave obliga a reescribirla
+        drop(snapshot);
+        engine.set(b"a", b"x").unwrap();
+        engine.set(b"z", b"x").unwrap();
+        testing::compact_all(&mut engine);
+        assert_eq!(stored(&engine, b"n"), vec![Entry::Value(b"16".to_vec())]);
+    }
+
+    // El filtro no ve operandos: el valor que sale de combinarlos se filtra en la compactación
+    // siguiente, cuando ya es un valor común
+    #[test]
+    fn a_resolved_merge_is_filtered_by_the_next_compaction() {
+        let dir = TempDir::new("merge_filter");
+        let mut options = options();
+        options.compaction_filter = PrefixFilter {
+            purge: vec![b"tenant:42:".to_vec()],
+            redact: vec![b"pii:".to_vec()],
+        }
+        .into_filter();
+        let mut engine = open(&dir, options);
+        engine.set(b"tenant:42:n", b"10").unwrap();
+        engine.set(b"pii:n", b"10").unwrap();
+        engine.flush().unwrap();
+        merge(&mut engine, b"tenant:42:n", &["1"]);
+        merge(&mut engine, b"pii:n", &["1"]);
+        engine.flush().unwrap();
+        testing::compact_all(&mut engine);
+
+        assert_eq!(
+            stored(&engine, b"tenant:42:n"),
+            vec![Entry::Value(b"11".to_vec())]
+        );
+        assert_eq!(
+            stored(&engine, b"pii:n"),
+            vec![Entry::Value(b"11".to_vec())]
+        );
+        assert_eq!(engine.stats.filter_removed.load(Ordering::Relaxed), 0);
+
+        // Algo que pise la tabla para que se reescriba (si no, solo bajaría de nivel)
+        engine.set(b"a", b"x").unwrap();
+        engine.set(b"z", b"x").unwrap();
+        testing::compact_all(&mut engine);
+        assert_eq!(engine.get(b"tenant:42:n").unwrap(), None);
+        assert_eq!(engine.get(b"pii:n").unwrap(), Some(b"<redacted>".to_vec()));
+        assert_eq!(engine.stats.filter_removed.load(Ordering::Relaxed), 1);
+        assert_eq!(engine.stats.filter_replaced.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,145 @@
+// Los MODIFICADORES atómicos (INCR, INCRBY, INCRBYFLOAT, DECR, APPEND, GETSET)
+// Antes un contador era GET + SET desde el cliente: dos conexiones que incrementan a la vez
+// leen el mismo valor y una de las dos sumas se pierde. Ahora la lectura, la cuenta y la
+// escritura pasan en el motor con el candado de escritura tomado de punta a punta.
+//
+// En el WAL queda el RESULTADO (un SET común con el valor nuevo), no la operación: la
+// recuperación y las réplicas de la historia (GETAT, HISTORY) no necesitan saber sumar.
+// Como en Redis, los valores numéricos son texto ("42", "3.5") y una llave que no existe vale 0.
+
+use std::io;
+
+fn type_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub fn parse_int(current: Option<&[u8]>) -> io::Result<i64> {
+    match current {
+        None => Ok(0),
+        Some(raw) => std::str::from_utf8(raw)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| type_error("el valor no es un entero o está fuera de rango")),
+    }
+}
+
+pub fn add_int(current: i64, delta: i64) -> io::Result<i64> {
+    current
+        .checked_add(delta)
+        .ok_or_else(|| type_error("el incremento o decremento desborda"))
+}
+
+pub fn parse_float(current: Option<&[u8]>) -> io::Result<f64> {
+    match current {
+        None => Ok(0.0),
+        Some(raw) => std::str::from_utf8(raw)
+            .ok()
+            .and_then(|text| text.parse::<f64>().ok())
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| type_error("el valor no es un float válido")),
+    }
+}
+
+pub fn add_float(current: f64, delta: f64) -> io::Result<f64> {
+    Some(current + delta)
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| type_error("el incremento daría NaN o infinito"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::super::testing::{self, TempDir};
+    use super::super::{now_millis, Ttl};
+
+    fn is_type_error<T: std::fmt::Debug>(result: io::Result<T>) -> bool {
+        matches!(result, Err(e) if e.kind() == io::ErrorKind::InvalidInput)
+    }
+
+    // Lo que no es un entero (o no entra en 64 bits) es un error y no escribe nada
+    #[test]
+    fn incr_on_a_value_that_is_not_an_integer() {
+        let dir = TempDir::new("mutate_incr_type");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let bad: [&[u8]; 7] = [
+            b"abc",
+            b"1.5",
+            b" 1",
+            b"1 ",
+            b"",
+            b"99999999999999999999",
+            b"\xff\xfe",
+        ];
+        for value in bad {
+            engine.set(b"k", value).unwrap();
+            let last = engine.last_sequence;
+            assert!(is_type_error(engine.incr_by(b"k", 1)), "{:?}", value);
+            assert_eq!(engine.last_sequence, last);
+            assert_eq!(engine.get(b"k").unwrap(), Some(value.to_vec()));
+        }
+
+        engine.set(b"k", b"-7").unwrap();
+        assert_eq!(engine.incr_by(b"k", 10).unwrap().1, 3);
+        assert_eq!(engine.incr_by(b"missing", -2).unwrap().1, -2);
+    }
+
+    #[test]
+    fn incr_overflow_is_an_error() {
+        let dir = TempDir::new("mutate_incr_overflow");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine.set(b"max", i64::MAX.to_string().as_bytes()).unwrap();
+        engine.set(b"min", i64::MIN.to_string().as_bytes()).unwrap();
+        assert!(is_type_error(engine.incr_by(b"max", 1)));
+        assert!(is_type_error(engine.incr_by(b"min", -1)));
+        assert_eq!(engine.incr_by(b"new", i64::MIN).unwrap().1, i64::MIN);
+        assert!(is_type_error(engine.incr_by(b"new", -1)));
+        assert_eq!(
+            engine.get(b"max").unwrap(),
+            Some(i64::MAX.to_string().into_bytes())
+        );
+        assert_eq!(engine.incr_by(b"max", -1).unwrap().1, i64::MAX - 1);
+    }
+
+    #[test]
+    fn incr_by_float_type_errors() {
+        let dir = TempDir::new("mutate_incr_float");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let bad: [&[u8]; 4] = [b"abc", b"inf", b"NaN", b""];
+        for value in bad {
+            engine.set(b"f", value).unwrap();
+            assert!(
+                is_type_error(engine.incr_by_float(b"f", 1.0)),
+                "{:?}",
+                value
+            );
+            assert_eq!(engine.get(b"f").unwrap(), Some(value.to_vec()));
+        }
+        // Llegar a infinito tampoco
+        engine.set(b"f", f64::MAX.to_string().as_bytes()).unwrap();
+        assert!(is_type_error(engine.incr_by_float(b"f", f64::MAX)));
+
+        engine.set(b"f", b"10").unwrap();
+        assert_eq!(engine.incr_by_float(b"f", 0.5).unwrap().1, b"10.5".to_vec());
+        // Un entero sirve como float, pero un float no sirve como entero
+        assert!(is_type_error(engine.incr_by(b"f", 1)));
+    }
+
+    // El vencimiento de la llave sobrevive al INCR; una llave vencida cuenta como que no existe
+    #[test]
+    fn incr_keeps_the_deadline() {
+        let dir = TempDir::new("mutate_incr_ttl");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine
+            .set_with_deadline(b"n", b"5", now_millis() + 3_600_000)
+            .unwrap();
+        assert_eq!(engine.incr_by(b"n", 1).unwrap().1, 6);
+        assert!(matches!(engine.ttl(b"n").unwrap(), Ttl::ExpiresIn(_)));
+
+        engine
+            .set_with_deadline(b"old", b"abc", now_millis() - 1)
+            .unwrap();
+        assert_eq!(engine.incr_by(b"old", 1).unwrap().1, 1);
+        assert_eq!(engine.ttl(b"old").unwrap(), Ttl::Persistent);
+    }
+}
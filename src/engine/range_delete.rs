@@ -0,0 +1,183 @@
+// Los BORRADOS POR RANGO (los comandos DELRANGE y DELPREFIX)
+// Borrar "todo lo de session:" con DEL es una lápida por llave (y hay que saber cuáles son). Una
+// LÁPIDA DE RANGO es UN solo registro: "las llaves de [start, end) escritas antes de la secuencia S
+// ya no existen". Se escribe como una versión más de la llave `start` (en el WAL, la memtable y las
+// SSTables) y, como hay pocas, el motor además las tiene todas en memoria (se rearma al abrir, igual
+// que la agenda de vencimientos): cualquier lectura que encuentra una versión de una llave se fija
+// si alguna lápida de rango más nueva la tapa.
+//
+// Nunca se mira la lápida de rango contra versiones escritas DESPUÉS de ella: un SET posterior a
+// DELPREFIX vuelve a existir normalmente. Y como cada una lleva su secuencia y su momento, los
+// snapshots y los viajes en el tiempo de antes del borrado siguen viendo lo que había.
+//
+// La compactación tira las versiones tapadas (si ningún snapshot ni la retención las necesitan)
+// y la lápida misma cuando ya no puede quedar nada tapado en ninguna otra tabla.
+
+use super::key;
+
+#[derive(Clone, Debug)]
+pub struct RangeTombstone {
+    pub start: Vec<u8>,
+    // Sin incluir (None = hasta el final del espacio de llaves)
+    pub end: Option<Vec<u8>>,
+    pub seq: u64,
+    pub time: u64,
+}
+
+impl RangeTombstone {
+    pub fn covers(&self, user_key: &[u8]) -> bool {
+        user_key >= self.start.as_slice() && self.end.as_deref().is_none_or(|end| user_key < end)
+    }
+
+    // ¿Alguna llave del rango puede estar en una tabla con llaves internas de [smallest, largest]?
+    pub fn overlaps(&self, smallest: &[u8], largest: &[u8]) -> bool {
+        key::user_part(largest) >= key::prefix(&self.start).as_slice()
+            && self
+                .end
+                .as_ref()
+                .is_none_or(|end| key::user_part(smallest) < key::prefix(end).as_slice())
+    }
+}
+
+// La primera llave que ya NO empieza con `prefix` ("user:" -> "user;"). None = no hay (vacío o todo 0xFF)
+pub fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while end.last() == Some(&0xFF) {
+        end.pop();
+    }
+    let last = end.last_mut()?;
+    *last += 1;
+    Some(end)
+}
+
+// Todas las lápidas de rango vivas, ordenadas por llave de inicio
+#[derive(Clone, Default)]
+pub struct RangeTombstones {
+    list: Vec<RangeTombstone>,
+}
+
+impl RangeTombstones {
+    pub fn insert(&mut self, tombstone: RangeTombstone) {
+        let pos = self
+            .list
+            .partition_point(|t| (&t.start, t.seq) < (&tombstone.start, tombstone.seq));
+        self.list.insert(pos, tombstone);
+    }
+
+    pub fn remove(&mut self, start: &[u8], seq: u64) {
+        self.list.retain(|t| !(t.start == start && t.seq == seq));
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    // Las que tapan a `user_key` (en cualquier secuencia)
+    pub fn covering<'a>(&'a self, user_key: &'a [u8]) -> impl Iterator<Item = &'a RangeTombstone> {
+        self.list
+            .iter()
+            .take_while(move |t| t.start.as_slice() <= user_key)
+            .filter(move |t| t.covers(user_key))
+    }
+
+    // La más nueva que borra la versión `seq` de la llave para quien lee hasta la secuencia `read_seq`
+    pub fn newest_covering<'a>(
+        &'a self,
+        user_key: &'a [u8],
+        seq: u64,
+        read_seq: u64,
+    ) -> Option<&'a RangeTombstone> {
+        self.covering(user_key)
+            .filter(|t| seq < t.seq && t.seq <= read_seq)
+            .max_by_key(|t| t.seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, TempDir};
+    use super::super::Engine;
+
+    fn fill(engine: &mut Engine, prefix: &str, count: usize, value: &str) {
+        for i in 0..count {
+            let key = format!("{}{:03}", prefix, i);
+            engine.set(key.as_bytes(), value.as_bytes()).unwrap();
+        }
+    }
+
+    fn get(engine: &Engine, key: &str) -> Option<Vec<u8>> {
+        engine.get(key.as_bytes()).unwrap()
+    }
+
+    // La lápida tapa lo escrito ANTES; el SET posterior a DELPREFIX vuelve a existir, esté donde
+    // esté cada cosa (memtable, SSTables, después de compactar o de reabrir)
+    fn check(engine: &Engine) {
+        assert_eq!(get(engine, "session:000"), None);
+        assert_eq!(get(engine, "session:099"), None);
+        assert_eq!(get(engine, "session:042"), Some(b"nuevo".to_vec()));
+        assert_eq!(get(engine, "user:007"), Some(b"viejo".to_vec()));
+    }
+
+    #[test]
+    fn a_newer_write_survives_the_range_tombstone() {
+        let dir = TempDir::new("range_newer");
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        fill(
+            &mut engine,
+            "session:",
+            100,
+            "viejo viejo viejo viejo viejo viejo",
+        );
+        fill(&mut engine, "user:", 20, "viejo");
+        engine.delete_prefix(b"session:").unwrap();
+        engine.set(b"session:042", b"nuevo").unwrap();
+        check(&engine);
+
+        // Sin volcar: todo vuelve desde el WAL
+        drop(engine);
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        check(&engine);
+
+        testing::compact_all(&mut engine);
+        check(&engine);
+        // Las versiones tapadas ya no se guardan (no hay snapshots)
+        assert_eq!(testing::stored_versions(&engine, b"session:000"), 0);
+        assert_eq!(testing::stored_versions(&engine, b"session:042"), 1);
+
+        drop(engine);
+        let engine = testing::open(&dir, testing::small_options());
+        check(&engine);
+    }
+
+    #[test]
+    fn a_snapshot_from_before_the_delete_still_sees_the_keys() {
+        let dir = TempDir::new("range_snapshot");
+        let mut engine = testing::open(&dir, testing::small_options());
+        testing::without_history(&mut engine);
+        fill(
+            &mut engine,
+            "session:",
+            100,
+            "viejo viejo viejo viejo viejo viejo",
+        );
+        let before = engine.snapshot();
+        engine
+            .delete_range(b"session:010", Some(b"session:020"))
+            .unwrap();
+        engine.set(b"session:015", b"nuevo").unwrap();
+        testing::compact_all(&mut engine);
+
+        let old = b"viejo viejo viejo viejo viejo viejo".to_vec();
+        for key in ["session:010", "session:015", "session:019"] {
+            assert_eq!(
+                engine.get_snapshot(key.as_bytes(), &before).unwrap(),
+                Some(old.clone())
+            );
+        }
+        assert_eq!(get(&engine, "session:010"), None);
+        assert_eq!(get(&engine, "session:015"), Some(b"nuevo".to_vec()));
+        assert_eq!(get(&engine, "session:020"), Some(old));
+    }
+}
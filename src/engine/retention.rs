@@ -0,0 +1,236 @@
+// La RETENCIÓN (cuánta historia guardamos de cada llave)
+// Guardar todas las versiones para siempre hace que el disco solo crezca. Una política de
+// retención dice qué versiones VIEJAS (pisadas por otra más nueva) se pueden tirar:
+//
+//   - max_age:      la historia más nueva que esto sigue consultable con GETAT/HISTORY
+//                   (una versión se tira cuando la que la pisó es más vieja que max_age)
+//   - max_versions: nos quedamos con las N versiones más nuevas de cada llave
+//   - las dos:      "como mucho N versiones, y ninguna más vieja que max_age"
+//
+// La versión más nueva de una llave NUNCA se tira por retención (para que una llave desaparezca
+// está DEL; la retención solo recorta historia). Sin límites ("FOREVER") se guarda todo: es lo
+// que usan las llaves sin política cuando no hay --history-retention, así GETAT y HISTORY ven
+// toda la historia también después de compactar. Las políticas se asignan por PREFIJO de llave
+// (gana el prefijo más largo), quedan anotadas en el MANIFEST y las aplica la compactación:
+// así el espacio se libera de verdad y sobrevive a los reinicios.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::key;
+use super::range_delete::prefix_end;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_versions: Option<u64>,
+}
+
+impl RetentionPolicy {
+    // La de las llaves sin política propia: la ventana de --history-retention, o toda la historia
+    pub fn history(window: Option<Duration>) -> Self {
+        RetentionPolicy {
+            max_age: window,
+            max_versions: None,
+        }
+    }
+
+    // FOREVER: ni edad ni cantidad, no se tira nada
+    pub fn keep_all(&self) -> bool {
+        self.max_age.is_none() && self.max_versions.is_none()
+    }
+
+    // ¿Se puede tirar la versión número `rank` (0 = la más nueva) pisada en el instante `superseded_at`?
+    pub fn expired(&self, rank: u64, superseded_at: u64, now: u64) -> bool {
+        if rank == 0 || self.keep_all() {
+            return false;
+        }
+        let too_old = self
+            .max_age
+            .is_some_and(|age| superseded_at < now.saturating_sub(age.as_millis() as u64));
+        let too_many = self.max_versions.is_some_and(|n| rank >= n);
+        too_old || too_many
+    }
+
+    // El instante más viejo que GETAT/HISTORY pueden contestar seguro: antes de él, una versión
+    // pisada pudo haberse tirado por max_age (0 = sin límite de edad)
+    pub fn horizon(&self, now: u64) -> u64 {
+        self.max_age
+            .map_or(0, |age| now.saturating_sub(age.as_millis() as u64))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(age) = self.max_age {
+            parts.push(format!("max_age={}ms", age.as_millis()));
+        }
+        if let Some(n) = self.max_versions {
+            parts.push(format!("max_versions={}", n));
+        }
+        if parts.is_empty() {
+            "forever".to_string()
+        } else {
+            parts.join(",")
+        }
+    }
+}
+
+// Todas las reglas vigentes (una copia viaja con cada trabajo de compactación)
+#[derive(Clone)]
+pub struct Retention {
+    // (prefijo, prefijo escapado como en las llaves internas, política)
+    rules: Vec<(Vec<u8>, Vec<u8>, RetentionPolicy)>,
+    // La que vale para las llaves que no matchean ningún prefijo
+    default: RetentionPolicy,
+}
+
+impl Retention {
+    pub fn new(rules: &[(Vec<u8>, RetentionPolicy)], default: RetentionPolicy) -> Self {
+        let mut retention = Retention {
+            rules: Vec::new(),
+            default,
+        };
+        for (prefix, policy) in rules {
+            retention.set(prefix, *policy);
+        }
+        retention
+    }
+
+    pub fn set(&mut self, prefix: &[u8], policy: RetentionPolicy) {
+        self.remove(prefix);
+        self.rules
+            .push((prefix.to_vec(), key::escape(prefix), policy));
+        self.rules.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    pub fn remove(&mut self, prefix: &[u8]) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|(p, _, _)| p != prefix);
+        self.rules.len() != before
+    }
+
+    // Lo que se anota en el MANIFEST
+    pub fn rules(&self) -> Vec<(Vec<u8>, RetentionPolicy)> {
+        self.rules
+            .iter()
+            .map(|(prefix, _, policy)| (prefix.clone(), *policy))
+            .collect()
+    }
+
+    pub fn default_policy(&self) -> RetentionPolicy {
+        self.default
+    }
+
+    pub fn policy_for(&self, user_key: &[u8]) -> RetentionPolicy {
+        self.policy_for_user_part(&key::prefix(user_key))
+    }
+
+    // Las políticas que le pueden tocar a alguna llave de [start, end) (la de por defecto siempre)
+    pub fn policies_in_range(&self, start: &[u8], end: Option<&[u8]>) -> Vec<RetentionPolicy> {
+        let mut policies = vec![self.default];
+        for (prefix, _, policy) in &self.rules {
+            let begins_before_end = end.is_none_or(|end| prefix.as_slice() < end);
+            let ends_after_start = prefix_end(prefix).is_none_or(|after| after.as_slice() > start);
+            if begins_before_end && ends_after_start {
+                policies.push(*policy);
+            }
+        }
+        policies
+    }
+
+    // Igual, pero con la llave ya escapada (lo que tiene la compactación a mano)
+    pub fn policy_for_user_part(&self, user_part: &[u8]) -> RetentionPolicy {
+        self.rules
+            .iter()
+            .filter(|(_, escaped, _)| user_part.starts_with(escaped))
+            .max_by_key(|(_, escaped, _)| escaped.len())
+            .map_or(self.default, |(_, _, policy)| *policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::super::history::HistoryQuery;
+    use super::super::now_millis;
+    use super::super::testing::{self, TempDir};
+
+    fn all_history(start: u64) -> HistoryQuery {
+        HistoryQuery {
+            start,
+            end: u64::MAX,
+            limit: 100,
+            reverse: false,
+            cursor: None,
+        }
+    }
+
+    // Sin --history-retention se guarda toda la historia: GETAT ve las versiones pisadas en la
+    // memtable, en las SSTables, después de compactar y después de reabrir
+    #[test]
+    fn getat_sees_old_versions_after_flush_compaction_and_reopen() {
+        let dir = TempDir::new("getat_default");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let before = now_millis().saturating_sub(1);
+        engine.set(b"k", b"v1").unwrap();
+        let t1 = testing::instant_between();
+        engine.set(b"k", b"v2").unwrap();
+        let t2 = testing::instant_between();
+        engine.delete(b"k").unwrap();
+        let t3 = testing::instant_between();
+        engine.set(b"k", b"v3").unwrap();
+
+        let check = |engine: &super::super::Engine| {
+            assert_eq!(engine.get_at_time(b"k", before).unwrap(), None);
+            assert_eq!(engine.get_at_time(b"k", t1).unwrap(), Some(b"v1".to_vec()));
+            assert_eq!(engine.get_at_time(b"k", t2).unwrap(), Some(b"v2".to_vec()));
+            assert_eq!(engine.get_at_time(b"k", t3).unwrap(), None);
+            assert_eq!(engine.get(b"k").unwrap(), Some(b"v3".to_vec()));
+            assert_eq!(
+                engine.history(b"k", &all_history(0)).unwrap().entries.len(),
+                4
+            );
+        };
+        check(&engine);
+        engine.flush().unwrap();
+        check(&engine);
+        engine.set(b"other", b"x").unwrap();
+        testing::compact_all(&mut engine);
+        check(&engine);
+        assert_eq!(testing::stored_versions(&engine, b"k"), 4);
+
+        drop(engine);
+        let engine = testing::open(&dir, testing::small_options());
+        check(&engine);
+    }
+
+    // Con ventana, lo de antes del horizonte puede haberse tirado: error en vez de NULL
+    #[test]
+    fn reads_before_the_history_window_are_an_error() {
+        let dir = TempDir::new("getat_window");
+        let mut options = testing::small_options();
+        options.history_retention = Some(Duration::from_secs(3600));
+        let mut engine = testing::open(&dir, options);
+        engine.set(b"k", b"v1").unwrap();
+        let now = now_millis();
+
+        assert!(engine.get_at_time(b"k", now - 7_200_000).is_err());
+        assert!(engine.history(b"k", &all_history(now - 7_200_000)).is_err());
+        assert_eq!(engine.get_at_time(b"k", now - 1_800_000).unwrap(), None);
+        assert_eq!(engine.get_at_time(b"k", now).unwrap(), Some(b"v1".to_vec()));
+        // "-" (desde el principio) es "todo lo que se guarda", no un instante pedido
+        assert_eq!(
+            engine.history(b"k", &all_history(0)).unwrap().entries.len(),
+            1
+        );
+
+        // Un prefijo con su propia política usa su propio horizonte
+        engine
+            .set_retention(b"audit:", super::RetentionPolicy::default())
+            .unwrap();
+        engine.set(b"audit:1", b"x").unwrap();
+        assert_eq!(engine.get_at_time(b"audit:1", 1).unwrap(), None);
+    }
+}
@@ -0,0 +1,643 @@
+// El RECORRIDO ORDENADO (los comandos SCAN y REVSCAN)
+// Las llaves viven desparramadas: la memtable, la congelada y cada SSTable tienen su pedazo,
+// cada uno ordenado por llave interna. Para listarlas en orden las mezclamos (merge.rs) y, como
+// una misma llave trae todas sus versiones juntas, de cada grupo nos quedamos con la más nueva
+// que ve nuestra secuencia: si es una lápida o ya venció, la llave no existe y se saltea.
+//
+// Hacia atrás es lo mismo pero cada fuente (y el mezclador) va de mayor a menor. Ojo: ahí las
+// versiones de una llave llegan de la más VIEJA a la más nueva, así que hay que ver el grupo
+// entero antes de decidir cuál vale.
+//
+// Si la versión que vale es un operando de MERGE, se resuelve con el motor (que busca la base
+// debajo) antes de entregarla. Y si un borrado de rango más nuevo la tapa, la llave no existe.
+//
+// Se devuelve de a PÁGINAS. El cursor es "desde qué llave seguir" (la siguiente a la última
+// entregada, o la última misma si vamos hacia atrás), no una posición: aunque entren o se borren
+// llaves entre página y página, seguir desde ahí nunca repite ni se saltea las que siguen estando.
+
+use std::io;
+
+use super::entry::Entry;
+use super::key;
+use super::merge::MergeIter;
+use super::range_delete::prefix_end;
+use super::Engine;
+
+// Cualquier fuente ordenada por llave interna (memtable, SSTable, nivel entero)
+pub type Source<'a> = Box<dyn Iterator<Item = io::Result<Record>> + 'a>;
+
+type Record = (Vec<u8>, Entry);
+
+#[derive(Clone, Default)]
+pub struct ScanRange {
+    // Primera llave (incluida; vacía = desde el principio)
+    pub start: Vec<u8>,
+    // Hasta acá (sin incluir; None = hasta el final)
+    pub end: Option<Vec<u8>>,
+    // Solo las llaves que empiezan así (vacío = todas)
+    pub prefix: Vec<u8>,
+}
+
+impl ScanRange {
+    // Desde dónde hay que buscar: lo más adelante entre el inicio y el prefijo
+    pub fn first_key(&self) -> &[u8] {
+        self.start.as_slice().max(self.prefix.as_slice())
+    }
+
+    // El tope (sin incluir) para recorrer hacia atrás: lo más atrás entre el final y el prefijo
+    fn upper_bound(&self) -> Option<Vec<u8>> {
+        // Todas las llaves con prefijo "user:" son menores que "user;" (último byte + 1)
+        match (prefix_end(&self.prefix), self.end.clone()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Lo que falta después de una página: el cursor achica el rango por el lado ya recorrido
+    pub fn resume(self, cursor: Vec<u8>, reverse: bool) -> ScanRange {
+        if reverse {
+            ScanRange {
+                end: Some(self.end.map_or(cursor.clone(), |end| end.min(cursor))),
+                ..self
+            }
+        } else {
+            ScanRange {
+                start: cursor.max(self.start),
+                ..self
+            }
+        }
+    }
+
+    fn contains(&self, user_key: &[u8]) -> bool {
+        user_key >= self.start.as_slice()
+            && user_key.starts_with(&self.prefix)
+            && self.end.as_deref().is_none_or(|end| user_key < end)
+    }
+}
+
+pub struct ScanPage {
+    // (llave, valor) en el orden del recorrido
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+    // Desde dónde pedir la página siguiente (None = no hay más)
+    pub cursor: Option<Vec<u8>>,
+}
+
+// Las llaves vivas vistas desde la secuencia `seq` en el instante `now`, en una dirección
+struct ScanIter<'a> {
+    engine: &'a Engine,
+    merged: MergeIter<Source<'a>>,
+    seq: u64,
+    now: u64,
+    reverse: bool,
+    // Hacia adelante: la llave (escapada) que ya resolvimos, sus versiones más viejas se ignoran
+    current: Option<Vec<u8>>,
+    // Hacia atrás: la primera versión de la llave siguiente (la leímos para saber que el grupo terminó)
+    pending: Option<Record>,
+}
+
+impl<'a> ScanIter<'a> {
+    fn forward(engine: &'a Engine, sources: Vec<Source<'a>>, seq: u64, now: u64) -> Self {
+        ScanIter {
+            engine,
+            merged: MergeIter::new(sources),
+            seq,
+            now,
+            reverse: false,
+            current: None,
+            pending: None,
+        }
+    }
+
+    fn backward(engine: &'a Engine, sources: Vec<Source<'a>>, seq: u64, now: u64) -> Self {
+        ScanIter {
+            merged: MergeIter::reversed(sources),
+            reverse: true,
+            ..ScanIter::forward(engine, Vec::new(), seq, now)
+        }
+    }
+
+    fn next_live(&mut self) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+        loop {
+            let visible = if self.reverse {
+                self.next_group()?
+            } else {
+                self.next_newest()?
+            };
+            let (internal, entry) = match visible {
+                Some(Some(version)) => version,
+                // Ninguna versión de esa llave existe para nuestra foto
+                Some(None) => continue,
+                None => return Ok(None),
+            };
+            let user_key = key::user_key(&internal)?;
+            let deleted =
+                self.engine
+                    .ranges
+                    .newest_covering(&user_key, key::seq(&internal), self.seq);
+            if deleted.is_some() {
+                continue;
+            }
+            let (_, entry) = self.engine.resolve(&user_key, (internal, entry))?;
+            if let Some(value) = entry.value_at(self.now) {
+                return Ok(Some((user_key, value)));
+            }
+        }
+    }
+
+    // Hacia adelante la primera versión visible de cada llave es la que vale
+    fn next_newest(&mut self) -> io::Result<Option<Option<Record>>> {
+        for record in self.merged.by_ref() {
+            let (internal, entry) = record?;
+            // Escrita después de nuestra foto: todavía no existe para nosotros
+            if key::seq(&internal) > self.seq {
+                continue;
+            }
+            let user_part = key::user_part(&internal);
+            if self.current.as_deref() == Some(user_part) {
+                continue;
+            }
+            self.current = Some(user_part.to_vec());
+            return Ok(Some(Some((internal, entry))));
+        }
+        Ok(None)
+    }
+
+    // Hacia atrás recorremos el grupo entero: la última versión visible es la más nueva
+    fn next_group(&mut self) -> io::Result<Option<Option<Record>>> {
+        let first = match self.pending.take() {
+            Some(record) => record,
+            None => match self.merged.next().transpose()? {
+                Some(record) => record,
+                None => return Ok(None),
+            },
+        };
+        let user_part = key::user_part(&first.0).to_vec();
+        let mut newest = (key::seq(&first.0) <= self.seq).then_some(first);
+        while let Some((internal, entry)) = self.merged.next().transpose()? {
+            if key::user_part(&internal) != user_part.as_slice() {
+                self.pending = Some((internal, entry));
+                break;
+            }
+            if key::seq(&internal) <= self.seq {
+                newest = Some((internal, entry));
+            }
+        }
+        Ok(Some(newest))
+    }
+}
+
+impl Iterator for ScanIter<'_> {
+    type Item = io::Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_live().transpose()
+    }
+}
+
+// Iterador en las dos direcciones sobre las llaves vivas de una foto de la base.
+// Se para en una llave (`current`) y se mueve con next/prev; cambiar de dirección arma el
+// recorrido de nuevo desde la llave actual (las fuentes solo saben ir para un lado).
+pub struct DbIter<'a> {
+    engine: &'a Engine,
+    seq: u64,
+    now: u64,
+    walk: Option<ScanIter<'a>>,
+    current: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<'a> DbIter<'a> {
+    pub fn new(engine: &'a Engine, seq: u64, now: u64) -> Self {
+        DbIter {
+            engine,
+            seq,
+            now,
+            walk: None,
+            current: None,
+        }
+    }
+
+    // (llave, valor) donde está parado (None = se salió por alguna de las puntas)
+    pub fn current(&self) -> Option<(&[u8], &[u8])> {
+        self.current
+            .as_ref()
+            .map(|(k, v)| (k.as_slice(), v.as_slice()))
+    }
+
+    // A la primera llave >= user_key
+    pub fn seek(&mut self, user_key: &[u8]) -> io::Result<()> {
+        let sources = self.engine.sources_from(&key::lookup(user_key, u64::MAX));
+        self.walk = Some(ScanIter::forward(self.engine, sources, self.seq, self.now));
+        self.step()
+    }
+
+    // A la última llave <= user_key
+    pub fn seek_for_prev(&mut self, user_key: &[u8]) -> io::Result<()> {
+        // Las versiones de user_key terminan justo donde empieza la llave user_key + 0x00
+        let mut after = user_key.to_vec();
+        after.push(0);
+        self.walk_back_from(Some(&key::lookup(&after, u64::MAX)))
+    }
+
+    pub fn seek_to_last(&mut self) -> io::Result<()> {
+        self.walk_back_from(None)
+    }
+
+    pub fn next(&mut self) -> io::Result<()> {
+        let (user_key, _) = match &self.current {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        if self.walk.as_ref().is_some_and(|walk| !walk.reverse) {
+            return self.step();
+        }
+        let mut after = user_key.clone();
+        after.push(0);
+        self.seek(&after)
+    }
+
+    pub fn prev(&mut self) -> io::Result<()> {
+        let (user_key, _) = match &self.current {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        if self.walk.as_ref().is_some_and(|walk| walk.reverse) {
+            return self.step();
+        }
+        let before = key::lookup(user_key, u64::MAX);
+        self.walk_back_from(Some(&before))
+    }
+
+    fn walk_back_from(&mut self, target: Option<&[u8]>) -> io::Result<()> {
+        let sources = self.engine.sources_before(target);
+        self.walk = Some(ScanIter::backward(self.engine, sources, self.seq, self.now));
+        self.step()
+    }
+
+    fn step(&mut self) -> io::Result<()> {
+        self.current = match self.walk.as_mut().and_then(|walk| walk.next()) {
+            Some(record) => Some(record?),
+            None => None,
+        };
+        Ok(())
+    }
+}
+
+// Junta hasta `limit` llaves del rango, de la primera en adelante
+pub fn page(mut iter: DbIter, range: &ScanRange, limit: usize) -> io::Result<ScanPage> {
+    iter.seek(range.first_key())?;
+    collect(iter, range, limit, false)
+}
+
+// Junta hasta `limit` llaves del rango, de la última para atrás
+pub fn rev_page(mut iter: DbIter, range: &ScanRange, limit: usize) -> io::Result<ScanPage> {
+    match range.upper_bound() {
+        Some(upper) => {
+            iter.seek_for_prev(&upper)?;
+            // El tope no se incluye
+            if iter.current().is_some_and(|(k, _)| k == upper.as_slice()) {
+                iter.prev()?;
+            }
+        }
+        None => iter.seek_to_last()?,
+    }
+    collect(iter, range, limit, true)
+}
+
+fn collect(
+    mut iter: DbIter,
+    range: &ScanRange,
+    limit: usize,
+    reverse: bool,
+) -> io::Result<ScanPage> {
+    let limit = limit.max(1);
+    let mut entries: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    // Arrancamos adentro del rango: la primera llave de afuera es el final del recorrido
+    while let Some((user_key, value)) = iter.current() {
+        if !range.contains(user_key) {
+            break;
+        }
+        // Hay al menos una más: la página siguiente arranca justo después de la última
+        if entries.len() == limit {
+            let mut cursor = entries.last().map_or_else(Vec::new, |(k, _)| k.clone());
+            if !reverse {
+                cursor.push(0);
+            }
+            return Ok(ScanPage {
+                entries,
+                cursor: Some(cursor),
+            });
+        }
+        entries.push((user_key.to_vec(), value.to_vec()));
+        if reverse {
+            iter.prev()?;
+        } else {
+            iter.next()?;
+        }
+    }
+    Ok(ScanPage {
+        entries,
+        cursor: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, TempDir};
+    use super::super::Engine;
+    use super::{DbIter, ScanRange};
+
+    fn keys(range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("k{:02}", i)).collect()
+    }
+
+    // Sigue el cursor hasta el final (como el servidor); las llaves en el orden del recorrido
+    fn all_pages(engine: &Engine, range: ScanRange, limit: usize, reverse: bool) -> Vec<String> {
+        let mut seen = Vec::new();
+        let mut next = range.clone();
+        loop {
+            let page = if reverse {
+                engine.rev_scan(&next, limit).unwrap()
+            } else {
+                engine.scan(&next, limit).unwrap()
+            };
+            assert!(page.entries.len() <= limit);
+            seen.extend(
+                page.entries
+                    .iter()
+                    .map(|(k, _)| String::from_utf8(k.clone()).unwrap()),
+            );
+            match page.cursor {
+                Some(cursor) => next = range.clone().resume(cursor, reverse),
+                None => return seen,
+            }
+        }
+    }
+
+    fn prefix(prefix: &[u8]) -> ScanRange {
+        ScanRange {
+            prefix: prefix.to_vec(),
+            ..ScanRange::default()
+        }
+    }
+
+    // k00..k29 repartidas entre SSTables de varios niveles y la memtable, con k05 borrada, k10
+    // pisada y llaves vecinas (j, l) fuera del prefijo
+    fn fill(engine: &mut Engine) {
+        engine.set(b"j", b"-").unwrap();
+        engine.set(b"l", b"-").unwrap();
+        for i in 0..30 {
+            let key = format!("k{:02}", i);
+            engine
+                .set(key.as_bytes(), format!("v{}", i).as_bytes())
+                .unwrap();
+            if i % 8 == 7 {
+                engine.flush().unwrap();
+            }
+            if i == 15 {
+                testing::compact_all(engine);
+            }
+        }
+        engine.delete(b"k05").unwrap();
+        engine.set(b"k10", b"w10").unwrap();
+    }
+
+    fn live() -> Vec<String> {
+        keys(0..30).into_iter().filter(|k| k != "k05").collect()
+    }
+
+    #[test]
+    fn pages_in_both_directions() {
+        let dir = TempDir::new("scan_pages");
+        let mut engine = testing::open(&dir, testing::small_options());
+        fill(&mut engine);
+
+        for limit in [1, 4, 29, 100] {
+            assert_eq!(all_pages(&engine, prefix(b"k"), limit, false), live());
+            let mut backwards = live();
+            backwards.reverse();
+            assert_eq!(all_pages(&engine, prefix(b"k"), limit, true), backwards);
+        }
+
+        // Inicio y final además del prefijo: [k07, k23)
+        let bounded = ScanRange {
+            start: b"k07".to_vec(),
+            end: Some(b"k23".to_vec()),
+            prefix: b"k".to_vec(),
+        };
+        assert_eq!(all_pages(&engine, bounded.clone(), 4, false), keys(7..23));
+        let mut backwards = keys(7..23);
+        backwards.reverse();
+        assert_eq!(all_pages(&engine, bounded, 4, true), backwards);
+
+        let page = engine.scan(&prefix(b"k1"), 100).unwrap();
+        assert_eq!(page.entries[0], (b"k10".to_vec(), b"w10".to_vec()));
+        assert_eq!(page.entries.len(), 10);
+        assert_eq!(page.cursor, None);
+    }
+
+    // Entre página y página entran y se borran llaves: lo ya entregado no se repite, lo que se
+    // borró adelante no aparece y lo nuevo adelante sí
+    #[test]
+    fn a_cursor_survives_writes_between_pages() {
+        let dir = TempDir::new("scan_cursor");
+        let mut engine = testing::open(&dir, testing::small_options());
+        fill(&mut engine);
+
+        for reverse in [false, true] {
+            let range = prefix(b"k");
+            let first = if reverse {
+                engine.rev_scan(&range, 10).unwrap()
+            } else {
+                engine.scan(&range, 10).unwrap()
+            };
+            let mut seen: Vec<Vec<u8>> = first.entries.into_iter().map(|(k, _)| k).collect();
+            // Una llave nueva del lado ya recorrido, una del lado que falta y una borrada que falta
+            let (behind, ahead, gone) = if reverse {
+                (&b"k28a"[..], &b"k03a"[..], &b"k04"[..])
+            } else {
+                (&b"k01a"[..], &b"k26a"[..], &b"k25"[..])
+            };
+            engine.set(behind, b"new").unwrap();
+            engine.set(ahead, b"new").unwrap();
+            engine.delete(gone).unwrap();
+            engine.flush().unwrap();
+
+            let mut cursor = first.cursor;
+            while let Some(c) = cursor {
+                let next = range.clone().resume(c, reverse);
+                let page = if reverse {
+                    engine.rev_scan(&next, 10).unwrap()
+                } else {
+                    engine.scan(&next, 10).unwrap()
+                };
+                seen.extend(page.entries.into_iter().map(|(k, _)| k));
+                cursor = page.cursor;
+            }
+
+            assert!(!seen.contains(&behind.to_vec()));
+            assert!(seen.contains(&ahead.to_vec()));
+            assert!(!seen.contains(&gone.to_vec()));
+            let mut sorted = seen.clone();
+            sorted.sort();
+            sorted.dedup();
+            if reverse {
+                sorted.reverse();
+            }
+            assert_eq!(seen, sorted);
+        }
+    }
+
+    // Un borrado de rango tapa llaves de varias páginas (y de varias tablas); lo que se escribe
+    // después del borrado vuelve a aparecer
+    #[test]
+    fn a_range_delete_hides_keys_across_pages() {
+        let dir = TempDir::new("scan_range_delete");
+        let mut engine = testing::open(&dir, testing::small_options());
+        fill(&mut engine);
+        engine.delete_range(b"k08", Some(b"k20")).unwrap();
+        engine.flush().unwrap();
+        engine.set(b"k12", b"again").unwrap();
+
+        let mut expected: Vec<String> = live()
+            .into_iter()
+            .filter(|k| k.as_str() < "k08" || k.as_str() >= "k20" || k == "k12")
+            .collect();
+        for limit in [1, 3, 100] {
+            assert_eq!(all_pages(&engine, prefix(b"k"), limit, false), expected);
+        }
+        expected.reverse();
+        for limit in [1, 3, 100] {
+            assert_eq!(all_pages(&engine, prefix(b"k"), limit, true), expected);
+        }
+
+        // Igual después de que la compactación aplica el borrado
+        testing::compact_all(&mut engine);
+        assert_eq!(all_pages(&engine, prefix(b"k"), 3, true), expected);
+        let page = engine
+            .scan(
+                &ScanRange {
+                    start: b"k08".to_vec(),
+                    end: Some(b"k20".to_vec()),
+                    prefix: Vec::new(),
+                },
+                100,
+            )
+            .unwrap();
+        assert_eq!(page.entries, vec![(b"k12".to_vec(), b"again".to_vec())]);
+    }
+
+    fn at(iter: &DbIter) -> Option<String> {
+        iter.current()
+            .map(|(k, _)| String::from_utf8(k.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn seek_and_seek_for_prev() {
+        let dir = TempDir::new("scan_seek");
+        let mut engine = testing::open(&dir, testing::small_options());
+        fill(&mut engine);
+        let mut iter = engine.iter();
+
+        // A la primera llave >= la pedida (k05 está borrada)
+        iter.seek(b"k05").unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k06"));
+        iter.seek(b"k04a").unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k06"));
+        iter.seek(b"k10").unwrap();
+        assert_eq!(iter.current(), Some((&b"k10"[..], &b"w10"[..])));
+        iter.seek(b"").unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("j"));
+        iter.seek(b"m").unwrap();
+        assert_eq!(at(&iter), None);
+
+        // A la última llave <= la pedida
+        iter.seek_for_prev(b"k05").unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k04"));
+        iter.seek_for_prev(b"k10").unwrap();
+        assert_eq!(iter.current(), Some((&b"k10"[..], &b"w10"[..])));
+        iter.seek_for_prev(b"k10a").unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k10"));
+        iter.seek_for_prev(b"k").unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("j"));
+        iter.seek_for_prev(b"a").unwrap();
+        assert_eq!(at(&iter), None);
+        iter.seek_to_last().unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("l"));
+    }
+
+    #[test]
+    fn switching_direction_mid_walk() {
+        let dir = TempDir::new("scan_direction");
+        let mut engine = testing::open(&dir, testing::small_options());
+        fill(&mut engine);
+        let mut iter = engine.iter();
+
+        iter.seek(b"k03").unwrap();
+        let mut path = Vec::new();
+        for forward in [true, true, false, false, false, true, true, true] {
+            if forward {
+                iter.next().unwrap();
+            } else {
+                iter.prev().unwrap();
+            }
+            path.push(at(&iter).unwrap());
+        }
+        assert_eq!(
+            path,
+            ["k04", "k06", "k04", "k03", "k02", "k03", "k04", "k06"]
+        );
+
+        // En las puntas: se sale y ahí se queda
+        iter.seek_to_last().unwrap();
+        iter.prev().unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k29"));
+        iter.next().unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("l"));
+        iter.next().unwrap();
+        assert_eq!(at(&iter), None);
+        iter.prev().unwrap();
+        assert_eq!(at(&iter), None);
+
+        // Ida y vuelta completas dan lo mismo
+        let mut forward = Vec::new();
+        iter.seek(b"").unwrap();
+        while let Some(key) = at(&iter) {
+            forward.push(key);
+            iter.next().unwrap();
+        }
+        let mut backward = Vec::new();
+        iter.seek_to_last().unwrap();
+        while let Some(key) = at(&iter) {
+            backward.push(key);
+            iter.prev().unwrap();
+        }
+        backward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(forward.len(), live().len() + 2);
+    }
+
+    // El iterador es una foto: lo que se escribe después de crearlo no se ve
+    #[test]
+    fn an_iterator_does_not_see_later_writes() {
+        let dir = TempDir::new("scan_snapshot");
+        let mut engine = testing::open(&dir, testing::small_options());
+        fill(&mut engine);
+        let seq = engine.last_sequence;
+        engine.set(b"k05", b"back").unwrap();
+        engine.delete(b"k06").unwrap();
+
+        let mut iter = DbIter::new(&engine, seq, super::super::now_millis());
+        iter.seek(b"k04").unwrap();
+        iter.next().unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k06"));
+        iter.prev().unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k04"));
+
+        let mut iter = engine.iter();
+        iter.seek(b"k04").unwrap();
+        iter.next().unwrap();
+        assert_eq!(iter.current(), Some((&b"k05"[..], &b"back"[..])));
+        iter.next().unwrap();
+        assert_eq!(at(&iter).as_deref(), Some("k07"));
+    }
+}
@@ -0,0 +1,340 @@
+// El WAL COMPARTIDO (uno solo para todos los keyspaces de un servidor)
+// Cada keyspace tiene su memtable, sus SSTables y su MANIFEST, pero los logs son los mismos para
+// todos: así un fsync cubre las escrituras de todos los keyspaces a la vez (el group commit sigue
+// siendo uno solo) y las secuencias son globales (crecen juntas para todos).
+//
+// Cada registro lleva el id de su keyspace. Lo delicado es saber cuándo se puede borrar un log:
+// cuando un keyspace vuelca su memtable, el log se rota, pero el viejo todavía puede tener escrituras
+// de OTROS keyspaces que siguen en sus memtables. Por eso cada keyspace "clava" el log más viejo que
+// todavía necesita (el primero en el que escribió desde su último volcado) y un log se borra recién
+// cuando ningún keyspace lo tiene clavado. Un keyspace con la memtable vacía no clava nada.
+//
+// Al arrancar se releen todos los logs una sola vez y cada keyspace, al abrirse, se lleva lo suyo
+// (salteando lo que su MANIFEST dice que ya está en SSTables). Lo de keyspaces que ya no existen
+// se descarta.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::durability::{Durability, GroupCommit};
+use super::entry::Entry;
+use super::wal::{self, log_path, Record, RecoveryReport, WalWriter};
+
+struct LogState {
+    writer: WalWriter,
+    // El log en el que escribimos ahora (siempre el de id más alto)
+    current: u64,
+    // Los logs que hay en disco, en orden
+    logs: Vec<u64>,
+    last_sequence: u64,
+    // keyspace -> el log más viejo con escrituras suyas que todavía no llegaron a una SSTable
+    pins: HashMap<u32, u64>,
+    // Hasta que abran todos los keyspaces no se borra nada: los que faltan todavía no clavaron sus logs
+    recovering: bool,
+}
+
+pub struct SharedWal {
+    dir: PathBuf,
+    state: Mutex<LogState>,
+    // Compartido con los hilos de conexión: esperan su fsync SIN ningún candado
+    commit: Arc<GroupCommit>,
+    // Qué pasó al releer los logs en el arranque (registros sanos vs. descartados)
+    recovery: RecoveryReport,
+    // Lo releído que espera a que su keyspace lo reclame: keyspace -> [(log, registro)]
+    recovered: Mutex<HashMap<u32, Vec<(u64, Record)>>>,
+}
+
+impl SharedWal {
+    pub fn open(dir: &Path, durability: Durability) -> io::Result<Arc<Self>> {
+        let mut logs = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            if let Some(id) = name
+                .to_string_lossy()
+                .strip_suffix(".log")
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                logs.push(id);
+            }
+        }
+        logs.sort_unstable();
+
+        let mut last_sequence = 0;
+        let mut recovery = RecoveryReport::default();
+        let mut recovered: HashMap<u32, Vec<(u64, Record)>> = HashMap::new();
+        for id in &logs {
+            let report = wal::replay(&log_path(dir, *id), |record| {
+                last_sequence = last_sequence.max(record.seq + record.writes.len() as u64 - 1);
+                recovered
+                    .entry(record.keyspace)
+                    .or_default()
+                    .push((*id, record));
+            })?;
+            if report.discarded > 0 {
+                println!(
+                    "   ⚠️  Log {:06}.log cortado: {} registros descartados ({} bytes truncados).",
+                    id, report.discarded, report.truncated_bytes
+                );
+            }
+            recovery.merge(report);
+        }
+
+        // Seguimos escribiendo en el último log (o abrimos uno nuevo si no había ninguno)
+        if logs.is_empty() {
+            logs.push(1);
+        }
+        let current = *logs.last().unwrap();
+        let writer = WalWriter::open(dir, current)?;
+        let commit = GroupCommit::new(durability, writer.try_clone_file()?, last_sequence);
+        Ok(Arc::new(SharedWal {
+            dir: dir.to_path_buf(),
+            state: Mutex::new(LogState {
+                writer,
+                current,
+                logs,
+                last_sequence,
+                pins: HashMap::new(),
+                recovering: true,
+            }),
+            commit,
+            recovery,
+            recovered: Mutex::new(recovered),
+        }))
+    }
+
+    // Lo que el keyspace tenía en los logs a partir de `first_log` (lo anterior ya está en sus
+    // SSTables), en orden. Lo deja clavado desde el primer log en el que aparece.
+    pub fn recover(&self, keyspace: u32, first_log: u64) -> io::Result<Vec<Record>> {
+        let records = self
+            .recovered
+            .lock()
+            .unwrap()
+            .remove(&keyspace)
+            .unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        // Un MANIFEST que ya vio logs más nuevos que los que quedan: seguimos numerando desde ahí
+        if first_log > state.current {
+            self.rotate_locked(&mut state, first_log)?;
+        }
+        let records: Vec<(u64, Record)> = records
+            .into_iter()
+            .filter(|(log, _)| *log >= first_log)
+            .collect();
+        if let Some((log, _)) = records.first() {
+            state.pins.insert(keyspace, *log);
+        }
+        Ok(records.into_iter().map(|(_, record)| record).collect())
+    }
+
+    // Ya abrieron todos los keyspaces: lo que nadie reclamó es de keyspaces borrados
+    pub fn finish_recovery(&self) -> io::Result<()> {
+        self.recovered.lock().unwrap().clear();
+        let mut state = self.state.lock().unwrap();
+        state.recovering = false;
+        self.collect(&mut state)
+    }
+
+    // Ninguna secuencia nueva puede repetir una que ya está en las SSTables de algún keyspace
+    pub fn advance_sequence(&self, seq: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.last_sequence = state.last_sequence.max(seq);
+    }
+
+    // Anota un lote del keyspace y le asigna sus secuencias (bajo el mismo candado: el group commit
+    // necesita que las secuencias lleguen al log en orden)
+    pub fn append(
+        &self,
+        keyspace: u32,
+        time: u64,
+        writes: Vec<(Vec<u8>, Entry)>,
+    ) -> io::Result<Record> {
+        let mut state = self.state.lock().unwrap();
+        let record = Record {
+            seq: state.last_sequence + 1,
+            time,
+            keyspace,
+            writes,
+        };
+        state.writer.append(&record)?;
+        state.last_sequence = record.seq + record.writes.len() as u64 - 1;
+        let current = state.current;
+        state.pins.entry(keyspace).or_insert(current);
+        self.commit.written(state.last_sequence);
+        Ok(record)
+    }
+
+    // Un keyspace va a volcar su memtable: lo que escriba de acá en adelante va a un log nuevo.
+    // Devuelve el id del log nuevo.
+    pub fn rotate(&self) -> io::Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.current + 1;
+        self.rotate_locked(&mut state, id)?;
+        Ok(id)
+    }
+
+    fn rotate_locked(&self, state: &mut LogState, id: u64) -> io::Result<()> {
+        // Lo escrito en el log viejo queda durable antes de empezar el nuevo
+        if self.commit.policy() != Durability::OsManaged {
+            state.writer.sync()?;
+        }
+        state.writer = WalWriter::open(&self.dir, id)?;
+        state.current = id;
+        state.logs.push(id);
+        self.commit.switch_file(state.writer.try_clone_file()?);
+        Ok(())
+    }
+
+    // El keyspace terminó un volcado (o dejó de existir): ahora necesita los logs desde `pin`
+    // (None = ninguno). Se borran los que ya no necesita nadie.
+    pub fn release(&self, keyspace: u32, pin: Option<u64>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match pin {
+            Some(log) => state.pins.insert(keyspace, log),
+            None => state.pins.remove(&keyspace),
+        };
+        self.collect(&mut state)
+    }
+
+    fn collect(&self, state: &mut LogState) -> io::Result<()> {
+        if state.recovering {
+            return Ok(());
+        }
+        let floor = state
+            .pins
+            .values()
+            .copied()
+            .min()
+            .unwrap_or(state.current)
+            .min(state.current);
+        while state.logs.first().is_some_and(|log| *log < floor) {
+            let log = state.logs.remove(0);
+            fs::remove_file(log_path(&self.dir, log))?;
+        }
+        Ok(())
+    }
+
+    pub fn group_commit(&self) -> Arc<GroupCommit> {
+        Arc::clone(&self.commit)
+    }
+
+    pub fn recovery(&self) -> RecoveryReport {
+        self.recovery
+    }
+
+    pub fn num_logs(&self) -> usize {
+        self.state.lock().unwrap().logs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::super::cache::BlockCache;
+    use super::super::testing::{self, TempDir};
+    use super::super::Engine;
+    use super::{log_path, SharedWal};
+
+    // El keyspace por defecto y uno más sobre el mismo WAL, como los abre Keyspaces::open (sin los
+    // hilos de fondo). Soltarlos sin volcar es un corte.
+    fn open_both(dir: &Path) -> (Arc<SharedWal>, Engine, Engine) {
+        let options = testing::small_options();
+        let wal = SharedWal::open(dir, options.durability).unwrap();
+        let cache = Arc::new(BlockCache::new(options.block_cache_size));
+        let open = |path: &Path, id| {
+            let options = testing::small_options();
+            let wal = Arc::clone(&wal);
+            Engine::open(path.to_path_buf(), options, id, wal, Arc::clone(&cache)).unwrap()
+        };
+        let default = open(dir, 0);
+        let other = open(&dir.join("keyspaces").join("other"), 1);
+        wal.finish_recovery().unwrap();
+        (wal, default, other)
+    }
+
+    fn logs_on_disk(dir: &Path) -> Vec<u64> {
+        (1..20).filter(|id| log_path(dir, *id).exists()).collect()
+    }
+
+    // Volcar un keyspace rota el log, pero el viejo se queda mientras otro tenga ahí escrituras
+    // que todavía están solo en su memtable
+    #[test]
+    fn a_log_stays_while_another_keyspace_pins_it() {
+        let dir = TempDir::new("wal_pins");
+        let (wal, mut default, mut other) = open_both(&dir);
+        default.set(b"a", b"1").unwrap();
+        other.set(b"b", b"1").unwrap();
+        default.flush().unwrap();
+        assert_eq!(logs_on_disk(&dir), vec![1, 2]);
+
+        // Lo nuevo de `default` va al log 2: clava ese, no el 1
+        default.set(b"a", b"2").unwrap();
+        default.flush().unwrap();
+        assert_eq!(logs_on_disk(&dir), vec![1, 2, 3]);
+        assert_eq!(wal.num_logs(), 3);
+
+        // `other` vuelca y suelta el 1; nadie clava el 2 tampoco
+        other.flush().unwrap();
+        assert_eq!(logs_on_disk(&dir), vec![4]);
+        assert_eq!(wal.num_logs(), 1);
+    }
+
+    // Corte después de que solo uno de los dos volcó: cada uno recupera lo suyo, ni más ni menos
+    #[test]
+    fn recovery_after_only_one_keyspace_flushed() {
+        let dir = TempDir::new("wal_partial_flush");
+        let (_, mut default, mut other) = open_both(&dir);
+        default.set(b"a", b"1").unwrap();
+        other.set(b"b", b"1").unwrap();
+        default.flush().unwrap();
+        default.set(b"a", b"2").unwrap();
+        other.set(b"b", b"2").unwrap();
+        other.set(b"c", b"1").unwrap();
+        let last = default.last_sequence;
+        drop((default, other));
+
+        let (wal, mut default, other) = open_both(&dir);
+        assert_eq!(wal.recovery().recovered, 5);
+        // `default` ya tenía el log 1 en una SSTable: solo se lleva su escritura del log 2
+        assert_eq!(default.memtable.iter().count(), 1);
+        assert_eq!(default.get(b"a").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(other.memtable.iter().count(), 3);
+        assert_eq!(other.get(b"b").unwrap(), Some(b"2".to_vec()));
+        assert_eq!(other.get(b"c").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(default.get(b"b").unwrap(), None);
+        assert_eq!(other.get(b"a").unwrap(), None);
+        // Las secuencias siguen desde la más alta de los dos
+        assert!(default.set(b"a", b"3").unwrap() > last + 2);
+        // `other` sigue clavando el log 1
+        assert_eq!(logs_on_disk(&dir), vec![1, 2]);
+    }
+
+    // Borrar un keyspace que clava el log más viejo lo suelta: los logs que solo él necesitaba se
+    // van y al reabrir sus registros se descartan
+    #[test]
+    fn dropping_the_keyspace_that_pins_the_oldest_log() {
+        let dir = TempDir::new("wal_drop");
+        let (wal, mut default, mut other) = open_both(&dir);
+        other.set(b"b", b"1").unwrap();
+        for round in 0..3 {
+            default.set(b"a", &[round]).unwrap();
+            default.flush().unwrap();
+        }
+        assert_eq!(logs_on_disk(&dir), vec![1, 2, 3, 4]);
+
+        other.close().unwrap();
+        assert_eq!(logs_on_disk(&dir), vec![4]);
+        assert_eq!(wal.num_logs(), 1);
+        assert!(other.set(b"b", b"2").is_err());
+        default.set(b"a", b"last").unwrap();
+        drop((default, other));
+
+        let (_, default, other) = open_both(&dir);
+        assert_eq!(default.get(b"a").unwrap(), Some(b"last".to_vec()));
+        assert_eq!(other.get(b"b").unwrap(), None);
+    }
+}
@@ -0,0 +1,65 @@
+// Los SNAPSHOTS (una foto consistente para leer)
+// Cada escritura lleva un número de secuencia creciente. Un snapshot es simplemente "la última
+// secuencia que existía cuando lo pedimos": sus lecturas ignoran toda versión más nueva, así que
+// una lectura de varias llaves nunca ve un lote de SETs de otra conexión a medio aplicar.
+//
+// Mientras un snapshot esté vivo, la compactación no puede tirar las versiones que él ve:
+// por eso anotamos en un registro compartido cuántos snapshots hay de cada secuencia.
+// El registro se limpia solo cuando el Snapshot se suelta (Drop).
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// secuencia -> cuántos snapshots vivos la usan
+#[derive(Default)]
+pub struct SnapshotList {
+    live: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl SnapshotList {
+    pub fn acquire(self: &Arc<Self>, seq: u64) -> Snapshot {
+        *self.live.lock().unwrap().entry(seq).or_insert(0) += 1;
+        Snapshot {
+            seq,
+            list: Arc::clone(self),
+        }
+    }
+
+    // La secuencia del snapshot vivo más viejo
+    pub fn oldest(&self) -> Option<u64> {
+        self.live.lock().unwrap().keys().next().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.live.lock().unwrap().values().sum()
+    }
+}
+
+pub struct Snapshot {
+    seq: u64,
+    list: Arc<SnapshotList>,
+}
+
+impl Snapshot {
+    pub fn sequence(&self) -> u64 {
+        self.seq
+    }
+}
+
+impl Clone for Snapshot {
+    fn clone(&self) -> Self {
+        self.list.acquire(self.seq)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let mut live = self.list.live.lock().unwrap();
+        if let Some(count) = live.get_mut(&self.seq) {
+            *count -= 1;
+            if *count == 0 {
+                live.remove(&self.seq);
+            }
+        }
+    }
+}
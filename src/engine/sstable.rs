@@ -0,0 +1,535 @@
+// Las SSTABLES (Sorted String Tables): la memoria de largo plazo del motor.
+// Son archivos INMUTABLES: se escriben una sola vez (al volcar una memtable o al compactar)
+// y de ahí en adelante solo se leen. Por eso varios hilos pueden leerlas a la vez sin candados.
+//
+// Formato en disco (todos los enteros en little-endian):
+//   [bloques]  bloque de datos | bloque de datos | ...   <- ~4 KB c/u, llaves ORDENADAS (ver block.rs)
+//              cada valor lleva su tipo adelante: valor o lápida (ver entry.rs)
+//   [índice]   klen u32 | última llave del bloque | offset u64 | size u32   <- UNA entrada por bloque
+//   [bloom]    bits... | num_probes u8                 <- vacío si la tabla se armó sin filtro
+//   [footer]   index_offset u64 | bloom_offset u64 | count u64 | MAGIC u64
+//
+// El índice es "ralo" (sparse): no dice dónde está cada llave, sino en qué bloque buscarla.
+// Así entra cómodo en RAM aunque la tabla tenga millones de llaves.
+//
+// Las llaves guardadas son LLAVES INTERNAS (llave + secuencia, ver key.rs). El filtro de Bloom,
+// en cambio, se arma con la parte de la llave del usuario: la pregunta es "¿está la llave?",
+// sin importar cuál versión.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::block::{Block, BlockBuilder};
+use super::bloom::{self, BloomFilter};
+use super::cache::BlockCache;
+use super::entry::Entry;
+use super::key;
+use super::version::FileMeta;
+
+const MAGIC: u64 = 0x4348_524F_4E4F_5334; // "CHRONOS4"
+const FOOTER_SIZE: u64 = 32;
+
+pub fn table_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:06}.sst", id))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Lo que hace falta saber para ESCRIBIR una tabla (una copia chiquita de EngineOptions que
+// la compactación en segundo plano se puede llevar a su hilo)
+#[derive(Clone, Copy)]
+pub struct TableOptions {
+    pub block_size: usize,
+    pub bloom_bits_per_key: usize,
+}
+
+// Lo que una tabla tiene de una llave para una lectura en cierta secuencia
+pub enum Lookup {
+    // La versión que ve la lectura, con su llave interna
+    Found(Vec<u8>, Entry),
+    // Solo versiones más nuevas que las que ve la lectura (GETAT, snapshots)
+    OnlyNewer,
+    // Ninguna versión de la llave
+    Absent,
+}
+
+// Dónde vive un bloque dentro del archivo
+struct BlockHandle {
+    last_key: Vec<u8>,
+    offset: u64,
+    size: u32,
+}
+
+// ---------------------------------------------------------------------------------
+// ESCRITURA
+// ---------------------------------------------------------------------------------
+
+pub struct TableBuilder {
+    id: u64,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: BufWriter<File>,
+    offset: u64,
+    block_size: usize,
+    block: BlockBuilder,
+    index: Vec<BlockHandle>,
+    count: u64,
+    // Cuántas entradas tienen vencimiento (al arrancar solo releemos las tablas que tengan alguna)
+    expiring: u64,
+    // Cuántas lápidas de rango (al arrancar se releen para armar la lista en memoria)
+    range_tombstones: u64,
+    smallest: Option<Vec<u8>>,
+    last_key: Vec<u8>,
+    // Hashes de las llaves para armar el filtro de Bloom al final (0 bits = sin filtro)
+    bits_per_key: usize,
+    key_hashes: Vec<u64>,
+}
+
+impl TableBuilder {
+    // Escribimos sobre un '.tmp' y recién al final lo renombramos: una tabla a medias nunca se ve como viva
+    pub fn create(dir: &Path, id: u64, options: &TableOptions) -> io::Result<Self> {
+        let path = table_path(dir, id);
+        let tmp_path = path.with_extension("tmp");
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp_path)?;
+        Ok(TableBuilder {
+            id,
+            path,
+            tmp_path,
+            writer: BufWriter::new(file),
+            offset: 0,
+            block_size: options.block_size,
+            block: BlockBuilder::new(),
+            index: Vec::new(),
+            count: 0,
+            expiring: 0,
+            range_tombstones: 0,
+            smallest: None,
+            last_key: Vec::new(),
+            bits_per_key: options.bloom_bits_per_key,
+            key_hashes: Vec::new(),
+        })
+    }
+
+    // Las llaves TIENEN que llegar en orden estrictamente creciente
+    pub fn add(&mut self, key: &[u8], entry: &Entry) -> io::Result<()> {
+        if self.smallest.is_some() && key <= self.last_key.as_slice() {
+            return Err(invalid("llaves fuera de orden al construir la SSTable"));
+        }
+        // Varias versiones seguidas de la misma llave van UNA sola vez al filtro
+        let new_user_key =
+            self.smallest.is_none() || key::user_part(key) != key::user_part(&self.last_key);
+        if self.smallest.is_none() {
+            self.smallest = Some(key.to_vec());
+        }
+        if self.bits_per_key > 0 && new_user_key {
+            self.key_hashes.push(bloom::hash(key::user_part(key)));
+        }
+        if entry.deadline().is_some() {
+            self.expiring += 1;
+        }
+        if let Entry::RangeDelete(_) = entry {
+            self.range_tombstones += 1;
+        }
+        self.block.add(key, &entry.encode());
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.count += 1;
+
+        if self.block.estimated_size() >= self.block_size {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let block = self.block.finish();
+        self.writer.write_all(&block)?;
+        self.index.push(BlockHandle {
+            last_key: self.last_key.clone(),
+            offset: self.offset,
+            size: block.len() as u32,
+        });
+        self.offset += block.len() as u64;
+        Ok(())
+    }
+
+    // Bytes escritos hasta ahora (la compactación corta tablas nuevas al pasar su tamaño objetivo)
+    pub fn size(&self) -> u64 {
+        self.offset + self.block.estimated_size() as u64
+    }
+
+    // Escribe el último bloque + índice + filtro + footer y fuerza todo al disco (fsync).
+    // Devuelve la ficha de la tabla.
+    pub fn finish(mut self) -> io::Result<FileMeta> {
+        self.flush_block()?;
+
+        let index_offset = self.offset;
+        for handle in &self.index {
+            self.writer
+                .write_all(&(handle.last_key.len() as u32).to_le_bytes())?;
+            self.writer.write_all(&handle.last_key)?;
+            self.writer.write_all(&handle.offset.to_le_bytes())?;
+            self.writer.write_all(&handle.size.to_le_bytes())?;
+            self.offset += 16 + handle.last_key.len() as u64;
+        }
+
+        let bloom_offset = self.offset;
+        if self.bits_per_key > 0 {
+            let filter = BloomFilter::build(&self.key_hashes, self.bits_per_key).encode();
+            self.writer.write_all(&filter)?;
+            self.offset += filter.len() as u64;
+        }
+
+        self.writer.write_all(&index_offset.to_le_bytes())?;
+        self.writer.write_all(&bloom_offset.to_le_bytes())?;
+        self.writer.write_all(&self.count.to_le_bytes())?;
+        self.writer.write_all(&MAGIC.to_le_bytes())?;
+        self.offset += FOOTER_SIZE;
+
+        let file = self.writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&self.tmp_path, &self.path)?;
+
+        // `run` y los tiempos los completa quien construyó la tabla (volcado o compactación)
+        Ok(FileMeta {
+            id: self.id,
+            size: self.offset,
+            smallest: self.smallest.unwrap_or_default(),
+            largest: self.last_key,
+            run: self.id,
+            min_time: 0,
+            max_time: 0,
+            expiring: self.expiring,
+            range_tombstones: self.range_tombstones,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// LECTURA
+// ---------------------------------------------------------------------------------
+
+pub struct Table {
+    meta: FileMeta,
+    path: PathBuf,
+    file: File,
+    // El índice ralo (una entrada por bloque) vive en RAM; los bloques se leen a pedido
+    index: Vec<BlockHandle>,
+    filter: Option<BloomFilter>,
+    cache: Arc<BlockCache>,
+}
+
+impl Table {
+    pub fn open(dir: &Path, meta: FileMeta, cache: Arc<BlockCache>) -> io::Result<Self> {
+        let path = table_path(dir, meta.id);
+        let file = File::open(&path)?;
+        let size = file.metadata()?.len();
+        if size < FOOTER_SIZE {
+            return Err(invalid("SSTable demasiado chica (sin footer)"));
+        }
+
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        file.read_exact_at(&mut footer, size - FOOTER_SIZE)?;
+        let index_offset = u64::from_le_bytes(footer[0..8].try_into().unwrap());
+        let bloom_offset = u64::from_le_bytes(footer[8..16].try_into().unwrap());
+        let magic = u64::from_le_bytes(footer[24..32].try_into().unwrap());
+        if magic != MAGIC || index_offset > bloom_offset || bloom_offset > size - FOOTER_SIZE {
+            return Err(invalid("footer de SSTable corrupto"));
+        }
+
+        let mut raw = vec![0u8; (size - FOOTER_SIZE - bloom_offset) as usize];
+        file.read_exact_at(&mut raw, bloom_offset)?;
+        let filter = BloomFilter::decode(raw);
+
+        let mut raw = vec![0u8; (bloom_offset - index_offset) as usize];
+        file.read_exact_at(&mut raw, index_offset)?;
+        let mut index = Vec::new();
+        let mut pos = 0;
+        while pos < raw.len() {
+            let klen = read_u32(&raw, &mut pos)? as usize;
+            let last_key = take(&raw, &mut pos, klen)?.to_vec();
+            let offset = u64::from_le_bytes(take(&raw, &mut pos, 8)?.try_into().unwrap());
+            let size = read_u32(&raw, &mut pos)?;
+            index.push(BlockHandle {
+                last_key,
+                offset,
+                size,
+            });
+        }
+
+        Ok(Table {
+            meta,
+            path,
+            file,
+            index,
+            filter,
+            cache,
+        })
+    }
+
+    pub fn meta(&self) -> &FileMeta {
+        &self.meta
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Si devuelve false, la llave SEGURO no está en esta tabla (`user_part` viene de key::prefix)
+    pub fn may_contain(&self, user_part: &[u8]) -> bool {
+        self.filter
+            .as_ref()
+            .is_none_or(|f| f.may_contain(user_part))
+    }
+
+    pub fn has_filter(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    fn read_block(&self, handle: &BlockHandle) -> io::Result<Block> {
+        let mut raw = vec![0u8; handle.size as usize];
+        self.file.read_exact_at(&mut raw, handle.offset)?;
+        Block::decode(raw)
+    }
+
+    // Primero el caché compartido; si no está, disco y lo dejamos cacheado para el próximo
+    fn cached_block(&self, handle: &BlockHandle) -> io::Result<Arc<Block>> {
+        let key = (self.meta.id, handle.offset);
+        if let Some(block) = self.cache.get(key) {
+            return Ok(block);
+        }
+        let block = Arc::new(self.read_block(handle)?);
+        self.cache.insert(key, Arc::clone(&block));
+        Ok(block)
+    }
+
+    // La versión más nueva de la llave con secuencia <= seq (puede ser una lápida), o si no la hay,
+    // si la tabla tiene versiones más nuevas de la llave (con la misma lectura: lo anterior a
+    // target está en el bloque que ya se leyó o es la última llave del bloque anterior, en el índice)
+    pub fn lookup(&self, user_key: &[u8], seq: u64) -> io::Result<Lookup> {
+        // El primer bloque cuya última llave sea >= target es el único que puede tenerla
+        let target = key::lookup(user_key, seq);
+        let same_key = |internal: &[u8]| key::user_part(internal) == key::user_part(&target);
+        let pos = self
+            .index
+            .partition_point(|h| h.last_key.as_slice() < target.as_slice());
+        let (previous, found) = match self.index.get(pos) {
+            Some(handle) => self.cached_block(handle)?.seek(&target)?,
+            None => (None, None),
+        };
+        if let Some((found, value)) = found {
+            if same_key(&found) {
+                return Ok(Lookup::Found(found, Entry::decode(value)?));
+            }
+        }
+        let previous = match previous {
+            Some(previous) => Some(previous),
+            None => pos.checked_sub(1).map(|i| self.index[i].last_key.clone()),
+        };
+        Ok(match previous {
+            Some(previous) if same_key(&previous) => Lookup::OnlyNewer,
+            _ => Lookup::Absent,
+        })
+    }
+
+    pub fn iter(self: &Arc<Self>) -> TableIter {
+        TableIter {
+            table: Arc::clone(self),
+            blocks: 0..self.index.len(),
+            reverse: false,
+            entries: Vec::new().into_iter(),
+            start: None,
+            read_bytes: None,
+        }
+    }
+
+    // Recorre desde la primera llave >= target (se saltea directo al bloque que la puede tener)
+    pub fn iter_from(self: &Arc<Self>, target: &[u8]) -> TableIter {
+        TableIter {
+            table: Arc::clone(self),
+            blocks: self
+                .index
+                .partition_point(|h| h.last_key.as_slice() < target)
+                ..self.index.len(),
+            reverse: false,
+            entries: Vec::new().into_iter(),
+            start: Some(target.to_vec()),
+            read_bytes: None,
+        }
+    }
+
+    // Recorre HACIA ATRÁS las llaves < target (None = desde la última de la tabla)
+    pub fn iter_rev_before(self: &Arc<Self>, target: Option<&[u8]>) -> TableIter {
+        // El bloque donde caería target puede tener llaves más chicas; los anteriores, todas
+        let end = target.map_or(self.index.len(), |target| {
+            let pos = self
+                .index
+                .partition_point(|h| h.last_key.as_slice() < target);
+            (pos + 1).min(self.index.len())
+        });
+        TableIter {
+            table: Arc::clone(self),
+            blocks: 0..end,
+            reverse: true,
+            entries: Vec::new().into_iter(),
+            start: target.map(|target| target.to_vec()),
+            read_bytes: None,
+        }
+    }
+}
+
+pub struct TableIter {
+    table: Arc<Table>,
+    // Los bloques que faltan leer (hacia atrás se consumen desde el final)
+    blocks: Range<usize>,
+    reverse: bool,
+    entries: std::vec::IntoIter<(Vec<u8>, Vec<u8>)>,
+    // Llave desde la que arrancamos: solo afecta al primer bloque que leemos
+    // (hacia adelante se incluye; hacia atrás es el tope y no se incluye)
+    start: Option<Vec<u8>>,
+    // Si alguien quiere seguir el avance (la compactación), acá sumamos los bytes de bloque leídos
+    read_bytes: Option<Arc<AtomicU64>>,
+}
+
+impl TableIter {
+    pub fn tracking(mut self, read_bytes: Arc<AtomicU64>) -> Self {
+        self.read_bytes = Some(read_bytes);
+        self
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<(Vec<u8>, Entry)>> {
+        loop {
+            if let Some((key, value)) = self.entries.next() {
+                return Ok(Some((key, Entry::decode(value)?)));
+            }
+            let next = if self.reverse {
+                self.blocks.next_back()
+            } else {
+                self.blocks.next()
+            };
+            let handle = match next {
+                Some(next) => &self.table.index[next],
+                None => return Ok(None),
+            };
+            let mut entries = self.table.read_block(handle)?.entries()?;
+            if let Some(start) = self.start.take() {
+                if self.reverse {
+                    entries.retain(|(key, _)| *key < start);
+                } else {
+                    entries.retain(|(key, _)| *key >= start);
+                }
+            }
+            if self.reverse {
+                entries.reverse();
+            }
+            self.entries = entries.into_iter();
+            if let Some(read_bytes) = &self.read_bytes {
+                read_bytes.fetch_add(handle.size as u64, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Iterator for TableIter {
+    type Item = io::Result<(Vec<u8>, Entry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.next_record();
+        if record.is_err() {
+            // Después de un error no seguimos leyendo basura
+            self.blocks = 0..0;
+            self.entries = Vec::new().into_iter();
+        }
+        record.transpose()
+    }
+}
+
+fn take<'a>(raw: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+    let end = pos
+        .checked_add(len)
+        .filter(|end| *end <= raw.len())
+        .ok_or_else(|| invalid("índice de SSTable truncado"))?;
+    let slice = &raw[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+fn read_u32(raw: &[u8], pos: &mut usize) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(raw, pos, 4)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::cache::BlockCache;
+    use super::super::entry::Entry;
+    use super::super::key;
+    use super::super::testing::TempDir;
+    use super::{Lookup, Table, TableBuilder, TableOptions};
+
+    // Con bloques chiquitos las versiones de una llave quedan repartidas entre varios; con uno
+    // grande, entre varios puntos de reinicio del mismo bloque
+    #[test]
+    fn lookup_tells_newer_versions_from_a_missing_key() {
+        for block_size in [64, 4096] {
+            check_lookup(block_size);
+        }
+    }
+
+    fn check_lookup(block_size: usize) {
+        let dir = TempDir::new("sstable_lookup");
+        let options = TableOptions {
+            block_size,
+            bloom_bits_per_key: 10,
+        };
+        // "b" tiene las versiones 11..=40, "d" la 50 sola; "a", "c" y "e" no están
+        let mut builder = TableBuilder::create(&dir, 1, &options).unwrap();
+        for seq in (11..=40).rev() {
+            let value = Entry::Value(format!("b{}", seq).into_bytes());
+            builder.add(&key::encode(b"b", seq, seq), &value).unwrap();
+        }
+        builder
+            .add(&key::encode(b"d", 50, 50), &Entry::Tombstone)
+            .unwrap();
+        let meta = builder.finish().unwrap();
+        let table = Table::open(&dir, meta, Arc::new(BlockCache::new(1 << 20))).unwrap();
+        assert_eq!(table.index.len() > 3, block_size == 64);
+
+        for seq in 0..=60 {
+            match table.lookup(b"b", seq).unwrap() {
+                Lookup::Found(internal, Entry::Value(value)) => {
+                    let expected = seq.min(40);
+                    assert!(seq >= 11);
+                    assert_eq!(key::seq(&internal), expected);
+                    assert_eq!(value, format!("b{}", expected).into_bytes());
+                }
+                Lookup::OnlyNewer => assert!(seq < 11, "seq {}", seq),
+                _ => panic!("seq {}: la tabla tiene versiones de b", seq),
+            }
+            let d = table.lookup(b"d", seq).unwrap();
+            assert_eq!(matches!(d, Lookup::OnlyNewer), seq < 50);
+            assert_eq!(matches!(d, Lookup::Found(_, Entry::Tombstone)), seq >= 50);
+            for missing in [&b"a"[..], b"c", b"e", b"bb"] {
+                assert!(matches!(
+                    table.lookup(missing, seq).unwrap(),
+                    Lookup::Absent
+                ));
+            }
+        }
+    }
+}
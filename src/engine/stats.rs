@@ -0,0 +1,42 @@
+// Los CONTADORES del motor (los reporta INFO).
+// Se actualizan desde lecturas que corren en paralelo bajo el candado de lectura,
+// así que son atómicos: nada de &mut acá.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Default)]
+pub struct Stats {
+    // El filtro de Bloom dijo "seguro no está" -> nos ahorramos una lectura de disco
+    pub bloom_useful: AtomicU64,
+    // El filtro dijo "quizás" pero la llave no estaba -> lectura de disco desperdiciada
+    pub bloom_false_positives: AtomicU64,
+    // Lápidas que la compactación tiró porque ya no tapaban a nadie
+    pub tombstones_dropped: AtomicU64,
+    // Versiones viejas que la compactación tiró porque ningún snapshot podía verlas
+    pub versions_dropped: AtomicU64,
+    // Lápidas de rango que la compactación tiró porque ya no quedaba nada que tapar
+    pub range_tombstones_dropped: AtomicU64,
+    // Operandos de MERGE que la compactación combinó (contra su base o entre ellos)
+    pub merge_operands_resolved: AtomicU64,
+    // Versiones que el filtro de compactación quitó / les cambió el valor
+    pub filter_removed: AtomicU64,
+    pub filter_replaced: AtomicU64,
+    // Llaves vencidas que el barrendero borró (las que se leen vencidas no cuentan)
+    pub expired_keys: AtomicU64,
+}
+
+impl Stats {
+    pub fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Tasa de falsos positivos observada entre las consultas por llaves AUSENTES
+    pub fn bloom_fp_rate(&self) -> f64 {
+        let useful = self.bloom_useful.load(Ordering::Relaxed);
+        let false_positives = self.bloom_false_positives.load(Ordering::Relaxed);
+        if useful + false_positives == 0 {
+            return 0.0;
+        }
+        false_positives as f64 / (useful + false_positives) as f64
+    }
+}
@@ -0,0 +1,115 @@
+// Ayudas para las PRUEBAS: carpetas temporales y motores sueltos (sin servidor ni keyspaces)
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::cache::BlockCache;
+use super::compaction::Leveled;
+use super::key;
+use super::shared_wal::SharedWal;
+use super::{now_millis, Durability, Engine, EngineOptions, RetentionPolicy};
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+// Una carpeta vacía propia de cada prueba (corren en paralelo); se borra al terminar
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!(
+            "chronos_test_{}_{}_{}",
+            std::process::id(),
+            name,
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Memtables y tablas de pocos KB (para que haya volcados y compactaciones con pocos datos) y
+// sin fsync en cada escritura (las pruebas no apagan la máquina)
+pub fn small_options() -> EngineOptions {
+    EngineOptions {
+        memtable_size: 4 * 1024,
+        target_file_size: 4 * 1024,
+        compaction_strategy: Box::new(Leveled::new(4, 16 * 1024)),
+        durability: Durability::OsManaged,
+        ..Default::default()
+    }
+}
+
+// El keyspace por defecto con su propio WAL, como lo abre Keyspaces::open. Soltar el motor sin
+// volcarlo es lo mismo que un corte: lo que estaba en la memtable vuelve desde el log.
+pub fn open(dir: &Path, options: EngineOptions) -> Engine {
+    let wal = SharedWal::open(dir, options.durability).unwrap();
+    let cache = Arc::new(BlockCache::new(options.block_cache_size));
+    let engine = Engine::open(dir.to_path_buf(), options, 0, Arc::clone(&wal), cache).unwrap();
+    wal.finish_recovery().unwrap();
+    engine
+}
+
+// Para probar la recolección: sin historia, lo pisado se tira en cuanto ningún snapshot lo
+// necesita (por defecto se guarda toda)
+pub fn without_history(engine: &mut Engine) {
+    let latest = RetentionPolicy {
+        max_age: None,
+        max_versions: Some(1),
+    };
+    engine.set_retention(b"", latest).unwrap();
+}
+
+// Vuelca y compacta hasta que no quede nada por bajar (todo termina en el último nivel)
+pub fn compact_all(engine: &mut Engine) {
+    engine.flush().unwrap();
+    while engine.compact_step(true).unwrap() {}
+}
+
+// Cuántas versiones de la llave hay guardadas de verdad (memtables + SSTables), lápidas incluidas
+pub fn stored_versions(engine: &Engine, user_key: &[u8]) -> usize {
+    let prefix = key::prefix(user_key);
+    let mut count = 0;
+    for memtable in std::iter::once(&engine.memtable).chain(&engine.immutable) {
+        count += memtable
+            .iter()
+            .filter(|(internal, _)| key::user_part(internal) == prefix.as_slice())
+            .count();
+    }
+    for table in engine.version.levels.iter().flatten() {
+        for record in table.iter() {
+            let (internal, _) = record.unwrap();
+            if key::user_part(&internal) == prefix.as_slice() {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+// Un instante que queda estrictamente entre la escritura anterior y la siguiente
+pub fn instant_between() -> u64 {
+    thread::sleep(Duration::from_millis(3));
+    let time = now_millis();
+    thread::sleep(Duration::from_millis(3));
+    time
+}
@@ -0,0 +1,183 @@
+// Las TRANSACCIONES interactivas (BEGIN / COMMIT / ROLLBACK)
+// BEGIN toma un snapshot: todas las lecturas de la transacción ven la base como estaba en ese
+// momento, más lo que la propia transacción escribió. Las escrituras no tocan el motor: quedan
+// en un buffer privado hasta el COMMIT.
+//
+// COMMIT, con el candado de escritura tomado, mira cada llave escrita: si alguien la cambió
+// después de nuestro BEGIN (su versión más nueva tiene una secuencia más alta que el snapshot),
+// la transacción se descarta entera y se avisa del conflicto. Gana el primero que confirma,
+// nadie pisa en silencio lo que no vio. Si no hay conflicto, el buffer entero se aplica como
+// un WriteBatch: un solo registro en el WAL, todo o nada.
+//
+// Es SNAPSHOT ISOLATION: solo chocan dos escrituras a la misma llave. Leer una llave que otro
+// cambió mientras tanto no es un conflicto.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use super::batch::WriteBatch;
+use super::entry::Entry;
+use super::snapshot::Snapshot;
+use super::{now_millis, Engine};
+
+pub struct Transaction {
+    snapshot: Snapshot,
+    // La última escritura de la transacción para cada llave
+    writes: BTreeMap<Vec<u8>, Entry>,
+}
+
+// Cómo terminó un COMMIT
+pub enum CommitOutcome {
+    // Secuencia de la última escritura (la que hay que esperar para el fsync)
+    Committed(u64),
+    // Esta llave cambió después del BEGIN: no se aplicó nada
+    Conflict(Vec<u8>),
+}
+
+impl Transaction {
+    pub(super) fn new(snapshot: Snapshot) -> Self {
+        Transaction {
+            snapshot,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    // La secuencia del BEGIN: una versión más nueva que esta en una llave escrita es un conflicto
+    pub(super) fn base(&self) -> u64 {
+        self.snapshot.sequence()
+    }
+
+    pub(super) fn written_keys(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.writes.keys()
+    }
+
+    pub(super) fn into_batch(self) -> WriteBatch {
+        let mut batch = WriteBatch::new();
+        for (key, entry) in self.writes {
+            batch.push(key, entry);
+        }
+        batch
+    }
+
+    pub fn get(&self, engine: &Engine, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        match self.writes.get(key) {
+            Some(entry) => Ok(entry.clone().value_at(now_millis())),
+            None => engine.get_snapshot(key, &self.snapshot),
+        }
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.writes
+            .insert(key.to_vec(), Entry::Value(value.to_vec()));
+    }
+
+    pub fn put_with_deadline(&mut self, key: &[u8], value: &[u8], deadline: u64) {
+        self.writes
+            .insert(key.to_vec(), Entry::Expiring(value.to_vec(), deadline));
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.writes.insert(key.to_vec(), Entry::Tombstone);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, TempDir};
+    use super::CommitOutcome;
+
+    #[test]
+    fn a_write_after_begin_is_a_conflict() {
+        let dir = TempDir::new("tx_conflict");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine.set(b"balance", b"100").unwrap();
+
+        let mut tx = engine.begin();
+        tx.put(b"balance", b"90");
+        tx.put(b"log", b"retiro");
+        // Otra conexión escribe la misma llave antes del COMMIT
+        engine.set(b"balance", b"150").unwrap();
+
+        match engine.commit(tx).unwrap() {
+            CommitOutcome::Conflict(key) => assert_eq!(key, b"balance"),
+            CommitOutcome::Committed(_) => panic!("tenía que chocar"),
+        }
+        // Nada de la transacción se aplicó
+        assert_eq!(engine.get(b"balance").unwrap(), Some(b"150".to_vec()));
+        assert_eq!(engine.get(b"log").unwrap(), None);
+    }
+
+    #[test]
+    fn a_delete_after_begin_is_a_conflict_too() {
+        let dir = TempDir::new("tx_delete");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine.set(b"k", b"v").unwrap();
+        let mut tx = engine.begin();
+        tx.put(b"k", b"mine");
+        engine.delete(b"k").unwrap();
+        assert!(matches!(
+            engine.commit(tx).unwrap(),
+            CommitOutcome::Conflict(_)
+        ));
+        assert_eq!(engine.get(b"k").unwrap(), None);
+    }
+
+    #[test]
+    fn the_first_to_commit_wins() {
+        let dir = TempDir::new("tx_first_wins");
+        let mut engine = testing::open(&dir, testing::small_options());
+        let mut first = engine.begin();
+        let mut second = engine.begin();
+        first.put(b"leader", b"a");
+        second.put(b"leader", b"b");
+
+        assert!(matches!(
+            engine.commit(first).unwrap(),
+            CommitOutcome::Committed(_)
+        ));
+        assert!(matches!(
+            engine.commit(second).unwrap(),
+            CommitOutcome::Conflict(_)
+        ));
+        assert_eq!(engine.get(b"leader").unwrap(), Some(b"a".to_vec()));
+    }
+
+    // Snapshot isolation: leer una llave que otro cambió no es un conflicto (solo chocan escrituras)
+    #[test]
+    fn reads_and_disjoint_writes_do_not_conflict() {
+        let dir = TempDir::new("tx_disjoint");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine.set(b"read", b"before").unwrap();
+        let mut tx = engine.begin();
+        assert_eq!(tx.get(&engine, b"read").unwrap(), Some(b"before".to_vec()));
+        engine.set(b"read", b"after").unwrap();
+        engine.set(b"other", b"x").unwrap();
+        // La transacción sigue viendo su foto, más lo que ella misma escribió
+        assert_eq!(tx.get(&engine, b"read").unwrap(), Some(b"before".to_vec()));
+        tx.put(b"mine", b"1");
+        assert_eq!(tx.get(&engine, b"mine").unwrap(), Some(b"1".to_vec()));
+
+        assert!(matches!(
+            engine.commit(tx).unwrap(),
+            CommitOutcome::Committed(_)
+        ));
+        assert_eq!(engine.get(b"mine").unwrap(), Some(b"1".to_vec()));
+    }
+
+    // El conflicto se detecta aunque la escritura rival ya haya bajado a una SSTable
+    #[test]
+    fn a_conflict_survives_a_flush() {
+        let dir = TempDir::new("tx_flush");
+        let mut engine = testing::open(&dir, testing::small_options());
+        engine.set(b"k", b"v1").unwrap();
+        let mut tx = engine.begin();
+        tx.put(b"k", b"tx");
+        engine.set(b"k", b"v2").unwrap();
+        testing::compact_all(&mut engine);
+        assert!(matches!(
+            engine.commit(tx).unwrap(),
+            CommitOutcome::Conflict(_)
+        ));
+        assert_eq!(engine.get(b"k").unwrap(), Some(b"v2".to_vec()));
+    }
+}
@@ -0,0 +1,345 @@
+// La VERSIÓN: la foto de qué SSTables están vivas y en qué nivel vive cada una.
+// Nunca se modifica "a mano": cada cambio (volcado, compactación) se describe como un
+// VersionEdit, se anota en el MANIFEST y recién después se aplica en memoria.
+//
+// Niveles (estilo LevelDB):
+//   L0      -> tablas recién volcadas desde la memtable. PUEDEN pisarse entre sí.
+//   L1..Ln  -> cada nivel es una partición ordenada: las tablas NO se pisan entre sí,
+//              y cada nivel puede crecer 10 veces más que el anterior.
+//
+// Los rangos de las tablas son de LLAVES INTERNAS, pero "pisarse" se decide por la llave del
+// usuario: dos tablas con versiones distintas de la misma llave se pisan aunque sus rangos internos no.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::cache::BlockCache;
+use super::entry::Entry;
+use super::key;
+use super::retention::RetentionPolicy;
+use super::scan::Source;
+use super::sstable::{Lookup, Table};
+use super::stats::Stats;
+
+pub const NUM_LEVELS: usize = 7;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileMeta {
+    pub id: u64,
+    pub size: u64,
+    pub smallest: Vec<u8>,
+    pub largest: Vec<u8>,
+    // Antigüedad de la corrida: en L0 manda la más alta (una tabla compactada hereda la de su entrada más nueva)
+    pub run: u64,
+    // Rango (en ms) de las escrituras que contiene: lo usa la estrategia por ventanas de tiempo
+    pub min_time: u64,
+    pub max_time: u64,
+    // Entradas con vencimiento (EXPIRE/SETEX) que hay adentro
+    pub expiring: u64,
+    // Lápidas de rango (DELRANGE/DELPREFIX) que hay adentro
+    pub range_tombstones: u64,
+}
+
+impl FileMeta {
+    // [smallest, largest] son llaves internas; se comparan solo sus llaves de usuario
+    pub fn overlaps(&self, smallest: &[u8], largest: &[u8]) -> bool {
+        key::user_part(&self.smallest) <= key::user_part(largest)
+            && key::user_part(&self.largest) >= key::user_part(smallest)
+    }
+}
+
+// Un cambio atómico sobre el conjunto de tablas vivas
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct VersionEdit {
+    pub new_files: Vec<(usize, FileMeta)>,
+    pub deleted_files: Vec<(usize, u64)>,
+    pub next_file_id: Option<u64>,
+    // Los logs con id MENOR a este ya fueron volcados a SSTables y se pueden borrar
+    pub log_id: Option<u64>,
+    // Último número de secuencia que ya quedó a salvo en una SSTable
+    pub last_sequence: Option<u64>,
+    // Las políticas de retención por prefijo (si viene, REEMPLAZA a todas las anteriores)
+    pub retention: Option<Vec<(Vec<u8>, RetentionPolicy)>>,
+}
+
+// Acumula los VersionEdit del MANIFEST (solo metadatos, sin abrir archivos todavía)
+pub struct VersionBuilder {
+    pub levels: Vec<BTreeMap<u64, FileMeta>>,
+    pub next_file_id: u64,
+    pub log_id: u64,
+    pub last_sequence: u64,
+    pub retention: Vec<(Vec<u8>, RetentionPolicy)>,
+}
+
+impl VersionBuilder {
+    pub fn new() -> Self {
+        VersionBuilder {
+            levels: vec![BTreeMap::new(); NUM_LEVELS],
+            next_file_id: 1,
+            log_id: 0,
+            last_sequence: 0,
+            retention: Vec::new(),
+        }
+    }
+
+    pub fn apply(&mut self, edit: &VersionEdit) {
+        for (level, id) in &edit.deleted_files {
+            self.levels[*level].remove(id);
+        }
+        for (level, meta) in &edit.new_files {
+            self.levels[*level].insert(meta.id, meta.clone());
+        }
+        if let Some(id) = edit.next_file_id {
+            self.next_file_id = self.next_file_id.max(id);
+        }
+        if let Some(id) = edit.log_id {
+            self.log_id = self.log_id.max(id);
+        }
+        if let Some(seq) = edit.last_sequence {
+            self.last_sequence = self.last_sequence.max(seq);
+        }
+        if let Some(rules) = &edit.retention {
+            self.retention = rules.clone();
+        }
+    }
+
+    // Un único edit que describe el estado completo (lo usamos para reescribir el MANIFEST)
+    pub fn snapshot(&self) -> VersionEdit {
+        let mut edit = VersionEdit {
+            next_file_id: Some(self.next_file_id),
+            log_id: Some(self.log_id),
+            last_sequence: Some(self.last_sequence),
+            retention: Some(self.retention.clone()),
+            ..Default::default()
+        };
+        for (level, files) in self.levels.iter().enumerate() {
+            for meta in files.values() {
+                edit.new_files.push((level, meta.clone()));
+            }
+        }
+        edit
+    }
+
+    pub fn open_version(&self, dir: &Path, cache: &Arc<BlockCache>) -> io::Result<Version> {
+        let mut version = Version::new();
+        for (level, files) in self.levels.iter().enumerate() {
+            for meta in files.values() {
+                let table = Table::open(dir, meta.clone(), Arc::clone(cache))?;
+                version.levels[level].push(Arc::new(table));
+            }
+        }
+        version.sort();
+        Ok(version)
+    }
+}
+
+#[derive(Clone)]
+pub struct Version {
+    // levels[0] ordenado de la corrida MÁS NUEVA a la más vieja; el resto por llave más chica
+    pub levels: Vec<Vec<Arc<Table>>>,
+}
+
+impl Version {
+    pub fn new() -> Self {
+        Version {
+            levels: vec![Vec::new(); NUM_LEVELS],
+        }
+    }
+
+    fn sort(&mut self) {
+        self.levels[0].sort_by_key(|t| Reverse((t.meta().run, t.meta().id)));
+        for level in self.levels.iter_mut().skip(1) {
+            level.sort_by(|a, b| a.meta().smallest.cmp(&b.meta().smallest));
+        }
+    }
+
+    // Aplica un edit cuyas tablas nuevas ya están abiertas (volcado o compactación en caliente)
+    pub fn apply(&self, edit: &VersionEdit, opened: &[Arc<Table>]) -> Version {
+        let mut next = self.clone();
+        for (level, id) in &edit.deleted_files {
+            next.levels[*level].retain(|t| t.meta().id != *id);
+        }
+        for (level, meta) in &edit.new_files {
+            let table = opened
+                .iter()
+                .find(|t| t.meta().id == meta.id)
+                .expect("tabla del edit sin abrir");
+            next.levels[*level].push(Arc::clone(table));
+        }
+        next.sort();
+        next
+    }
+
+    pub fn level_size(&self, level: usize) -> u64 {
+        self.levels[level].iter().map(|t| t.meta().size).sum()
+    }
+
+    // El momento de escritura más nuevo que hay en disco
+    pub fn max_time(&self) -> u64 {
+        self.levels
+            .iter()
+            .flatten()
+            .map(|t| t.meta().max_time)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.levels.iter().map(|l| l.len()).sum()
+    }
+
+    // Tablas de un nivel cuyo rango de llaves se pisa con [smallest, largest]
+    pub fn overlapping(&self, level: usize, smallest: &[u8], largest: &[u8]) -> Vec<Arc<Table>> {
+        self.levels[level]
+            .iter()
+            .filter(|t| t.meta().overlaps(smallest, largest))
+            .cloned()
+            .collect()
+    }
+
+    // La versión más nueva de la llave en disco con secuencia <= seq, con su llave interna
+    // (puede ser una lápida: ahí se corta la búsqueda)
+    pub fn get(
+        &self,
+        user_key: &[u8],
+        seq: u64,
+        stats: &Stats,
+    ) -> io::Result<Option<(Vec<u8>, Entry)>> {
+        let target = key::lookup(user_key, seq);
+        let user_part = key::user_part(&target);
+        // L0: las tablas se pisan, hay que mirarlas todas (de la más nueva a la más vieja)
+        for table in &self.levels[0] {
+            if table.meta().overlaps(&target, &target) {
+                if let Some(v) = Version::table_get(table, user_key, seq, user_part, stats)? {
+                    return Ok(Some(v));
+                }
+            }
+        }
+        // L1+: como mucho UNA tabla por nivel puede contener la llave -> búsqueda binaria
+        for level in &self.levels[1..] {
+            let pos = level.partition_point(|t| t.meta().largest < target);
+            if let Some(table) = level.get(pos) {
+                if table.meta().overlaps(&target, &target) {
+                    if let Some(v) = Version::table_get(table, user_key, seq, user_part, stats)? {
+                        return Ok(Some(v));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // Las versiones de UNA llave que hay en disco a partir de la llave interna `target`: hacia
+    // adelante (de la más nueva a la más vieja, desde `target` incluida) o hacia atrás (antes de
+    // `target`). Solo las tablas que la pueden tener.
+    pub fn versions_from(
+        &self,
+        user_key: &[u8],
+        target: &[u8],
+        backward: bool,
+    ) -> Vec<Source<'static>> {
+        let (newest, oldest) = (key::lookup(user_key, u64::MAX), key::lookup(user_key, 0));
+        let user_part = key::user_part(&newest);
+        let mut tables: Vec<&Arc<Table>> = self.levels[0].iter().collect();
+        // En L1+ una llave (con todas sus versiones) vive en una sola tabla por nivel
+        for level in &self.levels[1..] {
+            let pos = level.partition_point(|t| t.meta().largest < newest);
+            tables.extend(level.get(pos));
+        }
+        let mut sources: Vec<Source<'static>> = Vec::new();
+        for table in tables {
+            if !table.meta().overlaps(&newest, &oldest) || !table.may_contain(user_part) {
+                continue;
+            }
+            if backward {
+                sources.push(Box::new(table.iter_rev_before(Some(target))));
+            } else {
+                sources.push(Box::new(table.iter_from(target)));
+            }
+        }
+        sources
+    }
+
+    // Fuentes ordenadas para recorrer el disco desde `target`: cada tabla de L0 por separado
+    // (se pisan) y cada nivel L1+ como UNA sola fuente que abre sus tablas de a una, en orden
+    pub fn iter_from(&self, target: &[u8]) -> Vec<Source<'static>> {
+        let mut sources: Vec<Source<'static>> = Vec::new();
+        for table in &self.levels[0] {
+            if table.meta().largest.as_slice() >= target {
+                sources.push(Box::new(table.iter_from(target)));
+            }
+        }
+        for level in &self.levels[1..] {
+            let pos = level.partition_point(|t| t.meta().largest.as_slice() < target);
+            if pos == level.len() {
+                continue;
+            }
+            let tables = level[pos..].to_vec();
+            let target = target.to_vec();
+            sources.push(Box::new(
+                tables
+                    .into_iter()
+                    .flat_map(move |table| table.iter_from(&target)),
+            ));
+        }
+        sources
+    }
+
+    // Lo mismo pero HACIA ATRÁS: las llaves internas < target (None = desde la última)
+    pub fn iter_rev_before(&self, target: Option<&[u8]>) -> Vec<Source<'static>> {
+        let below =
+            |t: &Arc<Table>| target.is_none_or(|target| t.meta().smallest.as_slice() < target);
+        let mut sources: Vec<Source<'static>> = Vec::new();
+        for table in &self.levels[0] {
+            if below(table) {
+                sources.push(Box::new(table.iter_rev_before(target)));
+            }
+        }
+        for level in &self.levels[1..] {
+            let end = level.partition_point(below);
+            if end == 0 {
+                continue;
+            }
+            let tables = level[..end].to_vec();
+            let target = target.map(|target| target.to_vec());
+            sources.push(Box::new(
+                tables
+                    .into_iter()
+                    .rev()
+                    .flat_map(move |table| table.iter_rev_before(target.as_deref())),
+            ));
+        }
+        sources
+    }
+
+    // Antes de tocar el disco le preguntamos al filtro de Bloom de la tabla
+    fn table_get(
+        table: &Table,
+        user_key: &[u8],
+        seq: u64,
+        user_part: &[u8],
+        stats: &Stats,
+    ) -> io::Result<Option<(Vec<u8>, Entry)>> {
+        if !table.may_contain(user_part) {
+            Stats::bump(&stats.bloom_useful);
+            return Ok(None);
+        }
+        match table.lookup(user_key, seq)? {
+            Lookup::Found(internal, entry) => Ok(Some((internal, entry))),
+            // Si la tabla tiene versiones más nuevas que las que ve esta lectura, el filtro acertó:
+            // falso positivo es que no tenga NINGUNA
+            Lookup::OnlyNewer => Ok(None),
+            Lookup::Absent => {
+                if table.has_filter() {
+                    Stats::bump(&stats.bloom_false_positives);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+// El WAL (Write-Ahead Log): la caja negra del motor.
+// Cada escritura se anota acá ANTES de tocar la memtable. Si el proceso muere, al arrancar
+// releemos el log y reconstruimos la memtable.
+//
+// Antes era texto "key,value\n": una línea cortada por la mitad o un bit dado vuelta se
+// confundían con datos válidos. Ahora cada registro es binario y se autoverifica:
+//
+//   len u32 | crc32 u32 | payload
+//   payload = seq u64 | time u64 | n u32 | n x (klen u32 | key | elen u32 | entrada) | keyspace u32
+//   entrada: la misma codificación que en las SSTables (tipo + datos: valor, lápida, vencimiento...)
+//   time: momento de la escritura en ms (lo necesitan los viajes en el tiempo)
+//   keyspace: de qué keyspace es el lote (el log es uno solo para todos, ver shared_wal.rs). Los
+//             registros de antes de los keyspaces no lo traen: son del keyspace por defecto (0).
+//
+// Un registro es un LOTE: sus n escrituras llevan las secuencias seq, seq+1, ... y se recuperan
+// todas o ninguna (un SET suelto es un lote de una). Así MULTI/EXEC nunca queda a medias.
+//
+// `len` es el largo del payload y `crc32` su checksum. Al recuperar, el primer registro
+// incompleto o con checksum inválido marca el final del log: se trunca ahí y se informa.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::entry::Entry;
+
+const HEADER_SIZE: usize = 8;
+
+pub fn log_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:06}.log", id))
+}
+
+pub struct Record {
+    // Secuencia de la primera escritura del lote
+    pub seq: u64,
+    pub time: u64,
+    pub keyspace: u32,
+    pub writes: Vec<(Vec<u8>, Entry)>,
+}
+
+// ---------------------------------------------------------------------------------
+// CRC32 (IEEE, el mismo de zip/ethernet), con la tabla armada en tiempo de compilación
+// ---------------------------------------------------------------------------------
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// ---------------------------------------------------------------------------------
+// ESCRITURA
+// ---------------------------------------------------------------------------------
+
+pub struct WalWriter {
+    file: File,
+}
+
+impl WalWriter {
+    pub fn open(dir: &Path, id: u64) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path(dir, id))?;
+        Ok(WalWriter { file })
+    }
+
+    pub fn append(&mut self, record: &Record) -> io::Result<()> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&record.seq.to_le_bytes());
+        payload.extend_from_slice(&record.time.to_le_bytes());
+        payload.extend_from_slice(&(record.writes.len() as u32).to_le_bytes());
+        for (key, entry) in &record.writes {
+            let entry = entry.encode();
+            payload.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            payload.extend_from_slice(key);
+            payload.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+            payload.extend_from_slice(&entry);
+        }
+        payload.extend_from_slice(&record.keyspace.to_le_bytes());
+
+        // Header + payload en UN solo write: así un corte deja a lo sumo un registro a medias al final
+        let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32(&payload).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        self.file.write_all(&frame)
+    }
+
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    // Otro descriptor del mismo archivo: el group commit hace fsync sin tocar el del motor
+    pub fn try_clone_file(&self) -> io::Result<File> {
+        self.file.try_clone()
+    }
+}
+
+// ---------------------------------------------------------------------------------
+// RECUPERACIÓN
+// ---------------------------------------------------------------------------------
+
+#[derive(Default, Clone, Copy)]
+pub struct RecoveryReport {
+    pub recovered: u64,
+    // Registros tirados a partir del primero roto (incluido él)
+    pub discarded: u64,
+    pub truncated_bytes: u64,
+}
+
+impl RecoveryReport {
+    pub fn merge(&mut self, other: RecoveryReport) {
+        self.recovered += other.recovered;
+        self.discarded += other.discarded;
+        self.truncated_bytes += other.truncated_bytes;
+    }
+}
+
+fn decode_payload(payload: &[u8]) -> Option<Record> {
+    let seq = u64::from_le_bytes(payload.get(0..8)?.try_into().ok()?);
+    let time = u64::from_le_bytes(payload.get(8..16)?.try_into().ok()?);
+    let count = u32::from_le_bytes(payload.get(16..20)?.try_into().ok()?);
+    let mut pos = 20;
+    let mut writes = Vec::new();
+    for _ in 0..count {
+        let key = chunk(payload, &mut pos)?.to_vec();
+        let entry = Entry::decode(chunk(payload, &mut pos)?.to_vec()).ok()?;
+        writes.push((key, entry));
+    }
+    // Todo el payload tiene que ser el lote (+ el keyspace): bytes de más = registro corrupto
+    let keyspace = match payload.len() - pos {
+        0 => 0,
+        4 => u32::from_le_bytes(payload[pos..].try_into().ok()?),
+        _ => return None,
+    };
+    Some(Record {
+        seq,
+        time,
+        keyspace,
+        writes,
+    })
+}
+
+// len u32 | datos
+fn chunk<'a>(payload: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let len = u32::from_le_bytes(payload.get(*pos..*pos + 4)?.try_into().ok()?) as usize;
+    let data = payload.get(*pos + 4..(*pos + 4).checked_add(len)?)?;
+    *pos += 4 + len;
+    Some(data)
+}
+
+// Intenta leer el registro en `pos`: Some((registro, próxima posición)) si está sano
+fn read_record(raw: &[u8], pos: usize) -> Option<(Record, usize)> {
+    let header = raw.get(pos..pos + HEADER_SIZE)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let payload = raw.get(pos + HEADER_SIZE..pos + HEADER_SIZE + len)?;
+    if crc32(payload) != crc {
+        return None;
+    }
+    Some((decode_payload(payload)?, pos + HEADER_SIZE + len))
+}
+
+// Relee un log entero llamando a `apply` por cada registro sano, en orden.
+// Al primer registro roto se detiene y TRUNCA el archivo ahí: lo que sigue no es confiable.
+pub fn replay(path: &Path, mut apply: impl FnMut(Record)) -> io::Result<RecoveryReport> {
+    let mut raw = Vec::new();
+    File::open(path)?.read_to_end(&mut raw)?;
+
+    let mut report = RecoveryReport::default();
+    let mut pos = 0;
+    while pos < raw.len() {
+        match read_record(&raw, pos) {
+            Some((record, next)) => {
+                apply(record);
+                report.recovered += 1;
+                pos = next;
+            }
+            None => break,
+        }
+    }
+    if pos == raw.len() {
+        return Ok(report);
+    }
+
+    // Contamos cuántos registros perdemos: saltamos por los largos mientras tengan sentido
+    let mut skip = pos;
+    while skip < raw.len() {
+        report.discarded += 1;
+        let len = match raw.get(skip..skip + 4) {
+            Some(len) => u32::from_le_bytes(len.try_into().unwrap()) as usize,
+            None => break,
+        };
+        skip = skip.saturating_add(HEADER_SIZE + len);
+    }
+    report.truncated_bytes = (raw.len() - pos) as u64;
+
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(pos as u64)?;
+    file.sync_all()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+
+    use super::super::entry::Entry;
+    use super::super::testing::TempDir;
+    use super::{log_path, replay, Record, WalWriter, HEADER_SIZE};
+
+    // Cinco lotes de una escritura (seq 1..=5) y dónde empieza cada uno en el archivo
+    fn write_log(dir: &TempDir) -> Vec<u64> {
+        let mut writer = WalWriter::open(dir, 1).unwrap();
+        let mut offsets = Vec::new();
+        for seq in 1..=5u64 {
+            offsets.push(fs::metadata(log_path(dir, 1)).unwrap().len());
+            let record = Record {
+                seq,
+                time: 1000 + seq,
+                keyspace: 0,
+                writes: vec![(
+                    format!("key{}", seq).into_bytes(),
+                    Entry::Value(format!("value{}", seq).into_bytes()),
+                )],
+            };
+            writer.append(&record).unwrap();
+        }
+        offsets
+    }
+
+    fn replay_seqs(dir: &TempDir) -> (Vec<u64>, super::RecoveryReport) {
+        let mut seqs = Vec::new();
+        let report = replay(&log_path(dir, 1), |record| seqs.push(record.seq)).unwrap();
+        (seqs, report)
+    }
+
+    #[test]
+    fn a_torn_tail_is_truncated() {
+        let dir = TempDir::new("wal_torn");
+        let offsets = write_log(&dir);
+        let path = log_path(&dir, 1);
+        let full = fs::metadata(&path).unwrap().len();
+        // El último registro quedó a medias: solo su header y dos bytes del payload
+        let cut = offsets[4] + HEADER_SIZE as u64 + 2;
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(cut)
+            .unwrap();
+        assert!(cut < full);
+
+        let (seqs, report) = replay_seqs(&dir);
+        assert_eq!(seqs, vec![1, 2, 3, 4]);
+        assert_eq!(report.recovered, 4);
+        assert_eq!(report.discarded, 1);
+        assert_eq!(report.truncated_bytes, cut - offsets[4]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), offsets[4]);
+
+        // Ya truncado, la próxima vez se relee limpio
+        let (seqs, report) = replay_seqs(&dir);
+        assert_eq!(seqs, vec![1, 2, 3, 4]);
+        assert_eq!(report.discarded, 0);
+        assert_eq!(report.truncated_bytes, 0);
+    }
+
+    #[test]
+    fn a_flipped_bit_discards_from_that_record_on() {
+        let dir = TempDir::new("wal_bitflip");
+        let offsets = write_log(&dir);
+        let path = log_path(&dir, 1);
+        let full = fs::metadata(&path).unwrap().len();
+        // Un bit dado vuelta en el payload del tercer registro: el CRC no cierra
+        let mut raw = fs::read(&path).unwrap();
+        let pos = offsets[2] as usize + HEADER_SIZE + 10;
+        raw[pos] ^= 0x04;
+        fs::write(&path, &raw).unwrap();
+
+        let (seqs, report) = replay_seqs(&dir);
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(report.recovered, 2);
+        // El roto y los dos sanos que venían detrás (no se puede confiar en nada después del corte)
+        assert_eq!(report.discarded, 3);
+        assert_eq!(report.truncated_bytes, full - offsets[2]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), offsets[2]);
+    }
+
+    #[test]
+    fn a_corrupt_length_does_not_run_past_the_end() {
+        let dir = TempDir::new("wal_length");
+        let offsets = write_log(&dir);
+        let path = log_path(&dir, 1);
+        let full = fs::metadata(&path).unwrap().len();
+        let mut raw = fs::read(&path).unwrap();
+        let pos = offsets[1] as usize;
+        raw[pos..pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &raw).unwrap();
+
+        let (seqs, report) = replay_seqs(&dir);
+        assert_eq!(seqs, vec![1]);
+        assert_eq!(report.discarded, 1);
+        assert_eq!(report.truncated_bytes, full - offsets[1]);
+    }
+
+    #[test]
+    fn records_without_keyspace_belong_to_the_default_one() {
+        let dir = TempDir::new("wal_legacy");
+        let mut writer = WalWriter::open(&dir, 1).unwrap();
+        writer
+            .append(&Record {
+                seq: 1,
+                time: 1,
+                keyspace: 7,
+                writes: vec![(b"k".to_vec(), Entry::Tombstone)],
+            })
+            .unwrap();
+        drop(writer);
+        // Sacamos los 4 bytes del keyspace y recalculamos el largo y el CRC, como un log viejo
+        let raw = fs::read(log_path(&dir, 1)).unwrap();
+        let payload = &raw[HEADER_SIZE..raw.len() - 4];
+        let mut legacy = (payload.len() as u32).to_le_bytes().to_vec();
+        legacy.extend_from_slice(&super::crc32(payload).to_le_bytes());
+        legacy.extend_from_slice(payload);
+        fs::write(log_path(&dir, 1), legacy).unwrap();
+
+        let mut keyspaces = Vec::new();
+        let report = replay(&log_path(&dir, 1), |record| keyspaces.push(record.keyspace)).unwrap();
+        assert_eq!(keyspaces, vec![0]);
+        assert_eq!(report.discarded, 0);
+    }
+}
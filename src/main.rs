@@ -1,46 +1,122 @@
-// EL CORAZON (El punto de ENTRADA)
-// Main.rs será pequeñito, limpio y elegante. El único trabajo es unir el motor y el servidor.
-
-// Le decimos a Rust que busque los otros dos archivos
-
-mod engine;
-mod parser;
-mod server; // <- AVISAMSO QUE HAY UN PARSER
-
-use engine::{Engine, DB_PATH};
-use std::process;
-use std::sync::{Arc, RwLock};
-
-fn main() {
-    println!("⏳ Iniciando Chronos DB...");
-
-    // 1. Instanciamos el Motor
-    let engine = Engine::new(DB_PATH).expect("Fallo crítico al iniciar la DB");
-
-    // 2. Lo envolvemos en nuestra barrera de hilos
-    let global_db = Arc::new(RwLock::new(engine));
-
-    // -- 🚨 PROTOCOLO DE APAGADO ELEGANTE (NUEVO) --
-    // Clonamos la referencia de la DB específicamente para el vigilante
-    let db_for_shutdown = Arc::clone(&global_db);
-
-    ctrlc::set_handler(move || {
-        println!("\n\n⚠️ SEÑAL DE INTERRUPCIÓN DETECTADA (Ctrl+C)");
-        println!("💾 Activando protocolo de guardado de emergencia...");
-
-        // 1. Tomamos el control absoluto (Escritura) para que nadie más modifique datos
-        let mut db = db_for_shutdown.write().unwrap();
-
-        // 2. Obligamos al motor a guardar/compactar todo en el disco de forma segura
-        let _ = db.compact();
-
-        println!("🛑 Memoria asegurada. Servidor Chronos apagado correctamente.¡Hasta la proxima, Arquitecto!");
-
-        // 3. Salimos del programa con código 0 (Éxito)
-        process::exit(0);
-    }).expect("Error al inicializar el escudo SIGINT");
-    // -------------------------------------------------
-
-    // 3. Arrancamos el Servidor TCP
-    server::start_server(global_db);
-}
+// EL CORAZON (El punto de ENTRADA)
+// Main.rs será pequeñito, limpio y elegante. El único trabajo es unir el motor y el servidor.
+
+// Le decimos a Rust que busque los otros dos archivos
+
+mod engine;
+mod parser;
+mod server; // <- AVISAMSO QUE HAY UN PARSER
+
+use engine::{Durability, EngineOptions, Keyspaces, PrefixFilter, DB_PATH};
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+fn main() {
+    println!("⏳ Iniciando Chronos DB...");
+
+    // 1. Instanciamos el Motor. Sin argumentos usa la configuración por defecto (compactación leveled,
+    //    fsync en cada escritura). Se puede elegir otra estrategia y otra política de durabilidad:
+    //    `cargo run -- size-tiered --durability every-100ms` (o `--durability os-managed`).
+    //    Para usar MERGE hay que elegir el operador: `--merge-operator add` (o max, append).
+    //    `--purge-prefix tenant:42:` / `--redact-prefix email:` (se pueden repetir) le dan reglas
+    //    al filtro de compactación: esas llaves se borran / anonimizan a medida que se compacta.
+    //    `--history-retention 7d` guarda esa ventana de historia para GETAT/HISTORY (sin él, la
+    //    compactación solo se queda con la versión más nueva de cada llave).
+    //    Todo esto configura el keyspace por defecto (y la durabilidad, el WAL compartido): los que se
+    //    crean con KEYSPACE CREATE traen su propia configuración.
+    let mut args = env::args().skip(1);
+    let mut strategy_name = None;
+    let mut durability_name = None;
+    let mut merge_operator_name = None;
+    let mut history_retention = None;
+    let mut prefix_filter = PrefixFilter::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--durability" => durability_name = args.next(),
+            "--merge-operator" => merge_operator_name = args.next(),
+            "--history-retention" => history_retention = args.next(),
+            "--purge-prefix" => prefix_filter
+                .purge
+                .extend(args.next().map(String::into_bytes)),
+            "--redact-prefix" => prefix_filter
+                .redact
+                .extend(args.next().map(String::into_bytes)),
+            _ => strategy_name = Some(arg),
+        }
+    }
+
+    let mut options = EngineOptions::default();
+    if let Some(strategy_name) = strategy_name {
+        options.compaction_strategy = engine::strategy_from_name(&strategy_name)
+            .unwrap_or_else(|| {
+                eprintln!(
+                    "❌ Estrategia de compactación desconocida: '{}' (usar leveled, size-tiered o time-window)",
+                    strategy_name
+                );
+                process::exit(1);
+            });
+    }
+    if let Some(durability_name) = durability_name {
+        options.durability = Durability::from_name(&durability_name).unwrap_or_else(|| {
+            eprintln!(
+                "❌ Política de durabilidad desconocida: '{}' (usar always, every-<N>ms u os-managed)",
+                durability_name
+            );
+            process::exit(1);
+        });
+    }
+    if let Some(merge_operator_name) = merge_operator_name {
+        options.merge_operator = Some(
+            engine::merge_operator_from_name(&merge_operator_name).unwrap_or_else(|| {
+                eprintln!(
+                    "❌ Operador de merge desconocido: '{}' (usar add, max o append)",
+                    merge_operator_name
+                );
+                process::exit(1);
+            }),
+        );
+    }
+    if let Some(history_retention) = history_retention {
+        options.history_retention = Some(
+            parser::duration(history_retention.as_bytes()).unwrap_or_else(|| {
+                eprintln!(
+                    "❌ Ventana de historia inválida: '{}' (usar 500ms, 30s, 5m, 2h, 7d)",
+                    history_retention
+                );
+                process::exit(1);
+            }),
+        );
+    }
+    options.compaction_filter = prefix_filter.into_filter();
+
+    // 2. Abrimos todos los keyspaces (el de por defecto + los creados con KEYSPACE CREATE). Cada
+    //    uno queda en su barrera de hilos, con su compactador y su barrendero de llaves vencidas
+    //    en hilos propios (solo toman el candado un instante / de a tandas cortas).
+    let keyspaces =
+        Arc::new(Keyspaces::open(DB_PATH, options).expect("Fallo crítico al iniciar la DB"));
+
+    // -- 🚨 PROTOCOLO DE APAGADO ELEGANTE (NUEVO) --
+    // Clonamos la referencia de la DB específicamente para el vigilante
+    let db_for_shutdown = Arc::clone(&keyspaces);
+
+    ctrlc::set_handler(move || {
+        println!("\n\n⚠️ SEÑAL DE INTERRUPCIÓN DETECTADA (Ctrl+C)");
+        println!("💾 Activando protocolo de guardado de emergencia...");
+
+        // 1 y 2. Keyspace por keyspace, tomamos el control absoluto (Escritura) y obligamos al
+        //    motor a volcar la memtable a una SSTable en el disco de forma segura
+        //    (solo el volcado: una compactación de fondo a medias no importa, sus tablas sin
+        //    instalar se limpian al reabrir y el MANIFEST sigue apuntando a las viejas)
+        let _ = db_for_shutdown.flush_all();
+
+        println!("🛑 Memoria asegurada. Servidor Chronos apagado correctamente.¡Hasta la proxima, Arquitecto!");
+
+        // 3. Salimos del programa con código 0 (Éxito)
+        process::exit(0);
+    }).expect("Error al inicializar el escudo SIGINT");
+    // -------------------------------------------------
+
+    // 3. Arrancamos el Servidor TCP
+    server::start_server(keyspaces);
+}
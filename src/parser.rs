@@ -1,23 +1,780 @@
+// El TRADUCTOR (Parser del protocolo de red)
+// Convierte los bytes crudos que llegan por TCP en un 'Command' tipado.
+//
+// Hablamos DOS dialectos (igual que Redis):
+//   - INLINE: una línea de texto, "SET user:1 Baltasar\n". Cómodo para netcat/telnet y el bench.
+//     Se puede entrecomillar: SET k "hola mundo\n\xff" (escapes \n \r \t \\ \" y \xHH).
+//   - RESP:   *3\r\n$3\r\nSET\r\n$6\r\nuser:1\r\n$8\r\nBaltasar\r\n
+//     Cada argumento viaja con su largo adelante: sirve para bytes ARBITRARIOS (comas,
+//     saltos de línea, binarios que no son UTF-8) sin ningún escape.
+//
+// TCP es un chorro de bytes, no de mensajes: un pedido puede llegar partido en varios `read`
+// o varios pedidos juntos en uno solo. Por eso `read_request` trabaja sobre un buffer acumulado
+// y avisa cuántos bytes consumió (o que todavía falta).
+
+use std::time::Duration;
+
 // Definimos todos los comandos válidos en Chronos
 pub enum Command {
-    Set(String, String), // SET requiere una llave y un valor
-    Get(String),         // GET requiere solo una llave
-    Del(String),
+    Set(Vec<u8>, Vec<u8>), // SET requiere una llave y un valor
+    Get(Vec<u8>),          // GET requiere solo una llave
+    MGet(Vec<Vec<u8>>),    // Varias llaves leídas desde la MISMA foto (snapshot)
+    GetAt(Vec<u8>, u64),   // Viaje en el tiempo: el valor de la llave en ese instante (ms)
+    // Las versiones escritas entre start y end (ms), de a páginas
+    History {
+        key: Vec<u8>,
+        start: u64,
+        end: u64,
+        limit: usize,
+        reverse: bool,
+        cursor: Option<u64>,
+    },
+    // Las llaves vivas en orden, de a páginas (cursor None = desde la punta; REVSCAN = al revés)
+    Scan {
+        cursor: Option<Vec<u8>>,
+        prefix: Vec<u8>,
+        start: Vec<u8>,
+        end: Option<Vec<u8>>,
+        count: usize,
+        reverse: bool,
+    },
+    Del(Vec<u8>),
+    // Borrados por rango con UNA escritura: [start, end) y todo lo que empieza con un prefijo
+    DelRange(Vec<u8>, Vec<u8>),
+    DelPrefix(Vec<u8>),
+    // Escrituras condicionales: solo si la llave no existe / sigue en esa versión / con ese valor
+    SetNx(Vec<u8>, Vec<u8>),
+    SetIfVersion(Vec<u8>, Vec<u8>, u64),
+    Cas(Vec<u8>, Vec<u8>, Vec<u8>),
+    Version(Vec<u8>),
+    // Leer-modificar-escribir en el motor, sin carreras (INCR/DECR/INCRBY/DECRBY van todos acá)
+    IncrBy(Vec<u8>, i64),
+    IncrByFloat(Vec<u8>, f64),
+    Append(Vec<u8>, Vec<u8>),
+    GetSet(Vec<u8>, Vec<u8>),
+    // Anota un operando sin leer nada: el operador del motor lo combina al leer
+    Merge(Vec<u8>, Vec<u8>),
+    // Vencimientos, con la semántica de Redis (los segundos pueden ser negativos: borra ya)
+    Expire(Vec<u8>, i64),
+    SetEx(Vec<u8>, i64, Vec<u8>),
+    Ttl(Vec<u8>),
+    Persist(Vec<u8>),
+    Retention(RetentionCommand),
+    // Elige el keyspace de la conexión (SELECT y USE son lo mismo; por nombre o por número)
+    Select(String),
+    Keyspace(KeyspaceCommand),
+    // Lote atómico: los SET/SETEX/DEL entre MULTI y EXEC se aplican todos juntos (o ninguno)
+    Multi,
+    Exec,
+    Discard,
+    // Transacción interactiva: lee desde una foto, escribe en privado y confirma si no hubo choque
+    Begin,
+    Commit,
+    Rollback,
     Compact,
+    Info, // Comando de administración: estado interno del motor
     Ping,
+    Invalid(String), // Comando conocido con argumentos que no tienen sentido
     Unknown,
 }
 
-// Esta función toma el texto sucio de la red y lo convierte en un 'Command'
-pub fn parse(input: &str) -> Command {
-    let parts: Vec<&str> = input.trim().split_whitespace().collect();
+// RETENTION LIST | GET key | SET prefix [MAXAGE 7d] [MAXVERSIONS 10] | SET prefix FOREVER | DEL prefix
+pub enum RetentionCommand {
+    List,
+    Get(Vec<u8>),
+    Set {
+        prefix: Vec<u8>,
+        max_age: Option<Duration>,
+        max_versions: Option<u64>,
+    },
+    Del(Vec<u8>),
+}
+
+// KEYSPACE LIST | DROP name | CREATE name [COMPACTION leveled] [MEMTABLE 1048576] [MERGE add] [HISTORY 7d]
+pub enum KeyspaceCommand {
+    List,
+    Create {
+        name: String,
+        compaction: Option<String>,
+        memtable_size: Option<usize>,
+        merge_operator: Option<String>,
+        history_retention: Option<Duration>,
+    },
+    Drop(String),
+}
+
+// En qué dialecto llegó el pedido: la respuesta se manda en el mismo
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Inline,
+    Resp,
+}
+
+pub struct Request {
+    pub protocol: Protocol,
+    pub args: Vec<Vec<u8>>,
+}
+
+// Límites para que un cliente roto (o malicioso) no nos haga reservar memoria infinita
+const MAX_INLINE_LEN: usize = 64 * 1024;
+const MAX_ARGS: usize = 1024 * 1024;
+const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+// Busca un pedido completo al principio de `buf`.
+//   Ok(Some((pedido, bytes consumidos)))  -> listo para ejecutar
+//   Ok(None)                              -> todavía no llegó entero, hay que leer más
+//   Err(motivo)                           -> el cliente habla cualquier cosa: cortamos la conexión
+pub fn read_request(buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+    match buf.first() {
+        None => Ok(None),
+        Some(b'*') => read_resp(buf),
+        Some(_) => read_inline(buf),
+    }
+}
+
+// Lee un número terminado en \r\n a partir de `pos` (el largo de un arreglo o de un argumento)
+fn read_number(buf: &[u8], pos: usize) -> Result<Option<(i64, usize)>, String> {
+    let end = match buf[pos..].windows(2).position(|w| w == b"\r\n") {
+        Some(end) => pos + end,
+        None if buf.len() - pos > 32 => return Err("largo RESP inválido".to_string()),
+        None => return Ok(None),
+    };
+    let number = std::str::from_utf8(&buf[pos..end])
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or_else(|| "largo RESP inválido".to_string())?;
+    Ok(Some((number, end + 2)))
+}
+
+fn read_resp(buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+    let (count, mut pos) = match read_number(buf, 1)? {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+    if count < 0 || count as usize > MAX_ARGS {
+        return Err("cantidad de argumentos RESP inválida".to_string());
+    }
+
+    let mut args = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        match buf.get(pos) {
+            None => return Ok(None),
+            Some(b'$') => {}
+            Some(_) => return Err("se esperaba '$' en el pedido RESP".to_string()),
+        }
+        let (len, start) = match read_number(buf, pos + 1)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        if len < 0 || len as usize > MAX_BULK_LEN {
+            return Err("largo de argumento RESP inválido".to_string());
+        }
+        let end = start + len as usize;
+        if buf.len() < end + 2 {
+            return Ok(None);
+        }
+        if &buf[end..end + 2] != b"\r\n" {
+            return Err("argumento RESP sin \\r\\n final".to_string());
+        }
+        args.push(buf[start..end].to_vec());
+        pos = end + 2;
+    }
+    let request = Request {
+        protocol: Protocol::Resp,
+        args,
+    };
+    Ok(Some((request, pos)))
+}
+
+fn read_inline(buf: &[u8]) -> Result<Option<(Request, usize)>, String> {
+    let end = match buf.iter().position(|b| *b == b'\n') {
+        Some(end) => end,
+        None if buf.len() > MAX_INLINE_LEN => return Err("línea demasiado larga".to_string()),
+        None => return Ok(None),
+    };
+    let line = buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]);
+    let request = Request {
+        protocol: Protocol::Inline,
+        args: split_inline(line)?,
+    };
+    Ok(Some((request, end + 1)))
+}
+
+// Separa una línea inline en argumentos, respetando comillas dobles (con escapes) y simples (literales)
+fn split_inline(line: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut args = Vec::new();
+    let mut pos = 0;
+    loop {
+        while pos < line.len() && line[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        match line[pos] {
+            b'"' => {
+                pos += 1;
+                loop {
+                    match line.get(pos) {
+                        None => return Err("comillas sin cerrar".to_string()),
+                        Some(b'"') => break,
+                        Some(b'\\') => {
+                            let (byte, used) = unescape(&line[pos + 1..])?;
+                            arg.push(byte);
+                            pos += 1 + used;
+                        }
+                        Some(byte) => {
+                            arg.push(*byte);
+                            pos += 1;
+                        }
+                    }
+                }
+                pos += 1;
+            }
+            b'\'' => {
+                let len = line[pos + 1..]
+                    .iter()
+                    .position(|b| *b == b'\'')
+                    .ok_or_else(|| "comillas sin cerrar".to_string())?;
+                arg.extend_from_slice(&line[pos + 1..pos + 1 + len]);
+                pos += len + 2;
+            }
+            _ => {
+                while pos < line.len() && !line[pos].is_ascii_whitespace() {
+                    arg.push(line[pos]);
+                    pos += 1;
+                }
+                args.push(arg);
+                continue;
+            }
+        }
+        // Después de cerrar comillas tiene que venir un espacio (o el final)
+        if pos < line.len() && !line[pos].is_ascii_whitespace() {
+            return Err("se esperaba un espacio después de las comillas".to_string());
+        }
+        args.push(arg);
+    }
+}
+
+// Decodifica lo que sigue a una '\': devuelve (byte, cuántos bytes se usaron)
+fn unescape(rest: &[u8]) -> Result<(u8, usize), String> {
+    match rest.first() {
+        Some(b'n') => Ok((b'\n', 1)),
+        Some(b'r') => Ok((b'\r', 1)),
+        Some(b't') => Ok((b'\t', 1)),
+        Some(b'x') => rest
+            .get(1..3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .map(|byte| (byte, 3))
+            .ok_or_else(|| "escape \\x inválido".to_string()),
+        Some(byte) => Ok((*byte, 1)),
+        None => Err("escape incompleto".to_string()),
+    }
+}
+
+// Convierte los argumentos de un pedido en un 'Command'
+pub fn parse(args: &[Vec<u8>], protocol: Protocol) -> Command {
+    // Un pedido vacío no es un comando (y no queremos que args[0] tumbe el hilo)
+    let first = match args.first() {
+        Some(first) => first.to_ascii_uppercase(),
+        None => return Command::Unknown,
+    };
 
-    match parts[0].to_uppercase().as_str() {
-        "SET" if parts.len() >= 3 => Command::Set(parts[1].to_string(), parts[2..].join(" ")),
-        "GET" if parts.len() == 2 => Command::Get(parts[1].to_string()),
-        "DEL" if parts.len() == 2 => Command::Del(parts[1].to_string()), // <- NUEVO RECONOCIMIENTO
-        "PING" => Command::Ping,
-        "COMPACT" => Command::Compact,
+    match first.as_slice() {
+        b"SET" => set(args, protocol),
+        b"GET" if args.len() == 2 => Command::Get(args[1].clone()),
+        // GETAT k 1718000000000  ==  GET k AS OF 1718000000000
+        b"GETAT" if args.len() == 3 => get_at(&args[1], &args[2]),
+        b"GET"
+            if args.len() == 5
+                && args[2].eq_ignore_ascii_case(b"AS")
+                && args[3].eq_ignore_ascii_case(b"OF") =>
+        {
+            get_at(&args[1], &args[4])
+        }
+        b"HISTORY" if args.len() >= 4 => history(args),
+        b"SCAN" if args.len() >= 2 => scan(args, false),
+        b"REVSCAN" if args.len() >= 2 => scan(args, true),
+        b"MGET" if args.len() >= 2 => Command::MGet(args[1..].to_vec()),
+        b"SETNX" => with_value(args, 2, protocol, Command::SetNx),
+        // CAS k esperado nuevo (con espacios: entre comillas o por RESP)
+        b"CAS" if args.len() == 4 => {
+            Command::Cas(args[1].clone(), args[2].clone(), args[3].clone())
+        }
+        b"INCR" if args.len() == 2 => Command::IncrBy(args[1].clone(), 1),
+        b"DECR" if args.len() == 2 => Command::IncrBy(args[1].clone(), -1),
+        b"INCRBY" if args.len() == 3 => match integer(&args[2]) {
+            Some(delta) => Command::IncrBy(args[1].clone(), delta),
+            None => Command::Invalid("el valor no es un entero o está fuera de rango".to_string()),
+        },
+        b"DECRBY" if args.len() == 3 => match integer(&args[2]).and_then(i64::checked_neg) {
+            Some(delta) => Command::IncrBy(args[1].clone(), delta),
+            None => Command::Invalid("el valor no es un entero o está fuera de rango".to_string()),
+        },
+        b"INCRBYFLOAT" if args.len() == 3 => match float(&args[2]) {
+            Some(delta) => Command::IncrByFloat(args[1].clone(), delta),
+            None => Command::Invalid("el valor no es un float válido".to_string()),
+        },
+        b"APPEND" => with_value(args, 2, protocol, Command::Append),
+        b"GETSET" => with_value(args, 2, protocol, Command::GetSet),
+        b"MERGE" => with_value(args, 2, protocol, Command::Merge),
+        b"VERSION" if args.len() == 2 => Command::Version(args[1].clone()),
+        b"DEL" if args.len() == 2 => Command::Del(args[1].clone()), // <- NUEVO RECONOCIMIENTO
+        b"DELRANGE" if args.len() == 3 => Command::DelRange(args[1].clone(), args[2].clone()),
+        b"DELPREFIX" if args.len() == 2 => Command::DelPrefix(args[1].clone()),
+        b"EXPIRE" if args.len() == 3 => match integer(&args[2]) {
+            Some(secs) => Command::Expire(args[1].clone(), secs),
+            None => Command::Invalid("los segundos tienen que ser un entero".to_string()),
+        },
+        // SETEX k 10 hola mundo: igual que SET, en inline el resto de la línea es el valor
+        b"SETEX" if args.len() >= 3 => match integer(&args[2]) {
+            Some(secs) => with_value(args, 3, protocol, |key, value| {
+                Command::SetEx(key, secs, value)
+            }),
+            None => Command::Invalid("los segundos tienen que ser un entero".to_string()),
+        },
+        b"TTL" if args.len() == 2 => Command::Ttl(args[1].clone()),
+        b"PERSIST" if args.len() == 2 => Command::Persist(args[1].clone()),
+        b"MULTI" if args.len() == 1 => Command::Multi,
+        b"EXEC" if args.len() == 1 => Command::Exec,
+        b"DISCARD" if args.len() == 1 => Command::Discard,
+        b"BEGIN" if args.len() == 1 => Command::Begin,
+        b"COMMIT" if args.len() == 1 => Command::Commit,
+        b"ROLLBACK" if args.len() == 1 => Command::Rollback,
+        b"PING" => Command::Ping,
+        b"RETENTION" if args.len() >= 2 => retention(args),
+        b"SELECT" | b"USE" if args.len() == 2 => match text(&args[1]) {
+            Some(name) => Command::Select(name),
+            None => Command::Invalid("el nombre del keyspace tiene que ser texto".to_string()),
+        },
+        b"KEYSPACE" if args.len() >= 2 => keyspace(args),
+        b"COMPACT" => Command::Compact,
+        b"INFO" => Command::Info,
         _ => Command::Unknown,
     }
 }
+
+// Los comandos "llave valor": el valor arranca en args[from]. En INLINE "SET k hola mundo" guarda
+// "hola mundo" (el resto de la línea, para no obligar a entrecomillar desde netcat). Por RESP cada
+// argumento llega entero, así que tiene que ser exactamente uno: un cliente Redis que manda
+// "SET k v EX 10" recibe un error, no un valor "v EX 10".
+fn with_value(
+    args: &[Vec<u8>],
+    from: usize,
+    protocol: Protocol,
+    command: impl FnOnce(Vec<u8>, Vec<u8>) -> Command,
+) -> Command {
+    let value = match protocol {
+        Protocol::Inline if args.len() > from => args[from..].join(&b' '),
+        Protocol::Resp if args.len() == from + 1 => args[from].clone(),
+        _ => {
+            return Command::Invalid(format!(
+                "cantidad de argumentos incorrecta para '{}'",
+                String::from_utf8_lossy(&args[0]).to_lowercase()
+            ))
+        }
+    };
+    command(args[1].clone(), value)
+}
+
+// SET k v [IF-VERSION n]: con el sufijo solo escribe si la llave sigue en la versión n (0 = si no
+// existe). En inline el sufijo se busca al final de la línea, así que un valor que termine en
+// "IF-VERSION 3" tiene que ir entre comillas (por RESP va en un solo argumento y no hay dudas).
+fn set(args: &[Vec<u8>], protocol: Protocol) -> Command {
+    let n = args.len();
+    let if_version = |arg: &Vec<u8>| arg.eq_ignore_ascii_case(b"IF-VERSION");
+    if n >= 4 && if_version(&args[n - 2]) {
+        return match number(&args[n - 1]) {
+            Some(version) => with_value(&args[..n - 2], 2, protocol, |key, value| {
+                Command::SetIfVersion(key, value, version)
+            }),
+            None => Command::Invalid("la versión tiene que ser un entero".to_string()),
+        };
+    }
+    if n >= 3 && if_version(&args[n - 1]) {
+        return Command::Invalid("falta la versión después de IF-VERSION".to_string());
+    }
+    with_value(args, 2, protocol, Command::Set)
+}
+
+fn get_at(key: &[u8], time: &[u8]) -> Command {
+    match number(time) {
+        Some(time) => Command::GetAt(key.to_vec(), time),
+        None => {
+            Command::Invalid("el instante tiene que ser un entero (ms desde epoch)".to_string())
+        }
+    }
+}
+
+// Cuántas versiones devuelve HISTORY por página si no se pide otra cosa
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+// HISTORY key start end [LIMIT n] [REVERSE] [CURSOR c]   ("-" y "+" = sin límite de tiempo)
+fn history(args: &[Vec<u8>]) -> Command {
+    let start = match args[2].as_slice() {
+        b"-" => Some(0),
+        other => number(other),
+    };
+    let end = match args[3].as_slice() {
+        b"+" => Some(u64::MAX),
+        other => number(other),
+    };
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => {
+            return Command::Invalid("start/end tienen que ser enteros (ms), '-' o '+'".to_string())
+        }
+    };
+
+    let mut limit = DEFAULT_HISTORY_LIMIT;
+    let mut reverse = false;
+    let mut cursor = None;
+    let mut options = args[4..].iter();
+    while let Some(option) = options.next() {
+        match option.to_ascii_uppercase().as_slice() {
+            b"REVERSE" => reverse = true,
+            b"LIMIT" => match options.next().and_then(|n| number(n)) {
+                Some(n) if n > 0 => limit = n as usize,
+                _ => return Command::Invalid("LIMIT tiene que ser un entero positivo".to_string()),
+            },
+            // El cursor "0" es el que devuelve la última página: equivale a empezar de nuevo
+            b"CURSOR" => match options.next().and_then(|c| number(c)) {
+                Some(c) => cursor = (c > 0).then_some(c),
+                None => return Command::Invalid("cursor inválido".to_string()),
+            },
+            _ => return Command::Invalid("opción desconocida para HISTORY".to_string()),
+        }
+    }
+    Command::History {
+        key: args[1].clone(),
+        start,
+        end,
+        limit,
+        reverse,
+        cursor,
+    }
+}
+
+// Cuántas llaves devuelve SCAN por página si no se pide otra cosa (igual que Redis)
+pub const DEFAULT_SCAN_COUNT: usize = 10;
+
+// SCAN cursor [PREFIX p | MATCH p*] [START k] [END k] [COUNT n]   (cursor "0" = desde el principio)
+// REVSCAN igual, pero arranca por la última llave del rango
+fn scan(args: &[Vec<u8>], reverse: bool) -> Command {
+    let cursor = match args[1].as_slice() {
+        b"0" => None,
+        other => match from_hex(other) {
+            Some(cursor) => Some(cursor),
+            None => return Command::Invalid("cursor inválido".to_string()),
+        },
+    };
+
+    let mut prefix = Vec::new();
+    let mut start = Vec::new();
+    let mut end = None;
+    let mut count = DEFAULT_SCAN_COUNT;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => value,
+            None => return Command::Invalid("falta el valor de la opción".to_string()),
+        };
+        match option.to_ascii_uppercase().as_slice() {
+            b"PREFIX" => prefix = value.clone(),
+            // Solo los patrones "algo*" (un prefijo): no recorremos toda la base para filtrar
+            b"MATCH" => match value.strip_suffix(b"*") {
+                Some(p) if !p.iter().any(|b| matches!(b, b'*' | b'?' | b'[' | b'\\')) => {
+                    prefix = p.to_vec()
+                }
+                _ => return Command::Invalid("MATCH solo acepta prefijos ('user:*')".to_string()),
+            },
+            b"START" => start = value.clone(),
+            b"END" => end = Some(value.clone()),
+            b"COUNT" => match number(value) {
+                Some(n) if n > 0 => count = n as usize,
+                _ => return Command::Invalid("COUNT tiene que ser un entero positivo".to_string()),
+            },
+            _ => return Command::Invalid("opción desconocida para SCAN".to_string()),
+        }
+    }
+    Command::Scan {
+        cursor,
+        prefix,
+        start,
+        end,
+        count,
+        reverse,
+    }
+}
+
+// El cursor de SCAN viaja en hexadecimal: es opaco para el cliente y sobrevive a cualquier protocolo
+fn from_hex(arg: &[u8]) -> Option<Vec<u8>> {
+    if arg.is_empty() || !arg.len().is_multiple_of(2) {
+        return None;
+    }
+    arg.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn retention(args: &[Vec<u8>]) -> Command {
+    let sub = args[1].to_ascii_uppercase();
+    let command = match (sub.as_slice(), args.len()) {
+        (b"LIST", 2) => RetentionCommand::List,
+        (b"GET", 3) => RetentionCommand::Get(args[2].clone()),
+        (b"DEL", 3) => RetentionCommand::Del(args[2].clone()),
+        (b"SET", n) if n >= 4 => {
+            let mut max_age = None;
+            let mut max_versions = None;
+            let mut options = args[3..].iter();
+            while let Some(option) = options.next() {
+                match option.to_ascii_uppercase().as_slice() {
+                    // Sin límites: se guarda toda la historia
+                    b"FOREVER" => {}
+                    b"MAXAGE" => match options.next().and_then(|d| duration(d)) {
+                        Some(age) => max_age = Some(age),
+                        None => {
+                            return Command::Invalid(
+                                "MAXAGE espera una duración (500ms, 30s, 5m, 2h, 7d)".to_string(),
+                            )
+                        }
+                    },
+                    b"MAXVERSIONS" => match options.next().and_then(|n| number(n)) {
+                        Some(n) if n > 0 => max_versions = Some(n),
+                        _ => {
+                            return Command::Invalid(
+                                "MAXVERSIONS tiene que ser un entero positivo".to_string(),
+                            )
+                        }
+                    },
+                    _ => {
+                        return Command::Invalid(
+                            "opción desconocida para RETENTION SET".to_string(),
+                        )
+                    }
+                }
+            }
+            RetentionCommand::Set {
+                prefix: args[2].clone(),
+                max_age,
+                max_versions,
+            }
+        }
+        _ => {
+            return Command::Invalid(
+                "uso: RETENTION LIST | GET key | SET prefix ... | DEL prefix".to_string(),
+            )
+        }
+    };
+    Command::Retention(command)
+}
+
+fn keyspace(args: &[Vec<u8>]) -> Command {
+    let sub = args[1].to_ascii_uppercase();
+    let name = args.get(2).and_then(|name| text(name));
+    let command = match (sub.as_slice(), args.len(), name) {
+        (b"LIST", 2, _) => KeyspaceCommand::List,
+        (b"DROP", 3, Some(name)) => KeyspaceCommand::Drop(name),
+        (b"CREATE", n, Some(name)) if n >= 3 => {
+            let mut compaction = None;
+            let mut memtable_size = None;
+            let mut merge_operator = None;
+            let mut history_retention = None;
+            let mut options = args[3..].iter();
+            while let Some(option) = options.next() {
+                match option.to_ascii_uppercase().as_slice() {
+                    b"COMPACTION" => match options.next().and_then(|s| text(s)) {
+                        Some(strategy) => compaction = Some(strategy),
+                        None => {
+                            return Command::Invalid(
+                                "COMPACTION espera una estrategia (leveled, size-tiered, time-window)"
+                                    .to_string(),
+                            )
+                        }
+                    },
+                    b"MEMTABLE" => match options.next().and_then(|n| number(n)) {
+                        Some(bytes) if bytes > 0 => memtable_size = Some(bytes as usize),
+                        _ => {
+                            return Command::Invalid(
+                                "MEMTABLE tiene que ser un tamaño en bytes positivo".to_string(),
+                            )
+                        }
+                    },
+                    b"MERGE" => match options.next().and_then(|s| text(s)) {
+                        Some(operator) => merge_operator = Some(operator),
+                        None => {
+                            return Command::Invalid(
+                                "MERGE espera un operador (add, max, append)".to_string(),
+                            )
+                        }
+                    },
+                    b"HISTORY" => match options.next().and_then(|d| duration(d)) {
+                        Some(age) => history_retention = Some(age),
+                        None => {
+                            return Command::Invalid(
+                                "HISTORY espera una duración (500ms, 30s, 5m, 2h, 7d)".to_string(),
+                            )
+                        }
+                    },
+                    _ => {
+                        return Command::Invalid(
+                            "opción desconocida para KEYSPACE CREATE".to_string(),
+                        )
+                    }
+                }
+            }
+            KeyspaceCommand::Create {
+                name,
+                compaction,
+                memtable_size,
+                merge_operator,
+                history_retention,
+            }
+        }
+        _ => {
+            return Command::Invalid(
+                "uso: KEYSPACE LIST | CREATE name [COMPACTION s] [MEMTABLE bytes] [MERGE op] [HISTORY d] | DROP name"
+                    .to_string(),
+            )
+        }
+    };
+    Command::Keyspace(command)
+}
+
+// Nombres (de keyspaces, estrategias, operadores): texto, no bytes arbitrarios
+fn text(arg: &[u8]) -> Option<String> {
+    String::from_utf8(arg.to_vec()).ok()
+}
+
+// "500ms", "30s", "5m", "2h", "7d" (un número solo = milisegundos)
+pub fn duration(arg: &[u8]) -> Option<Duration> {
+    let text = std::str::from_utf8(arg).ok()?.to_ascii_lowercase();
+    let digits = text.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let amount: u64 = digits.parse().ok()?;
+    let millis = match &text[digits.len()..] {
+        "" | "ms" => amount,
+        "s" => amount.checked_mul(1000)?,
+        "m" => amount.checked_mul(60 * 1000)?,
+        "h" => amount.checked_mul(60 * 60 * 1000)?,
+        "d" => amount.checked_mul(24 * 60 * 60 * 1000)?,
+        _ => return None,
+    };
+    Some(Duration::from_millis(millis))
+}
+
+// Un entero con signo (segundos de EXPIRE/SETEX, incrementos de INCRBY)
+fn integer(arg: &[u8]) -> Option<i64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+// Un incremento de INCRBYFLOAT (nada de "inf" ni "nan")
+fn float(arg: &[u8]) -> Option<f64> {
+    std::str::from_utf8(arg)
+        .ok()?
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+// Un entero sin signo escrito en ASCII
+fn number(arg: &[u8]) -> Option<u64> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, read_request, Command, Protocol};
+
+    fn args(line: &str) -> Vec<Vec<u8>> {
+        line.split(' ').map(|arg| arg.as_bytes().to_vec()).collect()
+    }
+
+    // Lo que llega por el socket, pasado por el mismo camino que usa el servidor
+    fn parse_wire(raw: &[u8]) -> Command {
+        let (request, used) = read_request(raw).unwrap().expect("pedido incompleto");
+        assert_eq!(used, raw.len());
+        parse(&request.args, request.protocol)
+    }
+
+    #[test]
+    fn inline_values_take_the_rest_of_the_line() {
+        match parse_wire(b"SET saludo hola  mundo\r\n") {
+            Command::Set(key, value) => {
+                assert_eq!(key, b"saludo");
+                assert_eq!(value, b"hola mundo");
+            }
+            _ => panic!("tenía que ser un SET"),
+        }
+        match parse_wire(b"SETEX s 10 \"a\\x00b\" c\n") {
+            Command::SetEx(_, 10, value) => assert_eq!(value, b"a\x00b c"),
+            _ => panic!("tenía que ser un SETEX"),
+        }
+    }
+
+    #[test]
+    fn resp_values_are_exactly_one_argument() {
+        match parse_wire(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$7\r\nv \r\n\x00 x\r\n") {
+            Command::Set(_, value) => assert_eq!(value, b"v \r\n\x00 x"),
+            _ => panic!("tenía que ser un SET"),
+        }
+        // Lo que manda un cliente Redis con opciones que no entendemos: error, no un valor "v EX 10"
+        let redis = b"*5\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n$2\r\nEX\r\n$2\r\n10\r\n";
+        assert!(matches!(parse_wire(redis), Command::Invalid(_)));
+        for command in ["SETNX", "APPEND", "GETSET", "MERGE"] {
+            let extra = args(&format!("{} k a b", command));
+            assert!(matches!(parse(&extra, Protocol::Resp), Command::Invalid(_)));
+            assert!(!matches!(
+                parse(&extra, Protocol::Inline),
+                Command::Invalid(_)
+            ));
+        }
+        assert!(matches!(
+            parse(&args("SETEX k 10 a b"), Protocol::Resp),
+            Command::Invalid(_)
+        ));
+        assert!(matches!(
+            parse(&args("SET k"), Protocol::Resp),
+            Command::Invalid(_)
+        ));
+        assert!(matches!(
+            parse(&args("SET k"), Protocol::Inline),
+            Command::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn set_if_version() {
+        for protocol in [Protocol::Inline, Protocol::Resp] {
+            match parse(&args("SET leader node-1 IF-VERSION 0"), protocol) {
+                Command::SetIfVersion(key, value, version) => {
+                    assert_eq!(key, b"leader");
+                    assert_eq!(value, b"node-1");
+                    assert_eq!(version, 0);
+                }
+                _ => panic!("tenía que ser un SET condicional"),
+            }
+        }
+        // En inline el valor puede tener varias palabras; el sufijo es lo último de la línea
+        match parse(&args("SET note hola mundo if-version 42"), Protocol::Inline) {
+            Command::SetIfVersion(_, value, 42) => assert_eq!(value, b"hola mundo"),
+            _ => panic!("tenía que ser un SET condicional"),
+        }
+        for line in [
+            "SET note v IF-VERSION tres",
+            "SET note v IF-VERSION",
+            "SET note IF-VERSION 3",
+        ] {
+            assert!(matches!(
+                parse(&args(line), Protocol::Inline),
+                Command::Invalid(_)
+            ));
+        }
+        assert!(matches!(
+            parse(&args("SET note a b IF-VERSION 3"), Protocol::Resp),
+            Command::Invalid(_)
+        ));
+    }
+}
@@ -13,13 +13,16 @@ use std::sync::{Arc, RwLock};
 use std::thread;
 
 // Importamos el motor que acabamos de crear
-use crate::engine::Engine;
-use crate::parser::{self, Command}; // <---- IMPORTAMOS NUESTRO PARSER
+use crate::engine::{
+    now_millis, CasOutcome, CommitOutcome, Engine, Expected, GroupCommit, HistoryQuery, Keyspace,
+    KeyspaceSettings, Keyspaces, RetentionPolicy, ScanRange, Transaction, Ttl, WriteBatch,
+};
+use crate::parser::{self, Command, KeyspaceCommand, Protocol, RetentionCommand}; // <---- IMPORTAMOS NUESTRO PARSER
 
-// Creamos un tipo de dato público para que sea fácil de escribir
+// Creamos un tipo de dato público para que sea fácil de escribir (un keyspace)
 pub type Db = Arc<RwLock<Engine>>;
 
-pub fn start_server(db: Db) {
+pub fn start_server(keyspaces: Arc<Keyspaces>) {
     let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
     println!("🚀 CHRONOS SERVER LISTO Y ESCUCHANDO EN TCP 127.0.0.1:8080");
     println!("   Esperando conexiones entrantes...\n");
@@ -27,13 +30,13 @@ pub fn start_server(db: Db) {
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                let db_clone = Arc::clone(&db);
+                let keyspaces = Arc::clone(&keyspaces);
                 // Obtenemos la IP del cliente para nuestros logs
                 let peer_addr = stream.peer_addr().unwrap();
                 println!("   🟢 NUEVA CONEXIÓN: {}", peer_addr);
 
                 thread::spawn(move || {
-                    handle_client(stream, db_clone, peer_addr.to_string());
+                    handle_client(stream, keyspaces, peer_addr.to_string());
                 });
             }
             Err(e) => println!("   ❌ Error de conexión entrante: {}", e),
@@ -41,69 +44,747 @@ pub fn start_server(db: Db) {
     }
 }
 
+// Lo que el motor contesta, antes de traducirlo al dialecto del cliente
+enum Reply {
+    Status(&'static str),
+    Bulk(Vec<u8>),
+    Nil,
+    Integer(i64),
+    Error(String),
+    Array(Vec<Reply>),
+}
+
+impl Reply {
+    // INLINE: texto plano terminado en \n (como siempre). RESP: cada tipo con su prefijo y largo.
+    fn encode(&self, protocol: Protocol) -> Vec<u8> {
+        match (protocol, self) {
+            (Protocol::Inline, Reply::Status(s)) => format!("{}\n", s).into_bytes(),
+            (Protocol::Inline, Reply::Bulk(v)) => [v.as_slice(), b"\n"].concat(),
+            (Protocol::Inline, Reply::Nil) => b"NULL\n".to_vec(),
+            (Protocol::Inline, Reply::Integer(n)) => format!("{}\n", n).into_bytes(),
+            (Protocol::Inline, Reply::Error(e)) => format!("{}\n", e).into_bytes(),
+            // En inline cada elemento va en su propia línea
+            (Protocol::Inline, Reply::Array(items)) if items.is_empty() => b"(empty)\n".to_vec(),
+            (Protocol::Inline, Reply::Array(items)) => items
+                .iter()
+                .flat_map(|item| item.encode(protocol))
+                .collect(),
+            (Protocol::Resp, Reply::Status(s)) => format!("+{}\r\n", s).into_bytes(),
+            (Protocol::Resp, Reply::Bulk(v)) => {
+                [format!("${}\r\n", v.len()).as_bytes(), v, b"\r\n"].concat()
+            }
+            (Protocol::Resp, Reply::Nil) => b"$-1\r\n".to_vec(),
+            (Protocol::Resp, Reply::Integer(n)) => format!(":{}\r\n", n).into_bytes(),
+            (Protocol::Resp, Reply::Error(e)) => format!("-{}\r\n", e).into_bytes(),
+            (Protocol::Resp, Reply::Array(items)) => {
+                let mut out = format!("*{}\r\n", items.len()).into_bytes();
+                for item in items {
+                    out.extend(item.encode(protocol));
+                }
+                out
+            }
+        }
+    }
+}
+
 // Esta función es privada (no tiene pub) porque solo se usa dentro de este archivo
-fn handle_client(mut stream: TcpStream, db: Db, peer_addr: String) {
-    let mut buffer = [0; 512];
+fn handle_client(mut stream: TcpStream, keyspaces: Arc<Keyspaces>, peer_addr: String) {
+    // El group commit es el mismo para todos los keyspaces y toda la vida del servidor
+    let commit = keyspaces.group_commit();
+    // Bytes recibidos que todavía no forman un pedido completo
+    let mut pending: Vec<u8> = Vec::new();
+    // MULTI o BEGIN abiertos en esta conexión
+    let mut session = Session::default();
+    let mut buffer = [0; 4096];
     loop {
+        // 1. Atendemos TODOS los pedidos completos que ya tengamos (puede haber varios en un solo read)
+        loop {
+            let (request, used) = match parser::read_request(&pending) {
+                Ok(Some(found)) => found,
+                Ok(None) => break,
+                Err(e) => {
+                    // No sabemos dónde termina este pedido ni dónde empieza el próximo: cortamos
+                    let protocol = if pending.first() == Some(&b'*') {
+                        Protocol::Resp
+                    } else {
+                        Protocol::Inline
+                    };
+                    let reply = Reply::Error(format!("ERR_PROTOCOL {}", e));
+                    let _ = stream.write_all(&reply.encode(protocol));
+                    println!(
+                        "   🔴 DESCONECTADO (Protocolo inválido): {} -> {}",
+                        peer_addr, e
+                    );
+                    return;
+                }
+            };
+            pending.drain(..used);
+
+            // Una línea en blanco no es un pedido: la ignoramos sin contestar
+            if request.args.is_empty() {
+                continue;
+            }
+
+            // 2. EJECUTAMOS EL COMANDO TIPADO
+            let reply = dispatch(
+                &keyspaces,
+                &commit,
+                &mut session,
+                parser::parse(&request.args, request.protocol),
+            );
+            if stream.write_all(&reply.encode(request.protocol)).is_err() {
+                println!("   ⚠️ Error al enviar respuesta {}", peer_addr);
+                return;
+            }
+        }
+
+        // 3. Esperamos más bytes del cliente
         match stream.read(&mut buffer) {
             Ok(bytes_read) => {
                 // Si leemos 0 bytes, significa que el cliente cerró la conexión (EOF)
                 if bytes_read == 0 {
                     println!("   🔴 DESCONECTADO (Limpio): {}", peer_addr);
-                    break;
-                }
-
-                let raw_msg = String::from_utf8_lossy(&buffer[..bytes_read]);
-
-                // 1. LE PASAMOS EL TEXTO SUCIO A NUESTRO PARSER
-                let command = parser::parse(&raw_msg);
-
-                // 2. EJECUTAMOS EL COMANDO TIPADO
-                let response = match command {
-                    Command::Set(key, value) => {
-                        let mut engine = db.write().unwrap();
-                        match engine.set(&key, &value) {
-                            Ok(_) => "OK\n".to_string(),
-                            Err(e) => format!("ERR {}\n", e),
-                        }
-                    }
-                    Command::Del(key) => {
-                        let mut engine = db.write().unwrap();
-                        // Insertamos la lápida usando el mismo motor SET
-                        match engine.set(&key, "__TOMBSTONE__") {
-                            Ok(_) => "OK_DELETED\n".to_string(),
-                            Err(e) => format!("ERR {}\n", e),
-                        }
-                    }
-                    Command::Get(key) => {
-                        let engine = db.read().unwrap();
-                        match engine.get(&key) {
-                            Some(v) if v == "__TOMBSTONE__" => "NULL\n".to_string(), // Fingimos demencia
-                            Some(v) => format!("{}\n", v),
-                            None => "NULL\n".to_string(),
-                        }
-                    }
-                    Command::Compact => {
-                        let mut engine = db.write().unwrap();
-                        match engine.compact() {
-                            Ok(_) => "OK_COMPACTED\n".to_string(),
-                            Err(e) => format!("ERR_COMPACT {}\n", e),
-                        }
-                    }
-                    Command::Ping => "PONG\n".to_string(),
-                    Command::Unknown => "ERR_UNKNOWN_COMMAND\n".to_string(),
-                };
-
-                if stream.write_all(response.as_bytes()).is_err() {
-                    println!("   ⚠️ Error al enviar respuesta {}", peer_addr);
-                    break;
+                    return;
                 }
+                pending.extend_from_slice(&buffer[..bytes_read]);
             }
             Err(e) => {
                 // Caso 2: Desconexión violenta (Ctrl+C, corte de internet, etc...)
                 println!("   🔴 DESCONECTADO (Forzado): {} -> {}", peer_addr, e);
-                break;
+                return;
+            }
+        }
+    }
+}
+
+// Lo que una conexión tiene abierto entre pedido y pedido
+#[derive(Default)]
+struct Session {
+    // El keyspace elegido con SELECT/USE (0 = el de por defecto)
+    keyspace: u32,
+    // Entre MULTI y EXEC: las escrituras que esperan para aplicarse juntas
+    multi: Option<Multi>,
+    // Entre BEGIN y COMMIT/ROLLBACK (si la conexión se corta, se descarta sola)
+    tx: Option<Transaction>,
+}
+
+// Un MULTI abierto en esta conexión
+struct Multi {
+    queued: Vec<Command>,
+    // Algún comando no se pudo encolar: EXEC descarta el lote entero (como Redis)
+    aborted: bool,
+}
+
+// Lo que depende del estado de la conexión (keyspace, MULTI, BEGIN); el resto va directo a execute
+fn dispatch(
+    keyspaces: &Keyspaces,
+    commit: &GroupCommit,
+    session: &mut Session,
+    command: Command,
+) -> Reply {
+    // Entre MULTI y EXEC todo se encola
+    if let Some(queue) = session.multi.as_mut() {
+        if !matches!(command, Command::Multi | Command::Exec | Command::Discard) {
+            return enqueue(queue, command);
+        }
+    }
+    // La transacción vive en un keyspace: no se cambia de keyspace en el medio
+    let command = match command {
+        Command::Select(_) | Command::Keyspace(_) if session.tx.is_some() => {
+            return Reply::Error(
+                "ERR comando no permitido dentro de una transacción (primero COMMIT o ROLLBACK)"
+                    .to_string(),
+            )
+        }
+        Command::Select(name) => {
+            return match keyspaces.find(&name) {
+                Some(keyspace) => {
+                    session.keyspace = keyspace.id;
+                    Reply::Status("OK")
+                }
+                None => Reply::Error(format!("ERR el keyspace '{}' no existe", name)),
+            }
+        }
+        Command::Keyspace(command) => return keyspace_command(keyspaces, command),
+        // No tocan datos: se contestan igual en cualquier keyspace (y adentro de BEGIN)
+        Command::Ping => return Reply::Status("PONG"),
+        Command::Invalid(reason) => return Reply::Error(format!("ERR_SYNTAX {}", reason)),
+        Command::Unknown => return Reply::Error("ERR_UNKNOWN_COMMAND".to_string()),
+        command => command,
+    };
+    let keyspace = match keyspaces.get(session.keyspace) {
+        Some(keyspace) => keyspace,
+        None => {
+            return Reply::Error(
+                "ERR el keyspace elegido fue borrado (hacer SELECT de otro)".to_string(),
+            )
+        }
+    };
+    let db = &keyspace.db;
+    match command {
+        Command::Multi if session.tx.is_some() => {
+            Reply::Error("ERR MULTI no se permite dentro de una transacción".to_string())
+        }
+        Command::Multi if session.multi.is_some() => {
+            Reply::Error("ERR MULTI no se puede anidar".to_string())
+        }
+        Command::Multi => {
+            session.multi = Some(Multi {
+                queued: Vec::new(),
+                aborted: false,
+            });
+            Reply::Status("OK")
+        }
+        Command::Exec => match session.multi.take() {
+            Some(queue) if queue.aborted => Reply::Error(
+                "EXECABORT la transacción se descartó por errores anteriores".to_string(),
+            ),
+            Some(queue) => exec(db, commit, queue.queued),
+            None => Reply::Error("ERR EXEC sin MULTI".to_string()),
+        },
+        Command::Discard => match session.multi.take() {
+            Some(_) => Reply::Status("OK"),
+            None => Reply::Error("ERR DISCARD sin MULTI".to_string()),
+        },
+        Command::Begin if session.tx.is_some() => {
+            Reply::Error("ERR ya hay una transacción en curso".to_string())
+        }
+        Command::Begin => {
+            session.tx = Some(db.read().unwrap().begin());
+            Reply::Status("OK")
+        }
+        Command::Commit => match session.tx.take() {
+            Some(tx) => commit_transaction(db, commit, tx),
+            None => Reply::Error("ERR COMMIT sin BEGIN".to_string()),
+        },
+        Command::Rollback => match session.tx.take() {
+            Some(_) => Reply::Status("OK"),
+            None => Reply::Error("ERR ROLLBACK sin BEGIN".to_string()),
+        },
+        Command::Info if session.tx.is_none() => info(keyspaces, &keyspace),
+        command => match session.tx.as_mut() {
+            Some(tx) => in_transaction(db, tx, command),
+            None => execute(db, commit, command),
+        },
+    }
+}
+
+// KEYSPACE LIST | CREATE | DROP (no dependen del keyspace elegido)
+fn keyspace_command(keyspaces: &Keyspaces, command: KeyspaceCommand) -> Reply {
+    match command {
+        // [[nombre, configuración], ...]
+        KeyspaceCommand::List => Reply::Array(
+            keyspaces
+                .list()
+                .iter()
+                .map(|keyspace| {
+                    let settings = keyspace.db.read().unwrap().describe_settings();
+                    Reply::Array(vec![
+                        Reply::Bulk(keyspace.name.clone().into_bytes()),
+                        Reply::Bulk(format!("id={} {}", keyspace.id, settings).into_bytes()),
+                    ])
+                })
+                .collect(),
+        ),
+        KeyspaceCommand::Create {
+            name,
+            compaction,
+            memtable_size,
+            merge_operator,
+            history_retention,
+        } => {
+            let settings = KeyspaceSettings {
+                compaction,
+                memtable_size,
+                merge_operator,
+                history_retention,
+            };
+            match keyspaces.create(&name, settings) {
+                Ok(_) => Reply::Status("OK"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        KeyspaceCommand::Drop(name) => match keyspaces.remove(&name) {
+            Ok(()) => Reply::Status("OK"),
+            Err(e) => Reply::Error(format!("ERR {}", e)),
+        },
+    }
+}
+
+// INFO del keyspace elegido, con su nombre y cuántos hay
+fn info(keyspaces: &Keyspaces, keyspace: &Keyspace) -> Reply {
+    let mut lines = vec![
+        format!("keyspace:{}", keyspace.name),
+        format!("keyspaces:{}", keyspaces.list().len()),
+    ];
+    lines.extend(
+        keyspace
+            .db
+            .read()
+            .unwrap()
+            .info()
+            .iter()
+            .map(|(k, v)| format!("{}:{}", k, v)),
+    );
+    Reply::Bulk(lines.join("\n").into_bytes())
+}
+
+// Adentro de BEGIN: las lecturas ven la foto + lo propio, las escrituras quedan en el buffer
+fn in_transaction(db: &Db, tx: &mut Transaction, command: Command) -> Reply {
+    match command {
+        Command::Get(key) => match tx.get(&db.read().unwrap(), &key) {
+            Ok(Some(v)) => Reply::Bulk(v),
+            Ok(None) => Reply::Nil,
+            Err(e) => Reply::Error(format!("ERR {}", e)),
+        },
+        Command::MGet(keys) => {
+            let engine = db.read().unwrap();
+            let mut values = Vec::with_capacity(keys.len());
+            for key in &keys {
+                match tx.get(&engine, key) {
+                    Ok(Some(v)) => values.push(Reply::Bulk(v)),
+                    Ok(None) => values.push(Reply::Nil),
+                    Err(e) => return Reply::Error(format!("ERR {}", e)),
+                }
+            }
+            Reply::Array(values)
+        }
+        Command::Set(key, value) => {
+            tx.put(&key, &value);
+            Reply::Status("OK")
+        }
+        Command::SetEx(_, secs, _) if secs <= 0 => {
+            Reply::Error("ERR tiempo de vencimiento inválido para 'setex'".to_string())
+        }
+        Command::SetEx(key, secs, value) => {
+            tx.put_with_deadline(&key, &value, deadline(secs));
+            Reply::Status("OK")
+        }
+        Command::Del(key) => {
+            tx.delete(&key);
+            Reply::Status("OK_DELETED")
+        }
+        _ => Reply::Error(
+            "ERR comando no permitido dentro de una transacción (primero COMMIT o ROLLBACK)"
+                .to_string(),
+        ),
+    }
+}
+
+fn commit_transaction(db: &Db, commit: &GroupCommit, tx: Transaction) -> Reply {
+    let outcome = db.write().unwrap().commit(tx);
+    match outcome {
+        Ok(CommitOutcome::Committed(seq)) => match commit.wait(seq) {
+            Ok(_) => Reply::Status("OK"),
+            Err(e) => Reply::Error(format!("ERR {}", e)),
+        },
+        Ok(CommitOutcome::Conflict(key)) => Reply::Error(format!(
+            "ERR_CONFLICT la llave '{}' cambió después de BEGIN; la transacción se deshizo",
+            String::from_utf8_lossy(&key)
+        )),
+        Err(e) => Reply::Error(format!("ERR {}", e)),
+    }
+}
+
+// Adentro de MULTI solo se encolan escrituras: son las que viajan juntas en un registro del WAL
+fn enqueue(queue: &mut Multi, command: Command) -> Reply {
+    let rejected = match &command {
+        Command::Set(..) | Command::Del(_) => None,
+        Command::SetEx(_, secs, _) if *secs > 0 => None,
+        Command::SetEx(..) => Some("ERR tiempo de vencimiento inválido para 'setex'".to_string()),
+        Command::Invalid(reason) => Some(format!("ERR_SYNTAX {}", reason)),
+        Command::Unknown => Some("ERR_UNKNOWN_COMMAND".to_string()),
+        _ => Some("ERR dentro de MULTI solo se encolan SET, SETEX y DEL".to_string()),
+    };
+    match rejected {
+        Some(error) => {
+            queue.aborted = true;
+            Reply::Error(error)
+        }
+        None => {
+            queue.queued.push(command);
+            Reply::Status("QUEUED")
+        }
+    }
+}
+
+// EXEC: el lote entero en UN registro del WAL, con un solo fsync que esperar
+fn exec(db: &Db, commit: &GroupCommit, queued: Vec<Command>) -> Reply {
+    let mut batch = WriteBatch::new();
+    let mut replies = Vec::with_capacity(queued.len());
+    for command in queued {
+        match command {
+            Command::Set(key, value) => {
+                batch.put(&key, &value);
+                replies.push(Reply::Status("OK"));
+            }
+            // Como en Redis, el vencimiento se cuenta desde EXEC y no desde que se encoló
+            Command::SetEx(key, secs, value) => {
+                batch.put_with_deadline(&key, &value, deadline(secs));
+                replies.push(Reply::Status("OK"));
+            }
+            Command::Del(key) => {
+                batch.delete(&key);
+                replies.push(Reply::Status("OK_DELETED"));
+            }
+            _ => unreachable!("enqueue() solo encola escrituras"),
+        }
+    }
+    let seq = db.write().unwrap().write_batch(batch);
+    match seq.and_then(|seq| commit.wait(seq)) {
+        Ok(_) => Reply::Array(replies),
+        Err(e) => Reply::Error(format!("ERR {}", e)),
+    }
+}
+
+fn execute(db: &Db, commit: &GroupCommit, command: Command) -> Reply {
+    match command {
+        Command::Set(key, value) => {
+            // El candado se suelta ANTES de esperar el fsync: así otros escritores se suman al mismo
+            let seq = db.write().unwrap().set(&key, &value);
+            match seq.and_then(|seq| commit.wait(seq)) {
+                Ok(_) => Reply::Status("OK"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::Del(key) => {
+            let seq = db.write().unwrap().delete(&key);
+            match seq.and_then(|seq| commit.wait(seq)) {
+                Ok(_) => Reply::Status("OK_DELETED"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::DelRange(start, end) => {
+            let seq = db.write().unwrap().delete_range(&start, Some(&end));
+            match seq.and_then(|seq| commit.wait(seq)) {
+                Ok(_) => Reply::Status("OK_DELETED"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::DelPrefix(prefix) => {
+            let seq = db.write().unwrap().delete_prefix(&prefix);
+            match seq.and_then(|seq| commit.wait(seq)) {
+                Ok(_) => Reply::Status("OK_DELETED"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::SetNx(key, value) => {
+            // Como en Redis: 1 si la escribió, 0 si ya existía
+            match conditional_set(db, commit, &key, Expected::Version(0), &value) {
+                Ok(CasOutcome::Applied(_)) => Reply::Integer(1),
+                Ok(CasOutcome::Mismatch(_)) => Reply::Integer(0),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::SetIfVersion(key, value, version) => cas_reply(conditional_set(
+            db,
+            commit,
+            &key,
+            Expected::Version(version),
+            &value,
+        )),
+        Command::Cas(key, expected, value) => cas_reply(conditional_set(
+            db,
+            commit,
+            &key,
+            Expected::Value(expected),
+            &value,
+        )),
+        Command::IncrBy(key, delta) => {
+            let result = db.write().unwrap().incr_by(&key, delta);
+            match result.and_then(|(seq, value)| commit.wait(seq).map(|_| value)) {
+                Ok(value) => Reply::Integer(value),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::IncrByFloat(key, delta) => {
+            let result = db.write().unwrap().incr_by_float(&key, delta);
+            match result.and_then(|(seq, value)| commit.wait(seq).map(|_| value)) {
+                Ok(value) => Reply::Bulk(value),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::Append(key, suffix) => {
+            let result = db.write().unwrap().append(&key, &suffix);
+            match result.and_then(|(seq, len)| commit.wait(seq).map(|_| len)) {
+                Ok(len) => Reply::Integer(len as i64),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::GetSet(key, value) => {
+            let result = db.write().unwrap().get_set(&key, &value);
+            match result.and_then(|(seq, old)| commit.wait(seq).map(|_| old)) {
+                Ok(Some(old)) => Reply::Bulk(old),
+                Ok(None) => Reply::Nil,
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::Merge(key, operand) => {
+            let seq = db.write().unwrap().merge(&key, &operand);
+            match seq.and_then(|seq| commit.wait(seq)) {
+                Ok(_) => Reply::Status("OK"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::Version(key) => match db.read().unwrap().version(&key) {
+            Ok(version) => Reply::Integer(version as i64),
+            Err(e) => Reply::Error(format!("ERR {}", e)),
+        },
+        Command::Expire(key, secs) => {
+            let deadline = deadline(secs);
+            let seq = db.write().unwrap().expire_at(&key, deadline);
+            match seq {
+                Ok(Some(seq)) => match commit.wait(seq) {
+                    Ok(_) => Reply::Integer(1),
+                    Err(e) => Reply::Error(format!("ERR {}", e)),
+                },
+                Ok(None) => Reply::Integer(0),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::SetEx(key, secs, value) => {
+            if secs <= 0 {
+                return Reply::Error("ERR tiempo de vencimiento inválido para 'setex'".to_string());
+            }
+            let seq = db
+                .write()
+                .unwrap()
+                .set_with_deadline(&key, &value, deadline(secs));
+            match seq.and_then(|seq| commit.wait(seq)) {
+                Ok(_) => Reply::Status("OK"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::Ttl(key) => match db.read().unwrap().ttl(&key) {
+            Ok(Ttl::Missing) => Reply::Integer(-2),
+            Ok(Ttl::Persistent) => Reply::Integer(-1),
+            // Redondeado al segundo más cercano, como Redis
+            Ok(Ttl::ExpiresIn(left)) => Reply::Integer(((left.as_millis() + 500) / 1000) as i64),
+            Err(e) => Reply::Error(format!("ERR {}", e)),
+        },
+        Command::Persist(key) => {
+            let seq = db.write().unwrap().persist(&key);
+            match seq {
+                Ok(Some(seq)) => match commit.wait(seq) {
+                    Ok(_) => Reply::Integer(1),
+                    Err(e) => Reply::Error(format!("ERR {}", e)),
+                },
+                Ok(None) => Reply::Integer(0),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::Get(key) => {
+            let engine = db.read().unwrap();
+            match engine.get(&key) {
+                Ok(Some(v)) => Reply::Bulk(v),
+                Ok(None) => Reply::Nil,
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::GetAt(key, time) => {
+            let engine = db.read().unwrap();
+            match engine.get_at_time(&key, time) {
+                Ok(Some(v)) => Reply::Bulk(v),
+                Ok(None) => Reply::Nil,
+                Err(e) => Reply::Error(format!("ERR {}", e)),
+            }
+        }
+        Command::History {
+            key,
+            start,
+            end,
+            limit,
+            reverse,
+            cursor,
+        } => {
+            let query = HistoryQuery {
+                start,
+                end,
+                limit,
+                reverse,
+                cursor,
+            };
+            let page = match db.read().unwrap().history(&key, &query) {
+                Ok(page) => page,
+                Err(e) => return Reply::Error(format!("ERR {}", e)),
+            };
+            // Igual que SCAN en Redis: [cursor, [[momento, valor], ...]] y cursor "0" = no hay más
+            let entries = page
+                .entries
+                .into_iter()
+                .map(|e| {
+                    let value = e.value.map_or(Reply::Nil, Reply::Bulk);
+                    Reply::Array(vec![Reply::Integer(e.time as i64), value])
+                })
+                .collect();
+            let cursor = page.cursor.unwrap_or(0).to_string().into_bytes();
+            Reply::Array(vec![Reply::Bulk(cursor), Reply::Array(entries)])
+        }
+        Command::Scan {
+            cursor,
+            prefix,
+            start,
+            end,
+            count,
+            reverse,
+        } => {
+            // El cursor es la llave desde la que seguir
+            let range = ScanRange { start, end, prefix };
+            let range = match cursor {
+                Some(cursor) => range.resume(cursor, reverse),
+                None => range,
+            };
+            let engine = db.read().unwrap();
+            let page = if reverse {
+                engine.rev_scan(&range, count)
+            } else {
+                engine.scan(&range, count)
+            };
+            drop(engine);
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => return Reply::Error(format!("ERR {}", e)),
+            };
+            // Como HISTORY: [cursor, [[llave, valor], ...]] y cursor "0" = no hay más
+            let entries = page
+                .entries
+                .into_iter()
+                .map(|(key, value)| Reply::Array(vec![Reply::Bulk(key), Reply::Bulk(value)]))
+                .collect();
+            let cursor = page.cursor.map_or_else(|| "0".to_string(), |c| to_hex(&c));
+            Reply::Array(vec![
+                Reply::Bulk(cursor.into_bytes()),
+                Reply::Array(entries),
+            ])
+        }
+        Command::MGet(keys) => {
+            // Tomamos la foto con el candado y leemos cada llave por separado: los escritores
+            // pueden colarse entre medio, pero ninguna de sus escrituras se ve en la respuesta
+            let snapshot = db.read().unwrap().snapshot();
+            let mut values = Vec::with_capacity(keys.len());
+            for key in &keys {
+                match db.read().unwrap().get_snapshot(key, &snapshot) {
+                    Ok(Some(v)) => values.push(Reply::Bulk(v)),
+                    Ok(None) => values.push(Reply::Nil),
+                    Err(e) => return Reply::Error(format!("ERR {}", e)),
+                }
+            }
+            Reply::Array(values)
+        }
+        Command::Retention(command) => retention(db, command),
+        Command::Compact => {
+            // Con el candado solo volcamos la memtable; la reescritura la hace el hilo de fondo
+            // y acá esperamos SIN candado (las demás conexiones siguen leyendo y escribiendo)
+            println!("   🧹 Iniciando Compactación (Garbage Colecction)...");
+            let compactor = {
+                let mut engine = db.write().unwrap();
+                engine.flush().map(|_| engine.compactor())
+            };
+            let result = match compactor {
+                Ok(Some(compactor)) => compactor.compact_and_wait(),
+                Ok(None) => db.write().unwrap().compact(),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(_) => Reply::Status("OK_COMPACTED"),
+                Err(e) => Reply::Error(format!("ERR_COMPACT {}", e)),
+            }
+        }
+        Command::Multi
+        | Command::Exec
+        | Command::Discard
+        | Command::Begin
+        | Command::Commit
+        | Command::Rollback
+        | Command::Select(_)
+        | Command::Keyspace(_)
+        | Command::Info
+        | Command::Ping
+        | Command::Invalid(_)
+        | Command::Unknown => unreachable!("los comandos de sesión se resuelven en dispatch()"),
+    }
+}
+
+// Compara y escribe con el candado tomado; el fsync se espera afuera (como SET)
+fn conditional_set(
+    db: &Db,
+    commit: &GroupCommit,
+    key: &[u8],
+    expected: Expected,
+    value: &[u8],
+) -> std::io::Result<CasOutcome> {
+    let outcome = db.write().unwrap().compare_and_set(key, &expected, value)?;
+    if let CasOutcome::Applied(version) = outcome {
+        commit.wait(version)?;
+    }
+    Ok(outcome)
+}
+
+// [1, versión nueva] si se escribió; [0, versión actual] si no (0 = la llave no existe)
+fn cas_reply(outcome: std::io::Result<CasOutcome>) -> Reply {
+    let (applied, version) = match outcome {
+        Ok(CasOutcome::Applied(version)) => (1, version),
+        Ok(CasOutcome::Mismatch(version)) => (0, version),
+        Err(e) => return Reply::Error(format!("ERR {}", e)),
+    };
+    Reply::Array(vec![
+        Reply::Integer(applied),
+        Reply::Integer(version as i64),
+    ])
+}
+
+// El cursor de SCAN (ver parser::from_hex)
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// De "dentro de N segundos" a un instante absoluto (ms desde epoch)
+fn deadline(secs: i64) -> u64 {
+    now_millis().saturating_add_signed(secs.saturating_mul(1000))
+}
+
+fn retention(db: &Db, command: RetentionCommand) -> Reply {
+    match command {
+        // [[prefijo, política], ...] y al final la general
+        RetentionCommand::List => {
+            let engine = db.read().unwrap();
+            let mut rows: Vec<Reply> = engine
+                .retention_rules()
+                .into_iter()
+                .map(|(prefix, policy)| {
+                    Reply::Array(vec![
+                        Reply::Bulk(prefix),
+                        Reply::Bulk(policy.describe().into_bytes()),
+                    ])
+                })
+                .collect();
+            rows.push(Reply::Array(vec![
+                Reply::Bulk(b"(default)".to_vec()),
+                Reply::Bulk(engine.default_retention().describe().into_bytes()),
+            ]));
+            Reply::Array(rows)
+        }
+        RetentionCommand::Get(key) => {
+            let policy = db.read().unwrap().retention_for(&key);
+            Reply::Bulk(policy.describe().into_bytes())
+        }
+        RetentionCommand::Set {
+            prefix,
+            max_age,
+            max_versions,
+        } => {
+            let policy = RetentionPolicy {
+                max_age,
+                max_versions,
+            };
+            match db.write().unwrap().set_retention(&prefix, policy) {
+                Ok(()) => Reply::Status("OK"),
+                Err(e) => Reply::Error(format!("ERR {}", e)),
             }
         }
+        RetentionCommand::Del(prefix) => match db.write().unwrap().remove_retention(&prefix) {
+            Ok(removed) => Reply::Integer(removed as i64),
+            Err(e) => Reply::Error(format!("ERR {}", e)),
+        },
     }
 }